@@ -1,9 +1,7 @@
-use resymo_agent::config::Config;
-
-fn main() -> anyhow::Result<()> {
-    let schema = schemars::schema_for!(Config);
-    let path = "deploy/config/schema.json";
+use resymo_agent::{config, hub};
+use schemars::schema::RootSchema;
 
+fn write(path: &str, schema: RootSchema) -> anyhow::Result<()> {
     let file = std::fs::File::create(path)?;
     serde_json::to_writer_pretty(file, &schema)?;
 
@@ -11,3 +9,16 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn main() -> anyhow::Result<()> {
+    write(
+        "deploy/config/schema.json",
+        schemars::schema_for!(config::Config),
+    )?;
+    write(
+        "deploy/config/hub-schema.json",
+        schemars::schema_for!(hub::Config),
+    )?;
+
+    Ok(())
+}
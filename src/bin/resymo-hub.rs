@@ -0,0 +1,102 @@
+use anyhow::Context;
+use clap::Parser;
+use resymo_agent::hub::{self, Config};
+use std::{path::PathBuf, process::ExitCode};
+use tokio::signal;
+
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+
+const CONFIG_FILE: &str = "resymo/hub.yaml";
+
+#[derive(Clone, Debug, clap::Parser)]
+#[command(
+    version,
+    about = "Scrapes many agents, and serves a combined dashboard",
+    author
+)]
+pub struct Cli {
+    /// Be quiet
+    #[arg(short, long, env)]
+    quiet: bool,
+
+    /// Be more verbose
+    #[arg(short, long, env, conflicts_with = "quiet", action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Path to the configuration file
+    #[arg(short, long, env, default_value = config_file())]
+    config: PathBuf,
+}
+
+fn config_file() -> String {
+    PathBuf::from("/etc")
+        .join(CONFIG_FILE)
+        .display()
+        .to_string()
+}
+
+fn init_logger(cli: &Cli) {
+    if std::env::var("RUST_LOG").is_ok() {
+        // if we have an external configuration, use it
+        env_logger::init();
+        return;
+    }
+
+    let mut logger = env_logger::builder();
+
+    let filters = match (cli.verbose, cli.quiet) {
+        // quiet overrides verbose
+        (_, true) => "error,resymo_agent=warn",
+        // increase verbosity
+        (0, false) => "warn,resymo_agent=info",
+        (1, false) => "info,resymo_agent=debug",
+        (2, false) => "debug",
+        (3, false) => "debug,resymo_agent=trace",
+        (_, false) => "trace",
+    };
+
+    logger.parse_filters(filters).init();
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    init_logger(&cli);
+
+    let config: Config = serde_yaml::from_reader(
+        std::fs::File::open(&cli.config)
+            .with_context(|| format!("Reading configuration file: '{}'", cli.config.display()))?,
+    )?;
+
+    log::info!("Starting hub");
+
+    #[cfg(unix)]
+    let mut terminate = signal(SignalKind::terminate())?;
+    #[cfg(unix)]
+    let terminated = terminate.recv();
+    #[cfg(not(unix))]
+    let terminated = std::future::pending::<Option<()>>();
+
+    tokio::select! {
+        result = hub::run(config) => result?,
+        result = signal::ctrl_c() => result.context("termination failed")?,
+        _ = terminated => {}
+    }
+
+    log::info!("Exiting hub");
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Cli;
+
+    #[test]
+    fn verify_cli() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+}
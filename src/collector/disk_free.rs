@@ -1,7 +1,9 @@
 //! Disk-free collector
 
+use crate::common::homeassistant::{Entity, EntityCategory};
 use async_trait::async_trait;
 use homeassistant_agent::model::{Discovery, SensorClass, StateClass};
+use schemars::{gen::SchemaGenerator, schema::Schema};
 use serde_json::Value;
 use std::collections::HashMap;
 use sysinfo::Disks;
@@ -26,6 +28,8 @@ impl super::Collector for Collector {
                     free: disk.available_space(),
                     total: disk.total_space(),
                     usage,
+                    mount_point: Some(disk.mount_point().to_string_lossy().to_string()),
+                    file_system: Some(disk.file_system().to_string_lossy().to_string()),
                 },
             );
         }
@@ -33,7 +37,11 @@ impl super::Collector for Collector {
         Ok(serde_json::to_value(Status { disks: result })?)
     }
 
-    fn describe_ha(&self) -> Vec<Discovery> {
+    fn schema(&self, gen: &mut SchemaGenerator) -> Option<Schema> {
+        Some(gen.subschema_for::<Status>())
+    }
+
+    fn describe_ha(&self) -> Vec<Entity> {
         let mut result = vec![];
         let disks = Disks::new_with_refreshed_list();
 
@@ -42,7 +50,7 @@ impl super::Collector for Collector {
             let id_name = display_name.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
             let source_name = disk.name().to_string_lossy();
 
-            result.push(Discovery {
+            result.push(Entity::from(Discovery {
                 unique_id: Some(format!("disk_{id_name}_free")),
                 name: Some(format!("Disk free {display_name}")),
                 state_class: Some(StateClass::Measurement),
@@ -52,19 +60,23 @@ impl super::Collector for Collector {
                 )),
                 unit_of_measurement: Some("B".to_string()),
                 ..Default::default()
-            });
-            result.push(Discovery {
-                unique_id: Some(format!("disk_{id_name}_total")),
-                name: Some(format!("Disk total {display_name}")),
-                state_class: Some(StateClass::Measurement),
-                value_template: Some(format!(
-                    r#"{{{{ value_json.disks['{source_name}'].total }}}}"#
-                )),
-                device_class: Some(SensorClass::DataSize.as_ref().to_string()),
-                unit_of_measurement: Some("B".to_string()),
-                ..Default::default()
-            });
-            result.push(Discovery {
+            }));
+            result.push(
+                Entity::from(Discovery {
+                    unique_id: Some(format!("disk_{id_name}_total")),
+                    name: Some(format!("Disk total {display_name}")),
+                    state_class: Some(StateClass::Measurement),
+                    value_template: Some(format!(
+                        r#"{{{{ value_json.disks['{source_name}'].total }}}}"#
+                    )),
+                    device_class: Some(SensorClass::DataSize.as_ref().to_string()),
+                    unit_of_measurement: Some("B".to_string()),
+                    ..Default::default()
+                })
+                .category(EntityCategory::Diagnostic)
+                .enabled(false),
+            );
+            result.push(Entity::from(Discovery {
                 unique_id: Some(format!("disk_{id_name}_usage")),
                 name: Some(format!("Disk usage {display_name}")),
                 state_class: Some(StateClass::Measurement),
@@ -73,22 +85,33 @@ impl super::Collector for Collector {
                 )),
                 unit_of_measurement: Some("%".to_string()),
                 ..Default::default()
-            });
+            }));
         }
 
         result
     }
 }
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(rename = "DiskFreeStatus")]
 pub struct Status {
+    /// Disks, by device name
     #[serde(default)]
     pub disks: HashMap<String, DiskStatus>,
 }
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct DiskStatus {
+    /// Total space, in bytes
     pub total: u64,
+    /// Free space, in bytes
     pub free: u64,
+    /// Used space, as fraction of the total space (0…1)
     pub usage: f64,
+    /// Where the disk is mounted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount_point: Option<String>,
+    /// The type of the file system, e.g. `ext4`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_system: Option<String>,
 }
@@ -1,3 +1,4 @@
+use crate::common::homeassistant::Entity;
 use crate::config::CommonCollector;
 use crate::utils::is_default;
 use anyhow::{anyhow, bail};
@@ -189,7 +190,7 @@ impl super::Collector for Collector {
         self.inner.lock().await.run().await
     }
 
-    fn describe_ha(&self) -> Vec<Discovery> {
-        self.descriptor.clone()
+    fn describe_ha(&self) -> Vec<Entity> {
+        self.descriptor.iter().cloned().map(Entity::from).collect()
     }
 }
@@ -1,16 +1,22 @@
 //! Load average collector
 
+use crate::common::homeassistant::Entity;
 use async_trait::async_trait;
 use homeassistant_agent::model::{Discovery, StateClass};
+use schemars::{gen::SchemaGenerator, schema::Schema};
 use serde_json::Value;
 use sysinfo::{LoadAvg, System};
 
 pub struct Collector;
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(rename = "LoadAvgStatus")]
 pub struct Status {
+    /// Load average over the last minute
     pub one: f64,
+    /// Load average over the last five minutes
     pub five: f64,
+    /// Load average over the last fifteen minutes
     pub fifteen: f64,
 }
 
@@ -21,29 +27,33 @@ impl super::Collector for Collector {
         Ok(serde_json::to_value(Status { one, five, fifteen })?)
     }
 
-    fn describe_ha(&self) -> Vec<Discovery> {
+    fn schema(&self, gen: &mut SchemaGenerator) -> Option<Schema> {
+        Some(gen.subschema_for::<Status>())
+    }
+
+    fn describe_ha(&self) -> Vec<Entity> {
         vec![
-            Discovery {
+            Entity::from(Discovery {
                 unique_id: Some("loadavg_1".to_string()),
                 name: Some("Load Average 1m".to_string()),
                 state_class: Some(StateClass::Measurement),
                 value_template: Some("{{ value_json.one }}".to_string()),
                 ..Default::default()
-            },
-            Discovery {
+            }),
+            Entity::from(Discovery {
                 unique_id: Some("loadavg_5".to_string()),
                 name: Some("Load Average 5m".to_string()),
                 state_class: Some(StateClass::Measurement),
                 value_template: Some("{{ value_json.five }}".to_string()),
                 ..Default::default()
-            },
-            Discovery {
+            }),
+            Entity::from(Discovery {
                 unique_id: Some("loadavg_15".to_string()),
                 name: Some("Load Average 15m".to_string()),
                 state_class: Some(StateClass::Measurement),
                 value_template: Some("{{ value_json.fifteen }}".to_string()),
                 ..Default::default()
-            },
+            }),
         ]
     }
 }
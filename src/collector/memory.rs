@@ -1,17 +1,24 @@
 //! Memory collector
 
+use crate::common::homeassistant::{Entity, EntityCategory};
 use async_trait::async_trait;
 use homeassistant_agent::model::{Discovery, SensorClass, StateClass};
+use schemars::{gen::SchemaGenerator, schema::Schema};
 use serde_json::Value;
 use sysinfo::System;
 
 pub struct Collector;
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(rename = "MemoryStatus")]
 pub struct Status {
+    /// Free memory, in bytes
     pub free: u64,
+    /// Total memory, in bytes
     pub total: u64,
+    /// Used memory, in bytes
     pub used: u64,
+    /// Available memory, in bytes
     pub available: u64,
 }
 
@@ -31,9 +38,13 @@ impl super::Collector for Collector {
         Ok(serde_json::to_value(status)?)
     }
 
-    fn describe_ha(&self) -> Vec<Discovery> {
+    fn schema(&self, gen: &mut SchemaGenerator) -> Option<Schema> {
+        Some(gen.subschema_for::<Status>())
+    }
+
+    fn describe_ha(&self) -> Vec<Entity> {
         vec![
-            Discovery {
+            Entity::from(Discovery {
                 unique_id: Some("free".to_string()),
                 name: Some("Free memory".to_string()),
                 state_class: Some(StateClass::Measurement),
@@ -41,8 +52,8 @@ impl super::Collector for Collector {
                 device_class: Some(SensorClass::DataSize.as_ref().to_string()),
                 unit_of_measurement: Some("B".to_string()),
                 ..Default::default()
-            },
-            Discovery {
+            }),
+            Entity::from(Discovery {
                 unique_id: Some("total".to_string()),
                 name: Some("Total memory".to_string()),
                 state_class: Some(StateClass::Measurement),
@@ -50,8 +61,10 @@ impl super::Collector for Collector {
                 device_class: Some(SensorClass::DataSize.as_ref().to_string()),
                 unit_of_measurement: Some("B".to_string()),
                 ..Default::default()
-            },
-            Discovery {
+            })
+            .category(EntityCategory::Diagnostic)
+            .enabled(false),
+            Entity::from(Discovery {
                 unique_id: Some("used".to_string()),
                 name: Some("Used memory".to_string()),
                 state_class: Some(StateClass::Measurement),
@@ -59,8 +72,8 @@ impl super::Collector for Collector {
                 device_class: Some(SensorClass::DataSize.as_ref().to_string()),
                 unit_of_measurement: Some("B".to_string()),
                 ..Default::default()
-            },
-            Discovery {
+            }),
+            Entity::from(Discovery {
                 unique_id: Some("available".to_string()),
                 name: Some("Available memory".to_string()),
                 state_class: Some(StateClass::Measurement),
@@ -68,7 +81,7 @@ impl super::Collector for Collector {
                 device_class: Some(SensorClass::DataSize.as_ref().to_string()),
                 unit_of_measurement: Some("B".to_string()),
                 ..Default::default()
-            },
+            }),
         ]
     }
 }
@@ -2,12 +2,17 @@ pub mod disk_free;
 pub mod exec;
 pub mod load_avg;
 pub mod memory;
+pub mod packages;
+pub mod reboot_required;
 pub mod swap;
+pub mod tls;
 
+use crate::{common::homeassistant::Entity, config::EntityOptions};
 use actix_web::{body::BoxBody, HttpResponse, ResponseError};
 use async_trait::async_trait;
-use homeassistant_agent::model::Discovery;
+use schemars::{gen::SchemaGenerator, schema::Schema};
 use serde_json::json;
+use std::collections::BTreeMap;
 
 #[derive(Clone, Debug)]
 pub struct ValueDescriptor {
@@ -21,9 +26,58 @@ pub trait Collector: Send + Sync {
     async fn collect(&self) -> anyhow::Result<serde_json::Value>;
 
     /// Describe payload for Home Assistant
-    fn describe_ha(&self) -> Vec<Discovery> {
+    fn describe_ha(&self) -> Vec<Entity> {
         vec![]
     }
+
+    /// Describe the schema of the collected value, for the OpenAPI document
+    fn schema(&self, _gen: &mut SchemaGenerator) -> Option<Schema> {
+        None
+    }
+}
+
+/// A collector, with overrides of its Home Assistant entities applied
+pub struct Customized<C: Collector> {
+    collector: C,
+    entities: BTreeMap<String, EntityOptions>,
+}
+
+impl<C: Collector> Customized<C> {
+    pub fn new(collector: C, entities: BTreeMap<String, EntityOptions>) -> Self {
+        Self {
+            collector,
+            entities,
+        }
+    }
+}
+
+#[async_trait]
+impl<C: Collector> Collector for Customized<C> {
+    async fn collect(&self) -> anyhow::Result<serde_json::Value> {
+        self.collector.collect().await
+    }
+
+    fn describe_ha(&self) -> Vec<Entity> {
+        self.collector
+            .describe_ha()
+            .into_iter()
+            .map(|entity| {
+                match entity
+                    .discovery
+                    .unique_id
+                    .as_ref()
+                    .and_then(|id| self.entities.get(id))
+                {
+                    Some(options) => options.apply(entity),
+                    None => entity,
+                }
+            })
+            .collect()
+    }
+
+    fn schema(&self, gen: &mut SchemaGenerator) -> Option<Schema> {
+        self.collector.schema(gen)
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -0,0 +1,109 @@
+//! Debian, Ubuntu, …
+
+use super::{command, run, Backend, Package};
+use async_trait::async_trait;
+
+pub struct Apt {
+    pub refresh: bool,
+}
+
+fn apt_get() -> tokio::process::Command {
+    let mut cmd = command("apt-get");
+    cmd.env("DEBIAN_FRONTEND", "noninteractive");
+    cmd
+}
+
+#[async_trait]
+impl Backend for Apt {
+    async fn pending(&self) -> anyhow::Result<Vec<Package>> {
+        if self.refresh {
+            run(apt_get().args(["-q", "update"]), &[0]).await?;
+        }
+
+        // simulating an upgrade has a stable output format, and doesn't require root permissions
+        let output = run(
+            apt_get().args(["-s", "-o", "Debug::NoLocking=true", "upgrade"]),
+            &[0],
+        )
+        .await?;
+
+        Ok(parse(&output))
+    }
+
+    async fn install(&self) -> anyhow::Result<()> {
+        run(apt_get().args(["-q", "-y", "upgrade"]), &[0]).await?;
+        Ok(())
+    }
+}
+
+/// Parse the `Inst` lines of a simulated upgrade:
+///
+/// ```text
+/// Inst openssl [3.0.11-1~deb12u2] (3.0.13-1~deb12u1 Debian-Security:12/stable-security [amd64])
+/// ```
+fn parse(output: &str) -> Vec<Package> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("Inst "))
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(' ')?;
+
+            // new packages don't have an installed version
+            let (installed_version, rest) = match rest.strip_prefix('[') {
+                Some(rest) => {
+                    let (version, rest) = rest.split_once("] ")?;
+                    (Some(version.to_string()), rest)
+                }
+                None => (None, rest),
+            };
+
+            let rest = rest.strip_prefix('(')?;
+            let (available_version, origin) = rest.split_once(' ').unwrap_or((rest, ""));
+
+            Some(Package {
+                name: name.to_string(),
+                installed_version,
+                available_version: available_version.trim_end_matches(')').to_string(),
+                security: origin.to_lowercase().contains("security"),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let packages = parse(
+            r#"NOTE: This is only a simulation!
+      apt-get needs root privileges for real execution.
+Reading package lists...
+Building dependency tree...
+The following packages will be upgraded:
+  libssl3 openssl tzdata
+3 upgraded, 0 newly installed, 0 to remove and 0 not upgraded.
+Inst libssl3 [3.0.11-1~deb12u2] (3.0.13-1~deb12u1 Debian-Security:12/stable-security [amd64])
+Inst openssl [3.0.11-1~deb12u2] (3.0.13-1~deb12u1 Debian-Security:12/stable-security [amd64])
+Inst tzdata [2024a-0+deb12u1] (2025b-0+deb12u1 Debian:12.11/stable [all])
+Inst linux-image-6.1.0-37-amd64 (6.1.140-1 Debian:12.11/stable [amd64])
+Conf libssl3 (3.0.13-1~deb12u1 Debian-Security:12/stable-security [amd64])
+"#,
+        );
+
+        assert_eq!(packages.len(), 4);
+        assert_eq!(
+            packages[0],
+            Package {
+                name: "libssl3".into(),
+                installed_version: Some("3.0.11-1~deb12u2".into()),
+                available_version: "3.0.13-1~deb12u1".into(),
+                security: true,
+            }
+        );
+        assert!(!packages[2].security);
+        assert_eq!(packages[3].installed_version, None);
+        assert_eq!(packages[3].available_version, "6.1.140-1");
+    }
+}
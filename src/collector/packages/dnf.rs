@@ -0,0 +1,111 @@
+//! Fedora, RHEL, …
+
+use super::{command, run, Backend, Package};
+use async_trait::async_trait;
+use std::collections::HashSet;
+
+pub struct Dnf {
+    pub refresh: bool,
+}
+
+#[async_trait]
+impl Backend for Dnf {
+    async fn pending(&self) -> anyhow::Result<Vec<Package>> {
+        let mut check = command("dnf");
+        check.args(["-q", "check-update"]);
+        if self.refresh {
+            // otherwise, the metadata is refreshed once it expired
+            check.arg("--refresh");
+        }
+
+        // exit code 100 signals pending updates
+        let updates = run(&mut check, &[0, 100]).await?;
+        let security = run(
+            command("dnf").args(["-q", "updateinfo", "list", "--security"]),
+            &[0],
+        )
+        .await?;
+
+        Ok(parse(&updates, &security))
+    }
+
+    async fn install(&self) -> anyhow::Result<()> {
+        run(command("dnf").args(["-q", "-y", "upgrade"]), &[0]).await?;
+        Ok(())
+    }
+}
+
+/// Parse the output of `check-update`, marking packages which are listed in the security advisories.
+///
+/// Long package names wrap the line, so the output is parsed as a stream of columns.
+fn parse(updates: &str, security: &str) -> Vec<Package> {
+    let security = security
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(2))
+        .filter_map(nevra_name)
+        .collect::<HashSet<_>>();
+
+    let columns = updates
+        .lines()
+        .take_while(|line| !line.starts_with("Obsoleting"))
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>();
+
+    columns
+        .chunks_exact(3)
+        .map(|chunk| {
+            let name = chunk[0];
+            Package {
+                name: name.to_string(),
+                installed_version: None,
+                available_version: chunk[1].to_string(),
+                security: security.contains(name),
+            }
+        })
+        .collect()
+}
+
+/// Convert `name-[epoch:]version-release.arch` into `name.arch`, as used by `check-update`
+fn nevra_name(nevra: &str) -> Option<String> {
+    let (nevr, arch) = nevra.rsplit_once('.')?;
+    let mut parts = nevr.rsplitn(3, '-');
+    let (_release, _version, name) = (parts.next()?, parts.next()?, parts.next()?);
+    Some(format!("{name}.{arch}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let packages = parse(
+            r#"
+openssl-libs.x86_64                  1:3.2.2-3.fc40                     updates
+python3-a-very-long-package-name-for-wrapping.noarch
+                                     2.1.0-1.fc40                       updates
+tzdata.noarch                        2025b-1.fc40                       updates
+Obsoleting Packages
+grub2-tools.x86_64                   1:2.06-121.fc40                    updates
+"#,
+            r#"FEDORA-2024-1a2b3c4d5e Important/Sec. openssl-libs-1:3.2.2-3.fc40.x86_64
+"#,
+        );
+
+        assert_eq!(packages.len(), 3);
+        assert_eq!(
+            packages[0],
+            Package {
+                name: "openssl-libs.x86_64".into(),
+                installed_version: None,
+                available_version: "1:3.2.2-3.fc40".into(),
+                security: true,
+            }
+        );
+        assert_eq!(
+            packages[1].name,
+            "python3-a-very-long-package-name-for-wrapping.noarch"
+        );
+        assert!(!packages[2].security);
+    }
+}
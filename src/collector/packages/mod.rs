@@ -0,0 +1,438 @@
+//! Pending package updates collector
+
+mod apt;
+mod dnf;
+mod pacman;
+
+use crate::common::homeassistant::{Component, Entity};
+use crate::config::CommonCollector;
+use crate::utils::is_default;
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use homeassistant_agent::model::{Discovery, StateClass};
+use schemars::{gen::SchemaGenerator, schema::Schema};
+use serde_json::Value;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Name of the command, installing the pending updates
+pub const INSTALL_COMMAND: &str = "packages_install";
+/// Payload sent by the Home Assistant update entity, to install the updates
+const PAYLOAD_INSTALL: &str = "install";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Configuration {
+    #[serde(flatten)]
+    pub common: CommonCollector,
+
+    /// The package manager, detected if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<BackendKind>,
+
+    /// Interval of checking for updates
+    #[serde(with = "humantime_serde", default = "default::period")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub period: Duration,
+
+    /// Refresh the package metadata before checking for updates. Requires root permissions,
+    /// except for pacman, which uses `checkupdates`.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub refresh: bool,
+
+    /// Provide the command `packages_install`, installing all pending updates.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub install: bool,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            common: Default::default(),
+            backend: None,
+            period: default::period(),
+            refresh: false,
+            install: false,
+        }
+    }
+}
+
+impl Deref for Configuration {
+    type Target = CommonCollector;
+
+    fn deref(&self) -> &Self::Target {
+        &self.common
+    }
+}
+
+mod default {
+    use super::*;
+
+    pub const fn period() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+}
+
+/// A supported package manager
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum BackendKind {
+    Apt,
+    Dnf,
+    Pacman,
+}
+
+impl BackendKind {
+    /// Detect the package manager of the system
+    pub fn detect() -> Option<Self> {
+        [
+            (Self::Apt, "apt-get"),
+            (Self::Dnf, "dnf"),
+            (Self::Pacman, "pacman"),
+        ]
+        .into_iter()
+        .find(|(_, binary)| in_path(binary))
+        .map(|(kind, _)| kind)
+    }
+
+    pub fn backend(self, refresh: bool) -> Box<dyn Backend> {
+        match self {
+            Self::Apt => Box::new(apt::Apt { refresh }),
+            Self::Dnf => Box::new(dnf::Dnf { refresh }),
+            Self::Pacman => Box::new(pacman::Pacman { refresh }),
+        }
+    }
+}
+
+fn in_path(binary: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join(binary).is_file()))
+        .unwrap_or_default()
+}
+
+/// Access to a package manager
+#[async_trait]
+pub trait Backend: Send + Sync {
+    /// List the pending updates, refreshing the metadata first if requested
+    async fn pending(&self) -> anyhow::Result<Vec<Package>>;
+
+    /// Install all pending updates
+    async fn install(&self) -> anyhow::Result<()>;
+}
+
+/// A command for running the package manager, with a stable output format
+fn command(program: &str) -> tokio::process::Command {
+    let mut cmd = tokio::process::Command::new(program);
+    cmd.env("LC_ALL", "C").kill_on_drop(true);
+    cmd
+}
+
+/// Run a command, returning its standard output. The exit codes `ok` are considered a success.
+async fn run(cmd: &mut tokio::process::Command, ok: &[i32]) -> anyhow::Result<String> {
+    let output = cmd
+        .output()
+        .await
+        .with_context(|| format!("Failed to launch: {cmd:?}"))?;
+
+    match output.status.code() {
+        Some(code) if ok.contains(&code) => Ok(String::from_utf8_lossy(&output.stdout).into()),
+        _ => bail!(
+            "Command failed: {cmd:?}: {status}: {stderr}",
+            status = output.status,
+            stderr = String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+}
+
+/// A package with a pending update
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Package {
+    /// Name of the package
+    pub name: String,
+    /// Installed version, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_version: Option<String>,
+    /// Version of the update
+    pub available_version: String,
+    /// If the update is a security update
+    #[serde(default)]
+    pub security: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(rename = "PackagesStatus")]
+pub struct Status {
+    /// The package manager
+    pub backend: BackendKind,
+    /// Name of the operating system
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    /// Installed version of the operating system
+    pub installed_version: String,
+    /// Version of the operating system after installing the pending updates
+    pub latest_version: String,
+    /// Number of pending updates
+    pub pending: usize,
+    /// Number of pending security updates
+    pub security: usize,
+    /// Packages with pending updates
+    pub packages: Vec<Package>,
+}
+
+impl Status {
+    fn new(backend: BackendKind, os: OsRelease, packages: Vec<Package>) -> Self {
+        let pending = packages.len();
+        let security = packages.iter().filter(|p| p.security).count();
+
+        // Home Assistant shows an update when the versions differ
+        let latest_version = match pending {
+            0 => os.version.clone(),
+            n => format!("{} (+{n})", os.version),
+        };
+
+        Self {
+            backend,
+            os: os.name,
+            installed_version: os.version,
+            latest_version,
+            pending,
+            security,
+            packages,
+        }
+    }
+}
+
+/// Information from `/etc/os-release`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct OsRelease {
+    name: Option<String>,
+    version: String,
+}
+
+impl OsRelease {
+    fn load() -> Self {
+        ["/etc/os-release", "/usr/lib/os-release"]
+            .into_iter()
+            .map(Path::new)
+            .find_map(|path| std::fs::read_to_string(path).ok())
+            .map(|content| Self::parse(&content))
+            .unwrap_or_else(|| Self {
+                name: None,
+                version: "unknown".into(),
+            })
+    }
+
+    fn parse(content: &str) -> Self {
+        let value = |key: &str| {
+            content.lines().find_map(|line| {
+                line.strip_prefix(key)
+                    .and_then(|rest| rest.strip_prefix('='))
+                    .map(|value| value.trim().trim_matches('"').to_string())
+            })
+        };
+
+        Self {
+            name: value("PRETTY_NAME").or_else(|| value("NAME")),
+            // rolling releases (like Arch) only have a build ID
+            version: value("VERSION_ID")
+                .or_else(|| value("BUILD_ID"))
+                .unwrap_or_else(|| "unknown".into()),
+        }
+    }
+}
+
+/// State shared by the collector and the install command
+pub struct Packages {
+    kind: BackendKind,
+    backend: Box<dyn Backend>,
+    period: Duration,
+    install: bool,
+    last: Mutex<Option<(Instant, Result<Status, String>)>>,
+}
+
+impl Packages {
+    pub fn new(kind: BackendKind, backend: Box<dyn Backend>, config: &Configuration) -> Self {
+        Self {
+            kind,
+            backend,
+            period: config.period,
+            install: config.install,
+            last: Mutex::new(None),
+        }
+    }
+
+    async fn status(&self) -> anyhow::Result<Status> {
+        let mut last = self.last.lock().await;
+
+        match &*last {
+            Some((when, result)) if when.elapsed() < self.period => {}
+            _ => {
+                let result = self
+                    .backend
+                    .pending()
+                    .await
+                    .map(|packages| Status::new(self.kind, OsRelease::load(), packages))
+                    .map_err(|err| err.to_string());
+                *last = Some((Instant::now(), result));
+            }
+        }
+
+        match &*last {
+            Some((_, Ok(status))) => Ok(status.clone()),
+            Some((_, Err(err))) => Err(anyhow!("{err}")),
+            None => unreachable!("state was set before"),
+        }
+    }
+
+    /// Install all pending updates, and check again with the next collection
+    pub async fn install(&self) -> anyhow::Result<()> {
+        let result = self.backend.install().await;
+        *self.last.lock().await = None;
+        result
+    }
+}
+
+pub struct Collector {
+    packages: Arc<Packages>,
+}
+
+impl Collector {
+    pub fn new(packages: Arc<Packages>) -> Self {
+        Self { packages }
+    }
+}
+
+#[async_trait]
+impl super::Collector for Collector {
+    async fn collect(&self) -> anyhow::Result<Value> {
+        Ok(serde_json::to_value(self.packages.status().await?)?)
+    }
+
+    fn schema(&self, gen: &mut SchemaGenerator) -> Option<Schema> {
+        Some(gen.subschema_for::<Status>())
+    }
+
+    fn describe_ha(&self) -> Vec<Entity> {
+        let mut update = Entity::from(Discovery {
+            unique_id: Some("update".into()),
+            name: Some("Operating system".into()),
+            value_template: Some(
+                r#"{{ {
+  "installed_version": value_json.installed_version,
+  "latest_version": value_json.latest_version,
+  "title": value_json.os,
+  "release_summary": value_json.packages | map(attribute='name') | join(', ') | truncate(250)
+} | to_json }}"#
+                    .into(),
+            ),
+            ..Default::default()
+        })
+        .component(Component::Update);
+
+        if self.packages.install {
+            update = update
+                .command(INSTALL_COMMAND)
+                .extra("payload_install", PAYLOAD_INSTALL);
+        }
+
+        vec![
+            update,
+            Entity::from(Discovery {
+                unique_id: Some("pending".into()),
+                name: Some("Pending updates".into()),
+                state_class: Some(StateClass::Measurement),
+                value_template: Some("{{ value_json.pending }}".into()),
+                ..Default::default()
+            }),
+            Entity::from(Discovery {
+                unique_id: Some("security".into()),
+                name: Some("Pending security updates".into()),
+                state_class: Some(StateClass::Measurement),
+                value_template: Some("{{ value_json.security }}".into()),
+                ..Default::default()
+            }),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::collector::Collector as _;
+
+    #[test]
+    fn test_default_period() {
+        let collectors: crate::config::Collectors =
+            serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(collectors.packages.period, Duration::from_secs(60 * 60));
+    }
+
+    struct Fixture(Vec<Package>);
+
+    #[async_trait]
+    impl Backend for Fixture {
+        async fn pending(&self) -> anyhow::Result<Vec<Package>> {
+            Ok(self.0.clone())
+        }
+
+        async fn install(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn package(name: &str, security: bool) -> Package {
+        Package {
+            name: name.into(),
+            installed_version: Some("1.0".into()),
+            available_version: "1.1".into(),
+            security,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collector() {
+        let packages = Packages::new(
+            BackendKind::Apt,
+            Box::new(Fixture(vec![
+                package("bash", false),
+                package("openssl", true),
+            ])),
+            &Configuration {
+                install: true,
+                period: default::period(),
+                ..Default::default()
+            },
+        );
+        let collector = Collector::new(Arc::new(packages));
+
+        let status: Status = serde_json::from_value(collector.collect().await.unwrap()).unwrap();
+        assert_eq!(status.pending, 2);
+        assert_eq!(status.security, 1);
+        assert_ne!(status.installed_version, status.latest_version);
+
+        let entities = collector.describe_ha();
+        assert_eq!(entities[0].component, Component::Update);
+        assert_eq!(entities[0].command.as_deref(), Some(INSTALL_COMMAND));
+    }
+
+    #[test]
+    fn test_os_release() {
+        let os = OsRelease::parse(
+            r#"PRETTY_NAME="Debian GNU/Linux 12 (bookworm)"
+NAME="Debian GNU/Linux"
+VERSION_ID="12"
+ID=debian
+"#,
+        );
+        assert_eq!(os.name.as_deref(), Some("Debian GNU/Linux 12 (bookworm)"));
+        assert_eq!(os.version, "12");
+
+        let os = OsRelease::parse("NAME=\"Arch Linux\"\nBUILD_ID=rolling\n");
+        assert_eq!(os.version, "rolling");
+    }
+}
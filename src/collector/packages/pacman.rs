@@ -0,0 +1,74 @@
+//! Arch Linux, …
+
+use super::{command, run, Backend, Package};
+use async_trait::async_trait;
+
+pub struct Pacman {
+    pub refresh: bool,
+}
+
+#[async_trait]
+impl Backend for Pacman {
+    async fn pending(&self) -> anyhow::Result<Vec<Package>> {
+        let output = if self.refresh {
+            // syncs a temporary copy of the database, avoiding partial upgrades. Exit code 2 means no updates.
+            run(&mut command("checkupdates"), &[0, 2]).await?
+        } else {
+            // exit code 1 means no updates
+            run(command("pacman").arg("-Qu"), &[0, 1]).await?
+        };
+
+        Ok(parse(&output))
+    }
+
+    async fn install(&self) -> anyhow::Result<()> {
+        run(command("pacman").args(["-Syu", "--noconfirm"]), &[0]).await?;
+        Ok(())
+    }
+}
+
+/// Parse lines like `linux 6.8.9.arch1-1 -> 6.9.1.arch1-1`. Pacman doesn't provide security information.
+fn parse(output: &str) -> Vec<Package> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let name = columns.next()?;
+            let installed_version = columns.next()?;
+            let _ = columns.next().filter(|arrow| *arrow == "->")?;
+            let available_version = columns.next()?;
+
+            Some(Package {
+                name: name.to_string(),
+                installed_version: Some(installed_version.to_string()),
+                available_version: available_version.to_string(),
+                security: false,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let packages = parse(
+            r#"linux 6.8.9.arch1-1 -> 6.9.1.arch1-1
+glibc 2.39-4 -> 2.39-5 [ignored]
+"#,
+        );
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(
+            packages[1],
+            Package {
+                name: "glibc".into(),
+                installed_version: Some("2.39-4".into()),
+                available_version: "2.39-5".into(),
+                security: false,
+            }
+        );
+    }
+}
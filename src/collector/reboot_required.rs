@@ -0,0 +1,378 @@
+//! Reboot-required collector
+
+use crate::common::homeassistant::{Component, Entity};
+use crate::config::CommonCollector;
+use anyhow::anyhow;
+use async_trait::async_trait;
+use homeassistant_agent::model::Discovery;
+use schemars::{gen::SchemaGenerator, schema::Schema};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::ops::Deref;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Flag file, created by Debian based distributions
+const FLAG_FILE: &str = "/var/run/reboot-required";
+/// Packages which requested the reboot
+const FLAG_PACKAGES_FILE: &str = "/var/run/reboot-required.pkgs";
+/// Installed kernels, one directory per version
+const MODULES_DIR: &str = "/lib/modules";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Configuration {
+    #[serde(flatten)]
+    pub common: CommonCollector,
+
+    /// Interval of checking, scanning the processes can be expensive
+    #[serde(with = "humantime_serde", default = "default::period")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub period: Duration,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            common: Default::default(),
+            period: default::period(),
+        }
+    }
+}
+
+impl Deref for Configuration {
+    type Target = CommonCollector;
+
+    fn deref(&self) -> &Self::Target {
+        &self.common
+    }
+}
+
+mod default {
+    use super::*;
+
+    pub const fn period() -> Duration {
+        Duration::from_secs(5 * 60)
+    }
+}
+
+/// The reason for requiring a reboot
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// The package manager requested a reboot
+    Flag,
+    /// A newer kernel is installed
+    Kernel,
+    /// Running processes still use deleted libraries
+    Libraries,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(rename = "RebootRequiredStatus")]
+pub struct Status {
+    /// If a reboot is required
+    pub required: bool,
+    /// Why a reboot is required
+    pub reasons: Vec<Reason>,
+    /// The running kernel
+    pub running_kernel: String,
+    /// The newest installed kernel, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newest_kernel: Option<String>,
+    /// Packages which requested a reboot
+    pub packages: Vec<String>,
+    /// Services with processes still using deleted libraries
+    pub services: Vec<String>,
+    /// Processes, outside of services, still using deleted libraries
+    pub processes: Vec<String>,
+}
+
+pub struct Collector {
+    period: Duration,
+    last: Mutex<Option<(Instant, Status)>>,
+}
+
+impl Collector {
+    pub fn new(config: Configuration) -> Self {
+        Self {
+            period: config.period,
+            last: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl super::Collector for Collector {
+    async fn collect(&self) -> anyhow::Result<Value> {
+        let mut last = self.last.lock().await;
+
+        let status = match &*last {
+            Some((when, status)) if when.elapsed() < self.period => status.clone(),
+            _ => {
+                let status = tokio::task::spawn_blocking(check)
+                    .await
+                    .map_err(|err| anyhow!("Failed to check: {err}"))??;
+                *last = Some((Instant::now(), status.clone()));
+                status
+            }
+        };
+
+        Ok(serde_json::to_value(status)?)
+    }
+
+    fn schema(&self, gen: &mut SchemaGenerator) -> Option<Schema> {
+        Some(gen.subschema_for::<Status>())
+    }
+
+    fn describe_ha(&self) -> Vec<Entity> {
+        vec![Entity::from(Discovery {
+            unique_id: Some("required".into()),
+            name: Some("Reboot required".into()),
+            device_class: Some("update".into()),
+            value_template: Some("{{ 'ON' if value_json.required else 'OFF' }}".into()),
+            ..Default::default()
+        })
+        .component(Component::BinarySensor)
+        .extra(
+            "json_attributes_template",
+            "{{ {'reasons': value_json.reasons, 'services': value_json.services, 'processes': value_json.processes} | to_json }}",
+        )]
+    }
+}
+
+fn check() -> anyhow::Result<Status> {
+    let mut reasons = vec![];
+
+    let flag = Path::new(FLAG_FILE).exists();
+    if flag {
+        reasons.push(Reason::Flag);
+    }
+    let packages = if flag {
+        std::fs::read_to_string(FLAG_PACKAGES_FILE)
+            .map(|content| {
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(ToString::to_string)
+                    .collect::<BTreeSet<_>>()
+            })
+            .unwrap_or_default()
+    } else {
+        Default::default()
+    };
+
+    let running_kernel = std::fs::read_to_string("/proc/sys/kernel/osrelease")?
+        .trim()
+        .to_string();
+    let newest_kernel = newest_kernel(
+        std::fs::read_dir(MODULES_DIR)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string()),
+    );
+    if newest_kernel
+        .as_deref()
+        .is_some_and(|newest| version_cmp(newest, &running_kernel) == Ordering::Greater)
+    {
+        reasons.push(Reason::Kernel);
+    }
+
+    let (services, processes) = deleted_libraries();
+    if !services.is_empty() || !processes.is_empty() {
+        reasons.push(Reason::Libraries);
+    }
+
+    Ok(Status {
+        required: !reasons.is_empty(),
+        reasons,
+        running_kernel,
+        newest_kernel,
+        packages: packages.into_iter().collect(),
+        services: services.into_iter().collect(),
+        processes: processes.into_iter().collect(),
+    })
+}
+
+/// Find services and processes which still map deleted libraries.
+///
+/// Processes of other users can only be inspected when running as root.
+fn deleted_libraries() -> (BTreeSet<String>, BTreeSet<String>) {
+    let mut services = BTreeSet::new();
+    let mut processes = BTreeSet::new();
+
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return (services, processes);
+    };
+
+    for entry in entries.flatten() {
+        let pid = entry.file_name();
+        if !pid.to_string_lossy().bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+
+        let path = entry.path();
+        let Ok(maps) = std::fs::read_to_string(path.join("maps")) else {
+            continue;
+        };
+        if deleted_mappings(&maps).is_empty() {
+            continue;
+        }
+
+        match std::fs::read_to_string(path.join("cgroup"))
+            .ok()
+            .and_then(|cgroup| service(&cgroup))
+        {
+            Some(service) => {
+                services.insert(service);
+            }
+            None => {
+                if let Ok(comm) = std::fs::read_to_string(path.join("comm")) {
+                    processes.insert(comm.trim().to_string());
+                }
+            }
+        }
+    }
+
+    (services, processes)
+}
+
+/// Libraries in the memory mappings of a process, which have been deleted
+fn deleted_mappings(maps: &str) -> BTreeSet<&str> {
+    maps.lines()
+        .filter_map(|line| line.strip_suffix(" (deleted)"))
+        // the path is the sixth column, and may contain spaces itself
+        .filter_map(|line| line.find(" /").map(|start| &line[start + 1..]))
+        .filter(|path| path.contains(".so"))
+        // shared memory and temporary files are deleted on purpose
+        .filter(|path| {
+            !["/dev/", "/memfd:", "/run/", "/tmp/", "/var/"]
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+        })
+        .collect()
+}
+
+/// The systemd service of a process, from its `/proc/<pid>/cgroup` file
+fn service(cgroup: &str) -> Option<String> {
+    cgroup
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .find_map(|path| {
+            path.rsplit('/')
+                .find(|segment| segment.ends_with(".service"))
+                .map(ToString::to_string)
+        })
+}
+
+fn newest_kernel(versions: impl IntoIterator<Item = String>) -> Option<String> {
+    versions.into_iter().max_by(|a, b| version_cmp(a, b))
+}
+
+/// Compare versions, comparing numeric parts by their value
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    fn parts(version: &str) -> Vec<(bool, &str)> {
+        let mut result = vec![];
+        let mut start = 0;
+        for (i, c) in version.char_indices().skip(1) {
+            let prev = version[..i].chars().next_back().unwrap_or_default();
+            if prev.is_ascii_digit() != c.is_ascii_digit() {
+                result.push(&version[start..i]);
+                start = i;
+            }
+        }
+        result.push(&version[start..]);
+        result
+            .into_iter()
+            .map(|part| (part.starts_with(|c: char| c.is_ascii_digit()), part))
+            .collect()
+    }
+
+    for (a, b) in parts(a).into_iter().zip(parts(b)) {
+        let ordering = match (a, b) {
+            ((true, a), (true, b)) => a
+                .parse::<u64>()
+                .unwrap_or_default()
+                .cmp(&b.parse::<u64>().unwrap_or_default()),
+            ((_, a), (_, b)) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_period() {
+        let collectors: crate::config::Collectors =
+            serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(
+            collectors.reboot_required.period,
+            Duration::from_secs(5 * 60)
+        );
+    }
+
+    #[test]
+    fn test_kernel() {
+        assert_eq!(
+            newest_kernel([
+                "6.1.0-9-amd64".to_string(),
+                "6.1.0-37-amd64".to_string(),
+                "6.1.0-18-amd64".to_string(),
+            ]),
+            Some("6.1.0-37-amd64".to_string())
+        );
+        assert_eq!(
+            version_cmp("6.9.1-300.fc40.x86_64", "6.10.2-200.fc40.x86_64"),
+            Ordering::Less
+        );
+        assert_eq!(
+            version_cmp("6.8.9-300.fc40.x86_64", "6.8.9-300.fc40.x86_64"),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_deleted_mappings() {
+        let maps = r#"55d0c6a00000-55d0c6a28000 r--p 00000000 08:01 1835017                    /usr/sbin/sshd
+7f3b2c000000-7f3b2c022000 r--p 00000000 08:01 1837212                    /usr/lib/x86_64-linux-gnu/libssl.so.3 (deleted)
+7f3b2c022000-7f3b2c0c8000 r-xp 00022000 08:01 1837212                    /usr/lib/x86_64-linux-gnu/libssl.so.3 (deleted)
+7f3b2d000000-7f3b2d100000 rw-s 00000000 00:01 4096                       /memfd:pulseaudio (deleted)
+7f3b2e000000-7f3b2e100000 rw-s 00000000 00:19 2048                       /dev/shm/cache.so (deleted)
+7f3b2f000000-7f3b2f020000 r--p 00000000 08:01 1837300                    /usr/lib/x86_64-linux-gnu/libc.so.6
+"#;
+        assert_eq!(
+            deleted_mappings(maps).into_iter().collect::<Vec<_>>(),
+            vec!["/usr/lib/x86_64-linux-gnu/libssl.so.3"]
+        );
+    }
+
+    #[test]
+    fn test_service() {
+        assert_eq!(
+            service("0::/system.slice/ssh.service\n"),
+            Some("ssh.service".to_string())
+        );
+        assert_eq!(
+            service("1:name=systemd:/system.slice/nginx.service\n0::/\n"),
+            Some("nginx.service".to_string())
+        );
+        assert_eq!(
+            service("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+    }
+}
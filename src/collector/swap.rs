@@ -1,16 +1,22 @@
 //! Swap space collector
 
+use crate::common::homeassistant::{Entity, EntityCategory};
 use async_trait::async_trait;
 use homeassistant_agent::model::{Discovery, SensorClass, StateClass};
+use schemars::{gen::SchemaGenerator, schema::Schema};
 use serde_json::Value;
 use sysinfo::System;
 
 pub struct Collector;
 
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(rename = "SwapStatus")]
 pub struct Status {
+    /// Free swap space, in bytes
     pub free: u64,
+    /// Total swap space, in bytes
     pub total: u64,
+    /// Used swap space, in bytes
     pub used: u64,
     /// used / total
     pub percentage: f64,
@@ -37,9 +43,13 @@ impl super::Collector for Collector {
         Ok(serde_json::to_value(status)?)
     }
 
-    fn describe_ha(&self) -> Vec<Discovery> {
+    fn schema(&self, gen: &mut SchemaGenerator) -> Option<Schema> {
+        Some(gen.subschema_for::<Status>())
+    }
+
+    fn describe_ha(&self) -> Vec<Entity> {
         vec![
-            Discovery {
+            Entity::from(Discovery {
                 unique_id: Some("free".to_string()),
                 name: Some("Free swap space".to_string()),
                 state_class: Some(StateClass::Measurement),
@@ -47,8 +57,8 @@ impl super::Collector for Collector {
                 device_class: Some(SensorClass::DataSize.as_ref().to_string()),
                 unit_of_measurement: Some("B".to_string()),
                 ..Default::default()
-            },
-            Discovery {
+            }),
+            Entity::from(Discovery {
                 unique_id: Some("total".to_string()),
                 name: Some("Total swap space".to_string()),
                 state_class: Some(StateClass::Measurement),
@@ -56,8 +66,10 @@ impl super::Collector for Collector {
                 device_class: Some(SensorClass::DataSize.as_ref().to_string()),
                 unit_of_measurement: Some("B".to_string()),
                 ..Default::default()
-            },
-            Discovery {
+            })
+            .category(EntityCategory::Diagnostic)
+            .enabled(false),
+            Entity::from(Discovery {
                 unique_id: Some("used".to_string()),
                 name: Some("Used swap space".to_string()),
                 state_class: Some(StateClass::Measurement),
@@ -65,15 +77,15 @@ impl super::Collector for Collector {
                 device_class: Some(SensorClass::DataSize.as_ref().to_string()),
                 unit_of_measurement: Some("B".to_string()),
                 ..Default::default()
-            },
-            Discovery {
+            }),
+            Entity::from(Discovery {
                 unique_id: Some("percentage".to_string()),
                 name: Some("Used swap space (%)".to_string()),
                 state_class: Some(StateClass::Measurement),
                 value_template: Some(r#"{{ value_json.percentage * 100 }}"#.to_string()),
                 unit_of_measurement: Some("%".to_string()),
                 ..Default::default()
-            },
+            }),
         ]
     }
 }
@@ -0,0 +1,88 @@
+//! TLS certificate collector
+//!
+//! Reports the expiry of the certificate currently served by the HTTP server.
+
+use crate::common::homeassistant::{Entity, EntityCategory};
+use async_trait::async_trait;
+use homeassistant_agent::model::{Discovery, SensorClass, StateClass};
+use schemars::{gen::SchemaGenerator, schema::Schema};
+use serde_json::Value;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::SystemTime;
+
+/// State of the certificate currently in use, shared with the server
+#[derive(Clone, Debug, Default)]
+pub struct State(Arc<RwLock<Option<SystemTime>>>);
+
+impl State {
+    pub fn set_expires(&self, expires: SystemTime) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Some(expires);
+    }
+
+    pub fn expires(&self) -> Option<SystemTime> {
+        *self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub struct Collector {
+    state: State,
+}
+
+impl Collector {
+    pub fn new(state: State) -> Self {
+        Self { state }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[schemars(rename = "TlsStatus")]
+pub struct Status {
+    /// Expiry of the certificate, in RFC 3339 format
+    pub expires: Option<String>,
+    /// Seconds until the certificate expires, negative if it already expired
+    pub remaining: Option<i64>,
+}
+
+#[async_trait]
+impl super::Collector for Collector {
+    async fn collect(&self) -> anyhow::Result<Value> {
+        let expires = self.state.expires();
+
+        let remaining = expires.map(|expires| match expires.duration_since(SystemTime::now()) {
+            Ok(remaining) => remaining.as_secs() as i64,
+            Err(err) => -(err.duration().as_secs() as i64),
+        });
+
+        Ok(serde_json::to_value(Status {
+            expires: expires.map(|expires| humantime::format_rfc3339_seconds(expires).to_string()),
+            remaining,
+        })?)
+    }
+
+    fn schema(&self, gen: &mut SchemaGenerator) -> Option<Schema> {
+        Some(gen.subschema_for::<Status>())
+    }
+
+    fn describe_ha(&self) -> Vec<Entity> {
+        vec![
+            Entity::from(Discovery {
+                unique_id: Some("expires".to_string()),
+                name: Some("TLS certificate expiry".to_string()),
+                value_template: Some("{{ value_json.expires }}".to_string()),
+                device_class: Some(SensorClass::Timestamp.as_ref().to_string()),
+                ..Default::default()
+            })
+            .category(EntityCategory::Diagnostic),
+            Entity::from(Discovery {
+                unique_id: Some("remaining".to_string()),
+                name: Some("TLS certificate remaining validity".to_string()),
+                state_class: Some(StateClass::Measurement),
+                value_template: Some("{{ value_json.remaining }}".to_string()),
+                device_class: Some(SensorClass::Duration.as_ref().to_string()),
+                unit_of_measurement: Some("s".to_string()),
+                ..Default::default()
+            })
+            .category(EntityCategory::Diagnostic),
+        ]
+    }
+}
@@ -1,12 +1,29 @@
 use crate::{
     command::CallbackFn,
+    common::homeassistant::{Component, Entity},
     config::CommonCommand,
     uplink::homeassistant::{PAYLOAD_RUNNING, PAYLOAD_STOPPED},
     utils::is_default,
 };
 use async_trait::async_trait;
 use homeassistant_agent::model::{Availability, Discovery};
-use std::{borrow::Cow, collections::HashMap, ops::Deref};
+use regex::Regex;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Payload turning a switch on
+const PAYLOAD_ON: &str = "ON";
+/// Payload turning a switch off
+const PAYLOAD_OFF: &str = "OFF";
+/// Placeholder in the arguments, replaced with the value of the input
+const PLACEHOLDER_VALUE: &str = "{value}";
+/// Environment variable, holding the value of the input
+const ENV_VALUE: &str = "RESYMO_VALUE";
 
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -30,6 +47,63 @@ impl Deref for Configuration {
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Run {
+    #[serde(flatten)]
+    pub exec: Exec,
+
+    /// The input the command takes, announced as a matching Home Assistant entity instead of a button.
+    ///
+    /// The value is provided to the command as `{value}` in the arguments, and as the environment variable
+    /// `RESYMO_VALUE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<Input>,
+
+    /// A command reporting an external on/off state, for buttons and switches.
+    ///
+    /// Takes precedence over the state query of a switch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_command: Option<StateCommand>,
+
+    /// The Home Assistant discovery section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovery: Option<Discovery>,
+}
+
+impl Run {
+    /// The process querying the on/off state, if any
+    fn state_query(&self) -> Option<&Exec> {
+        match (&self.state_command, &self.input) {
+            (Some(state_command), _) => Some(&state_command.exec),
+            (None, Some(Input::Switch { .. })) => Some(&self.exec),
+            (None, _) => None,
+        }
+    }
+}
+
+/// A process reporting an on/off state, an exit code of zero meaning "on"
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StateCommand {
+    #[serde(flatten)]
+    pub exec: Exec,
+
+    /// Interval of running the command
+    #[serde(with = "crate::utils::non_zero_duration", default = "default::period")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub period: Duration,
+}
+
+mod default {
+    use super::*;
+
+    pub const fn period() -> Duration {
+        Duration::from_secs(60)
+    }
+}
+
+/// A process to execute
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Exec {
     /// The binary to call
     pub command: String,
 
@@ -43,15 +117,193 @@ pub struct Run {
 
     #[serde(default, skip_serializing_if = "is_default")]
     pub clean_env: bool,
+}
 
-    /// The Home Assistant discovery section
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub discovery: Option<Discovery>,
+impl Exec {
+    /// Run the process, succeeding if it exits with zero
+    async fn run(&self, value: Option<&str>) -> Result<(), ()> {
+        let mut cmd = tokio::process::Command::new(&self.command);
+
+        if self.clean_env {
+            cmd.env_clear();
+        }
+
+        cmd.envs(self.envs.clone());
+
+        match value {
+            Some(value) => {
+                cmd.args(
+                    self.args
+                        .iter()
+                        .map(|arg| arg.replace(PLACEHOLDER_VALUE, value)),
+                )
+                .env(ENV_VALUE, value);
+            }
+            None => {
+                cmd.args(self.args.clone());
+            }
+        }
+
+        match cmd.output().await {
+            Ok(output) if output.status.success() => Ok(()),
+            Ok(_) => Err(()),
+            Err(err) => {
+                log::warn!("Failed to launch command: {err}");
+                Err(())
+            }
+        }
+    }
+}
+
+/// Input of a command
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Input {
+    /// One of a fixed set of options
+    Select { options: Vec<String> },
+    /// A number
+    #[serde(rename_all = "camelCase")]
+    Number {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        step: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unit_of_measurement: Option<String>,
+    },
+    /// Text, validated by Home Assistant as well as by the agent
+    #[serde(rename_all = "camelCase")]
+    Text {
+        /// Minimum length
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<usize>,
+        /// Maximum length
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<usize>,
+        /// A regular expression the whole text must match
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pattern: Option<String>,
+        /// Allow text starting with `-`, which a command may take as an option
+        #[serde(default, skip_serializing_if = "is_default")]
+        allow_leading_dash: bool,
+    },
+    /// A switch, turned on and off by dedicated runs.
+    ///
+    /// The command itself queries the state, an exit code of zero meaning "on".
+    Switch { on: Exec, off: Exec },
+}
+
+impl Input {
+    fn component(&self) -> Component {
+        match self {
+            Self::Select { .. } => Component::Select,
+            Self::Number { .. } => Component::Number,
+            Self::Text { .. } => Component::Text,
+            Self::Switch { .. } => Component::Switch,
+        }
+    }
+
+    /// Add the component specific fields to the entity
+    fn describe(&self, mut entity: Entity) -> Entity {
+        match self {
+            Self::Select { options } => {
+                entity = entity.extra("options", options.clone());
+            }
+            Self::Number {
+                min,
+                max,
+                step,
+                unit_of_measurement,
+            } => {
+                entity.discovery.unit_of_measurement = unit_of_measurement.clone();
+                for (name, value) in [("min", min), ("max", max), ("step", step)] {
+                    if let Some(value) = value {
+                        entity = entity.extra(name, *value);
+                    }
+                }
+            }
+            Self::Text {
+                min, max, pattern, ..
+            } => {
+                for (name, value) in [("min", min), ("max", max)] {
+                    if let Some(value) = value {
+                        entity = entity.extra(name, *value);
+                    }
+                }
+                if let Some(pattern) = pattern {
+                    entity = entity.extra("pattern", pattern.clone());
+                }
+            }
+            Self::Switch { .. } => {}
+        }
+
+        entity
+    }
+
+    /// Validate a value, received as payload
+    fn validate(&self, value: &str) -> Result<(), String> {
+        match self {
+            Self::Select { options } => {
+                if !options.iter().any(|option| option == value) {
+                    return Err(format!("'{value}' is not one of the options"));
+                }
+                Ok(())
+            }
+            Self::Number { min, max, .. } => {
+                let number = value
+                    .parse::<f64>()
+                    .map_err(|err| format!("'{value}' is not a number: {err}"))?;
+                // comparisons with NaN are always false, so it would pass the range check
+                if !number.is_finite() {
+                    return Err(format!("'{value}' is not a finite number"));
+                }
+                if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+                    return Err(format!("{number} is out of range"));
+                }
+                Ok(())
+            }
+            Self::Text {
+                min,
+                max,
+                pattern,
+                allow_leading_dash,
+            } => {
+                let len = value.chars().count();
+                if min.is_some_and(|min| len < min) || max.is_some_and(|max| len > max) {
+                    return Err(format!("Length of '{value}' is out of range"));
+                }
+                if let Some(pattern) = pattern {
+                    if !full_match(pattern)?.is_match(value) {
+                        return Err(format!("'{value}' doesn't match the pattern"));
+                    }
+                }
+                if !allow_leading_dash && value.starts_with('-') {
+                    return Err(format!("'{value}' must not start with '-'"));
+                }
+                Ok(())
+            }
+            Self::Switch { .. } => match value {
+                PAYLOAD_ON | PAYLOAD_OFF => Ok(()),
+                _ => Err(format!(
+                    "'{value}' is neither '{PAYLOAD_ON}' nor '{PAYLOAD_OFF}'"
+                )),
+            },
+        }
+    }
+}
+
+/// Compile a pattern, which must match the whole text
+fn full_match(pattern: &str) -> Result<Regex, String> {
+    Regex::new(&format!("^(?:{pattern})$")).map_err(|err| format!("Invalid pattern: {err}"))
 }
 
 pub struct Command {
     config: Run,
-    discovery: Option<Discovery>,
+    discovery: Option<Entity>,
+    /// The last value set, for inputs which can't be queried
+    last: Arc<Mutex<Option<String>>>,
 }
 
 impl Command {
@@ -66,7 +318,27 @@ impl Command {
             .collect()
     }
 
-    fn new_run(name: &str, config: Run) -> Self {
+    fn new_run(name: &str, mut config: Run) -> Self {
+        if config.state_command.is_some()
+            && !matches!(config.input, None | Some(Input::Switch { .. }))
+        {
+            log::warn!(
+                "Ignoring state command of '{name}', only supported by buttons and switches"
+            );
+            // its on/off state would end up as the value of the entity
+            config.state_command = None;
+        }
+
+        if let Some(Input::Text {
+            pattern: Some(pattern),
+            ..
+        }) = &config.input
+        {
+            if let Err(err) = full_match(pattern) {
+                log::warn!("Rejecting all input of '{name}': {err}");
+            }
+        }
+
         let discovery = if let Some(mut discovery) = config.discovery.clone() {
             if discovery.unique_id.is_none() {
                 discovery.unique_id = Some(name.into());
@@ -76,44 +348,180 @@ impl Command {
                 .payload_available(PAYLOAD_STOPPED)
                 .payload_not_available(PAYLOAD_RUNNING)];
 
-            Some(discovery)
+            let entity = Entity::from(discovery);
+            Some(match &config.input {
+                Some(input) => input.describe(entity.component(input.component())),
+                None => entity.component(Component::Button),
+            })
         } else {
             None
         };
 
-        Self { config, discovery }
+        Self {
+            config,
+            discovery,
+            last: Default::default(),
+        }
     }
 }
 
-#[async_trait(?Send)]
+/// Query an on/off state
+async fn query_state(exec: &Exec) -> String {
+    match exec.run(None).await {
+        Ok(()) => PAYLOAD_ON.to_string(),
+        Err(()) => PAYLOAD_OFF.to_string(),
+    }
+}
+
+#[async_trait]
 impl super::Command for Command {
     async fn start(&self, payload: Cow<'_, str>, callback: Box<CallbackFn>) {
         log::info!("running command: {payload}");
 
-        let mut cmd = tokio::process::Command::new(&self.config.command);
-
-        if self.config.clean_env {
-            cmd.env_clear();
-        }
-
-        cmd.args(self.config.args.clone())
-            .envs(self.config.envs.clone());
+        let config = self.config.clone();
+        let last = self.last.clone();
+        let value = payload.trim().to_string();
 
         tokio::spawn(async move {
-            let result = match cmd.output().await {
-                Ok(output) if output.status.success() => Ok(()),
-                Ok(_) => Err(()),
-                Err(err) => {
-                    log::warn!("Failed to launch command: {err}");
-                    Err(())
+            if let Some(Err(err)) = config.input.as_ref().map(|input| input.validate(&value)) {
+                log::warn!("Invalid input: {err}");
+                (callback)(Err(()), None).await;
+                return;
+            }
+
+            let result = match &config.input {
+                None => config.exec.run(None).await,
+                Some(Input::Switch { on, off }) => {
+                    let run = if value == PAYLOAD_ON { on } else { off };
+                    run.run(None).await
+                }
+                Some(_) => {
+                    let result = config.exec.run(Some(&value)).await;
+                    if result.is_ok() {
+                        *last.lock().unwrap_or_else(|err| err.into_inner()) = Some(value.clone());
+                    }
+                    (callback)(result, result.ok().map(|()| value)).await;
+                    return;
                 }
             };
 
-            (callback)(result).await;
+            // report the actual state, even if the command failed
+            let value = match config.state_query() {
+                Some(exec) => Some(query_state(exec).await),
+                None => None,
+            };
+
+            (callback)(result, value).await;
         });
     }
 
-    fn describe_ha(&self) -> Option<Discovery> {
+    async fn value(&self) -> Option<String> {
+        if let Some(exec) = self.config.state_query() {
+            return Some(query_state(exec).await);
+        }
+
+        match &self.config.input {
+            Some(_) => self
+                .last
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .clone(),
+            None => None,
+        }
+    }
+
+    fn value_period(&self) -> Option<Duration> {
+        self.config
+            .state_command
+            .as_ref()
+            .map(|state_command| state_command.period)
+    }
+
+    fn describe_ha(&self) -> Option<Entity> {
         self.discovery.clone()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::command::Command as _;
+    use serde_json::json;
+
+    #[test]
+    fn test_input() {
+        let run: Run = serde_json::from_value(json!({
+            "command": "brightness",
+            "args": ["--set", "{value}"],
+            "input": {"type": "number", "min": 0, "max": 100, "step": 5},
+            "discovery": {"name": "Brightness"},
+        }))
+        .unwrap();
+
+        let input = run.input.clone().unwrap();
+        assert!(input.validate("50").is_ok());
+        assert!(input.validate("150").is_err());
+        assert!(input.validate("bright").is_err());
+        assert!(input.validate("NaN").is_err());
+        assert!(input.validate("inf").is_err());
+
+        let entity = Command::new_run("brightness", run).discovery.unwrap();
+        assert_eq!(entity.component, Component::Number);
+        assert_eq!(entity.extra.get("max"), Some(&json!(100.0)));
+
+        let input = Input::Select {
+            options: vec!["eco".into(), "boost".into()],
+        };
+        assert!(input.validate("eco").is_ok());
+        assert!(input.validate("turbo").is_err());
+
+        let input: Input =
+            serde_json::from_value(json!({"type": "text", "pattern": "[a-z]+(-[a-z]+)*"})).unwrap();
+        assert!(input.validate("backup-daily").is_ok());
+        // the whole text must match
+        assert!(input.validate("backup; reboot").is_err());
+        assert!(input.validate("--force").is_err());
+
+        let input: Input = serde_json::from_value(json!({"type": "text"})).unwrap();
+        assert!(input.validate("anything").is_ok());
+        assert!(input.validate("-rf").is_err());
+
+        let input: Input =
+            serde_json::from_value(json!({"type": "text", "allowLeadingDash": true})).unwrap();
+        assert!(input.validate("-rf").is_ok());
+
+        let input: Input = serde_json::from_value(json!({"type": "text", "pattern": "("})).unwrap();
+        assert!(input.validate("(").is_err());
+    }
+
+    #[test]
+    fn test_state_command() {
+        let run = |input: serde_json::Value| -> Run {
+            serde_json::from_value(json!({
+                "command": "mode",
+                "input": input,
+                "stateCommand": {"command": "mode", "args": ["--status"], "period": "10s"},
+            }))
+            .unwrap()
+        };
+
+        let command = Command::new_run(
+            "mode",
+            run(json!({"type": "switch", "on": {"command": "on"}, "off": {"command": "off"}})),
+        );
+        assert_eq!(command.value_period(), Some(Duration::from_secs(10)));
+        assert!(command.config.state_query().is_some());
+
+        let command = Command::new_run(
+            "mode",
+            run(json!({"type": "select", "options": ["eco", "boost"]})),
+        );
+        assert_eq!(command.value_period(), None);
+        assert!(command.config.state_query().is_none());
+
+        assert!(serde_json::from_value::<StateCommand>(
+            json!({"command": "mode", "args": ["--status"], "period": "0s"})
+        )
+        .is_err());
+    }
+}
@@ -1,18 +1,32 @@
 pub mod exec;
+pub mod packages;
 
+use crate::common::homeassistant::Entity;
 use async_trait::async_trait;
-use homeassistant_agent::model::Discovery;
 use std::borrow::Cow;
 use std::future::Future;
 use std::pin::Pin;
+use std::time::Duration;
 
-pub type CallbackFn = dyn FnOnce(Result<(), ()>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send;
+/// Called once a command completed, with its result and the new value of its entity (if any)
+pub type CallbackFn =
+    dyn FnOnce(Result<(), ()>, Option<String>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send;
 
-#[async_trait(?Send)]
+#[async_trait]
 pub trait Command: Send + Sync {
     async fn start(&self, payload: Cow<'_, str>, callback: Box<CallbackFn>);
 
-    fn describe_ha(&self) -> Option<Discovery> {
+    /// The current value of the entity, for commands taking input or reporting a state
+    async fn value(&self) -> Option<String> {
+        None
+    }
+
+    /// The interval of querying the value, if it reflects an external state
+    fn value_period(&self) -> Option<Duration> {
+        None
+    }
+
+    fn describe_ha(&self) -> Option<Entity> {
         None
     }
 }
@@ -0,0 +1,51 @@
+use crate::{
+    collector::packages::Packages,
+    command::CallbackFn,
+    common::homeassistant::{Component, Entity},
+    uplink::homeassistant::{PAYLOAD_RUNNING, PAYLOAD_STOPPED},
+};
+use async_trait::async_trait;
+use homeassistant_agent::model::{Availability, Discovery};
+use std::{borrow::Cow, sync::Arc};
+
+/// Install all pending package updates
+pub struct Command {
+    packages: Arc<Packages>,
+}
+
+impl Command {
+    pub fn new(packages: Arc<Packages>) -> Self {
+        Self { packages }
+    }
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn start(&self, _payload: Cow<'_, str>, callback: Box<CallbackFn>) {
+        log::info!("installing updates");
+
+        let packages = self.packages.clone();
+
+        tokio::spawn(async move {
+            let result = packages.install().await.map_err(|err| {
+                log::warn!("Failed to install updates: {err}");
+            });
+
+            (callback)(result, None).await;
+        });
+    }
+
+    fn describe_ha(&self) -> Option<Entity> {
+        Some(
+            Entity::from(Discovery {
+                unique_id: Some("packages_install".into()),
+                name: Some("Install updates".into()),
+                availability: vec![Availability::new("state")
+                    .payload_available(PAYLOAD_STOPPED)
+                    .payload_not_available(PAYLOAD_RUNNING)],
+                ..Default::default()
+            })
+            .component(Component::Button),
+        )
+    }
+}
@@ -0,0 +1,199 @@
+//! Home Assistant discovery model
+//!
+//! Extends the model of `homeassistant_agent` with information it doesn't support (yet).
+
+use homeassistant_agent::model::Discovery;
+use serde_json::{Map, Value};
+use std::fmt::{Display, Formatter};
+
+/// Category of an entity, entities without a category are shown as primary entities.
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityCategory {
+    /// Allows changing the configuration of a device
+    Config,
+    /// Exposes some configuration or diagnostics of a device
+    Diagnostic,
+}
+
+/// The component (platform) of an entity
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Component {
+    BinarySensor,
+    Button,
+    Number,
+    Select,
+    #[default]
+    Sensor,
+    Switch,
+    Text,
+    Update,
+}
+
+impl AsRef<str> for Component {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::BinarySensor => "binary_sensor",
+            Self::Button => "button",
+            Self::Number => "number",
+            Self::Select => "select",
+            Self::Sensor => "sensor",
+            Self::Switch => "switch",
+            Self::Text => "text",
+            Self::Update => "update",
+        }
+    }
+}
+
+impl Display for Component {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+/// An entity, announced to Home Assistant
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct Entity {
+    #[serde(flatten)]
+    pub discovery: Discovery,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity_category: Option<EntityCategory>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<Device>,
+
+    /// Component specific fields, not covered by the discovery model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+
+    /// The component of the entity
+    #[serde(skip)]
+    pub component: Component,
+
+    /// The name of a command, triggered by the entity
+    #[serde(skip)]
+    pub command: Option<String>,
+}
+
+impl From<Discovery> for Entity {
+    fn from(discovery: Discovery) -> Self {
+        Self {
+            discovery,
+            ..Default::default()
+        }
+    }
+}
+
+impl Entity {
+    pub fn category(mut self, category: EntityCategory) -> Self {
+        self.entity_category = Some(category);
+        self
+    }
+
+    /// Enable the entity when it gets added to Home Assistant, disabled entities can still be
+    /// enabled by the user.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.discovery.enabled_by_default = Some(enabled);
+        self
+    }
+
+    pub fn component(mut self, component: Component) -> Self {
+        self.component = component;
+        self
+    }
+
+    pub fn command(mut self, command: impl Into<String>) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+
+    pub fn extra(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.extra.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn device(mut self, device: &Device) -> Self {
+        // the device of the discovery would conflict with ours
+        self.discovery.device = None;
+        self.device = Some(device.clone());
+        self
+    }
+}
+
+/// Device information of a discovery message
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize)]
+pub struct Device {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub identifiers: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub connections: Vec<(String, String)>,
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hw_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sw_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggested_area: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configuration_url: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_entity() {
+        let device = Device {
+            identifiers: vec!["my-server".into()],
+            connections: vec![("mac".into(), "02:42:ac:11:00:02".into())],
+            name: Some("My server".into()),
+            suggested_area: Some("Basement".into()),
+            ..Default::default()
+        };
+
+        let entity = Entity::from(Discovery {
+            name: Some("Total memory".into()),
+            unique_id: Some("total".into()),
+            device: Some(homeassistant_agent::model::Device {
+                identifiers: vec!["other".into()],
+                name: None,
+                base_topic: None,
+                sw_version: None,
+                support_url: None,
+            }),
+            ..Default::default()
+        })
+        .category(EntityCategory::Diagnostic)
+        .enabled(false)
+        .device(&device);
+
+        assert_eq!(
+            serde_json::to_value(entity).unwrap(),
+            json!({
+                "name": "Total memory",
+                "unique_id": "total",
+                "device_class": null,
+                "entity_category": "diagnostic",
+                "enabled_by_default": false,
+                "device": {
+                    "identifiers": ["my-server"],
+                    "connections": [["mac", "02:42:ac:11:00:02"]],
+                    "name": "My server",
+                    "suggested_area": "Basement",
+                }
+            })
+        );
+    }
+}
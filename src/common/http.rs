@@ -1,16 +1,20 @@
+use crate::collector::tls;
 use actix_http::Request;
 use actix_service::IntoServiceFactory;
 use actix_web::body::MessageBody;
 use actix_web::dev::{AppConfig, Response, Service, ServiceFactory};
 use actix_web::*;
-use anyhow::bail;
-use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use std::any::Any;
 use std::fmt;
 use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::str::FromStr;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use std::time::Duration;
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, clap::Args, schemars::JsonSchema)]
+#[derive(
+    Clone, Debug, Default, serde::Serialize, serde::Deserialize, clap::Args, schemars::JsonSchema,
+)]
 pub struct Options {
     /// Bind host
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -22,17 +26,89 @@ pub struct Options {
     #[arg(long, env)]
     bind_port: Option<u16>,
 
+    /// Additional addresses to bind to. For example: `127.0.0.1:4242` or `[::1]:4242`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[arg(long, env, value_delimiter = ',')]
+    bind_addresses: Vec<SocketAddr>,
+
+    /// Path of a Unix domain socket to listen on.
+    ///
+    /// If set, and neither the bind host, port, nor additional addresses are set, the server will
+    /// only listen on the socket.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[arg(long, env)]
+    bind_unix: Option<PathBuf>,
+
+    /// File mode of the Unix domain socket, in octal notation. For example: `0660`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[arg(long, env)]
+    unix_mode: Option<String>,
+
+    /// Owner of the Unix domain socket, either a user name or ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[arg(long, env)]
+    unix_owner: Option<String>,
+
+    /// Group of the Unix domain socket, either a group name or ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[arg(long, env)]
+    unix_group: Option<String>,
+
     /// A TLS certificate
-    #[cfg(feature = "openssl")]
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[arg(long, env)]
     tls_certificate: Option<PathBuf>,
 
     /// A TLS key
-    #[cfg(feature = "openssl")]
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[arg(long, env)]
     tls_key: Option<PathBuf>,
+
+    /// Interval for checking the TLS key and certificate for changes, defaults to one minute.
+    ///
+    /// The files will also be reloaded when receiving `SIGHUP`.
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::utils::non_zero_duration"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    #[arg(long, env, value_parser = crate::utils::non_zero_duration::parse)]
+    tls_reload_interval: Option<Duration>,
+}
+
+impl Options {
+    /// Check if TLS is enabled
+    pub fn tls_enabled(&self) -> bool {
+        #[cfg(any(feature = "openssl", feature = "rustls"))]
+        return self.tls_key.is_some() || self.tls_certificate.is_some();
+        #[cfg(not(any(feature = "openssl", feature = "rustls")))]
+        return false;
+    }
+
+    /// The TCP addresses to bind to
+    fn tcp_addresses(&self, defaults: &Defaults) -> anyhow::Result<Vec<SocketAddr>> {
+        let mut addresses = self.bind_addresses.clone();
+
+        if self.bind_host.is_some()
+            || self.bind_port.is_some()
+            || (addresses.is_empty() && self.bind_unix.is_none())
+        {
+            addresses.push(SocketAddr::new(
+                self.bind_host
+                    .as_deref()
+                    .map(IpAddr::from_str)
+                    .transpose()?
+                    .unwrap_or(defaults.host),
+                self.bind_port.unwrap_or(defaults.port),
+            ));
+        }
+
+        Ok(addresses)
+    }
 }
 
 pub struct Defaults {
@@ -40,9 +116,35 @@ pub struct Defaults {
     pub host: IpAddr,
 }
 
+/// Credentials of a peer, connected through a Unix domain socket
+///
+/// Available through [`HttpRequest::conn_data`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn on_connect(connection: &dyn Any, extensions: &mut dev::Extensions) {
+    #[cfg(unix)]
+    if let Some(stream) = connection.downcast_ref::<rt::net::UnixStream>() {
+        match stream.peer_cred() {
+            Ok(credentials) => {
+                extensions.insert(PeerCredentials {
+                    uid: credentials.uid(),
+                    gid: credentials.gid(),
+                });
+            }
+            Err(err) => log::warn!("Failed to retrieve peer credentials: {err}"),
+        }
+    }
+}
+
 pub async fn run_server<F, I, S, B>(
     options: Options,
     defaults: Defaults,
+    tls: tls::State,
     factory: F,
 ) -> anyhow::Result<()>
 where
@@ -58,17 +160,15 @@ where
 
     B: MessageBody + 'static,
 {
-    let bind_addr = SocketAddr::new(
-        options
-            .bind_host
-            .as_deref()
-            .map(IpAddr::from_str)
-            .transpose()?
-            .unwrap_or(defaults.host),
-        options.bind_port.unwrap_or(defaults.port),
-    );
+    let addresses = options.tcp_addresses(&defaults)?;
 
-    log::info!("  Binding on: {}", bind_addr);
+    for address in &addresses {
+        log::info!("  Binding on: {address}");
+    }
+    if let Some(path) = &options.bind_unix {
+        log::info!("  Binding on: unix:{}", path.display());
+    }
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     log::info!(
         "  TLS - key: {}",
         options
@@ -77,6 +177,7 @@ where
             .map(|p| p.display().to_string())
             .unwrap_or_else(|| "<none>".to_string())
     );
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
     log::info!(
         "  TLS - certificate: {}",
         options
@@ -86,25 +187,66 @@ where
             .unwrap_or_else(|| "<none>".to_string())
     );
 
-    let server = HttpServer::new(factory);
+    let server = HttpServer::new(factory).on_connect(on_connect);
 
-    let server = match (options.tls_key, options.tls_certificate) {
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
+    let (server, certificate) = match (options.tls_key, options.tls_certificate) {
+        (Some(_), Some(_)) if addresses.is_empty() => {
+            anyhow::bail!("Enabling TLS requires at least one TCP address to bind to");
+        }
         (Some(key), Some(cert)) => {
-            #[cfg(feature = "openssl")]
-            {
-                let mut acceptor = SslAcceptor::mozilla_modern_v5(SslMethod::tls_server())?;
-                acceptor.set_certificate_chain_file(cert)?;
-                acceptor.set_private_key_file(key, SslFiletype::PEM)?;
-                server.bind_openssl(bind_addr, acceptor)?.run()
-            }
+            let certificate = super::tls::Certificate::new(key, cert, tls)?;
+
+            #[cfg(feature = "rustls")]
+            let server = server.bind_rustls_0_22(&addresses[..], certificate.rustls_config())?;
+            #[cfg(all(feature = "openssl", not(feature = "rustls")))]
+            let server = server.bind_openssl(&addresses[..], certificate.openssl_acceptor()?)?;
+
+            (server, Some(certificate))
         }
-        (None, None) => server.bind(bind_addr)?.run(),
+        (None, None) if addresses.is_empty() => (server, None),
+        (None, None) => (server.bind(&addresses[..])?, None),
         _ => {
-            bail!("Enabling TLS requires both --tls-key and --tls-certificate");
+            anyhow::bail!("Enabling TLS requires both --tls-key and --tls-certificate");
+        }
+    };
+
+    #[cfg(not(any(feature = "openssl", feature = "rustls")))]
+    let server = {
+        let _ = tls;
+        match addresses.is_empty() {
+            true => server,
+            false => server.bind(&addresses[..])?,
         }
     };
 
-    server.await?;
+    let server = match &options.bind_unix {
+        #[cfg(unix)]
+        Some(path) => server.listen_uds(super::unix::bind(
+            path,
+            options.unix_mode.as_deref(),
+            options.unix_owner.as_deref(),
+            options.unix_group.as_deref(),
+        )?)?,
+        #[cfg(not(unix))]
+        Some(_) => anyhow::bail!("Unix domain sockets are not supported on this platform"),
+        None => server,
+    };
+
+    #[cfg(any(feature = "openssl", feature = "rustls"))]
+    if let Some(certificate) = certificate {
+        let period = options
+            .tls_reload_interval
+            .unwrap_or(Duration::from_secs(60));
+        let server = server.run();
+        tokio::select! {
+            result = server => result?,
+            result = certificate.watch(period) => result?,
+        }
+        return Ok(());
+    }
+
+    server.run().await?;
 
     Ok(())
 }
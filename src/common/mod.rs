@@ -1 +1,6 @@
+pub mod homeassistant;
 pub mod http;
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+pub mod tls;
+#[cfg(unix)]
+pub mod unix;
@@ -0,0 +1,204 @@
+//! TLS backends for the HTTP server
+//!
+//! The backend is selected at compile time. If both the `openssl` and the `rustls` features are
+//! enabled, `rustls` is being used.
+//!
+//! The certificate can be reloaded at runtime. New connections will use the reloaded certificate,
+//! existing connections are not affected.
+
+use crate::collector::tls::State;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio::time::MissedTickBehavior;
+
+#[cfg(feature = "rustls")]
+type Current = Arc<rustls::sign::CertifiedKey>;
+#[cfg(all(feature = "openssl", not(feature = "rustls")))]
+type Current = openssl::ssl::SslContext;
+
+/// A certificate, loaded from a PEM encoded key and certificate chain, which can be reloaded.
+pub struct Certificate {
+    key: PathBuf,
+    certificate: PathBuf,
+    current: Arc<RwLock<Current>>,
+    state: State,
+}
+
+impl Certificate {
+    pub fn new(key: PathBuf, certificate: PathBuf, state: State) -> anyhow::Result<Self> {
+        let (current, expires) = load(&key, &certificate)?;
+        state.set_expires(expires);
+
+        Ok(Self {
+            key,
+            certificate,
+            current: Arc::new(RwLock::new(current)),
+            state,
+        })
+    }
+
+    /// Load the key and certificate again, and use it for new connections.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let (current, expires) = load(&self.key, &self.certificate)?;
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = current;
+        self.state.set_expires(expires);
+
+        Ok(())
+    }
+
+    /// Reload the certificate when the files change or when receiving `SIGHUP`.
+    pub async fn watch(self, period: Duration) -> anyhow::Result<()> {
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        let mut modified = self.modified();
+
+        loop {
+            #[cfg(unix)]
+            let hangup = hangup.recv();
+            #[cfg(not(unix))]
+            let hangup = futures::future::pending::<Option<()>>();
+
+            tokio::select! {
+                _ = interval.tick() => {
+                    let current = self.modified();
+                    if current == modified {
+                        continue;
+                    }
+                    modified = current;
+                    log::info!("TLS key or certificate changed, reloading");
+                }
+                _ = hangup => {
+                    log::info!("Received SIGHUP, reloading TLS key and certificate");
+                }
+            }
+
+            match self.reload() {
+                Ok(()) => log::info!("Reloaded TLS key and certificate"),
+                Err(err) => log::warn!("Failed to reload TLS key and certificate: {err}"),
+            }
+        }
+    }
+
+    fn modified(&self) -> [Option<SystemTime>; 2] {
+        [&self.key, &self.certificate]
+            .map(|path| path.metadata().and_then(|meta| meta.modified()).ok())
+    }
+
+    /// Create a rustls server configuration, using the current certificate.
+    #[cfg(feature = "rustls")]
+    pub fn rustls_config(&self) -> rustls::ServerConfig {
+        rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(Resolver(self.current.clone())))
+    }
+
+    /// Create an OpenSSL acceptor, using the current certificate.
+    #[cfg(all(feature = "openssl", not(feature = "rustls")))]
+    pub fn openssl_acceptor(&self) -> anyhow::Result<openssl::ssl::SslAcceptorBuilder> {
+        use openssl::ssl::SniError;
+
+        let mut acceptor = acceptor(&self.key, &self.certificate)?;
+
+        // swap in the current context, for every new connection
+        let current = self.current.clone();
+        acceptor.set_servername_callback(move |ssl, _alert| {
+            let context = current.read().unwrap_or_else(PoisonError::into_inner);
+            ssl.set_ssl_context(&context)
+                .map_err(|_| SniError::ALERT_FATAL)
+        });
+
+        Ok(acceptor)
+    }
+}
+
+#[cfg(feature = "rustls")]
+#[derive(Debug)]
+struct Resolver(Arc<RwLock<Current>>);
+
+#[cfg(feature = "rustls")]
+impl rustls::server::ResolvesServerCert for Resolver {
+    fn resolve(&self, _client_hello: rustls::server::ClientHello) -> Option<Current> {
+        Some(
+            self.0
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        )
+    }
+}
+
+#[cfg(feature = "rustls")]
+fn load(key: &Path, certificate: &Path) -> anyhow::Result<(Current, SystemTime)> {
+    use anyhow::Context;
+    use std::{fs::File, io::BufReader};
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(
+        File::open(certificate)
+            .with_context(|| format!("Opening certificate: {}", certificate.display()))?,
+    ))
+    .collect::<Result<Vec<_>, _>>()
+    .with_context(|| format!("Parsing certificate: {}", certificate.display()))?;
+
+    let expires = certs
+        .first()
+        .map(|cert| x509_parser::parse_x509_certificate(cert))
+        .transpose()
+        .with_context(|| format!("Parsing certificate: {}", certificate.display()))?
+        .map(|(_, cert)| cert.validity().not_after.timestamp())
+        .ok_or_else(|| anyhow::anyhow!("No certificate found in: {}", certificate.display()))?;
+
+    let key = rustls_pemfile::private_key(&mut BufReader::new(
+        File::open(key).with_context(|| format!("Opening key: {}", key.display()))?,
+    ))
+    .with_context(|| format!("Parsing key: {}", key.display()))?
+    .ok_or_else(|| anyhow::anyhow!("No private key found in: {}", key.display()))?;
+
+    let key = rustls::crypto::ring::sign::any_supported_type(&key)?;
+
+    Ok((
+        Arc::new(rustls::sign::CertifiedKey::new(certs, key)),
+        unix_time(expires),
+    ))
+}
+
+#[cfg(all(feature = "openssl", not(feature = "rustls")))]
+fn acceptor(key: &Path, certificate: &Path) -> anyhow::Result<openssl::ssl::SslAcceptorBuilder> {
+    use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+
+    let mut acceptor = SslAcceptor::mozilla_modern_v5(SslMethod::tls_server())?;
+    acceptor.set_certificate_chain_file(certificate)?;
+    acceptor.set_private_key_file(key, SslFiletype::PEM)?;
+    acceptor.check_private_key()?;
+
+    Ok(acceptor)
+}
+
+#[cfg(all(feature = "openssl", not(feature = "rustls")))]
+fn load(key: &Path, certificate: &Path) -> anyhow::Result<(Current, SystemTime)> {
+    use openssl::asn1::Asn1Time;
+
+    let context = acceptor(key, certificate)?.build().into_context();
+
+    let not_after = context
+        .certificate()
+        .ok_or_else(|| anyhow::anyhow!("No certificate found in: {}", certificate.display()))?
+        .not_after();
+    let diff = Asn1Time::from_unix(0)?.diff(not_after)?;
+    let expires = diff.days as i64 * 86400 + diff.secs as i64;
+
+    Ok((context, unix_time(expires)))
+}
+
+fn unix_time(seconds: i64) -> SystemTime {
+    match seconds {
+        n if n >= 0 => SystemTime::UNIX_EPOCH + Duration::from_secs(n as u64),
+        n => SystemTime::UNIX_EPOCH - Duration::from_secs(n.unsigned_abs()),
+    }
+}
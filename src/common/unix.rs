@@ -0,0 +1,140 @@
+//! Unix domain socket helpers
+
+use anyhow::{anyhow, bail, Context};
+use std::{
+    fs::Permissions,
+    os::unix::{fs::PermissionsExt, net::UnixListener},
+    path::Path,
+};
+
+/// Bind a Unix domain socket, applying file mode and ownership.
+///
+/// The socket is created in a private directory first, and only moved to its final location
+/// once the mode and ownership are applied. So that it is never reachable with the permissions
+/// of the umask. A stale socket file from a previous run will be removed.
+pub fn bind(
+    path: &Path,
+    mode: Option<&str>,
+    owner: Option<&str>,
+    group: Option<&str>,
+) -> anyhow::Result<UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt};
+
+    if let Ok(meta) = path.symlink_metadata() {
+        if meta.file_type().is_socket() {
+            std::fs::remove_file(path)
+                .with_context(|| format!("Removing stale socket: {}", path.display()))?;
+        } else {
+            bail!("Not a socket, refusing to replace: {}", path.display());
+        }
+    }
+
+    let mode = mode
+        .map(|mode| {
+            u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                .with_context(|| format!("Invalid file mode: {mode}"))
+        })
+        .transpose()?;
+    let uid = owner
+        .map(|owner| resolve_id(owner, "/etc/passwd"))
+        .transpose()?;
+    let gid = group
+        .map(|group| resolve_id(group, "/etc/group"))
+        .transpose()?;
+
+    // next to the socket, as it must be on the same file system for renaming it
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid socket path: {}", path.display()))?;
+    let private = path.with_file_name(format!(
+        ".{}.{}",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private)
+        .with_context(|| format!("Creating directory: {}", private.display()))?;
+
+    let result = (|| {
+        let temp = private.join(name);
+        let listener = UnixListener::bind(&temp)
+            .with_context(|| format!("Binding to socket: {}", path.display()))?;
+
+        if let Some(mode) = mode {
+            std::fs::set_permissions(&temp, Permissions::from_mode(mode))?;
+        }
+        if uid.is_some() || gid.is_some() {
+            std::os::unix::fs::chown(&temp, uid, gid)
+                .with_context(|| format!("Changing ownership of socket: {}", path.display()))?;
+        }
+
+        std::fs::rename(&temp, path)
+            .with_context(|| format!("Moving socket to: {}", path.display()))?;
+
+        Ok(listener)
+    })();
+
+    let _ = std::fs::remove_dir_all(&private);
+
+    result
+}
+
+/// Resolve a user or group, either given by its numeric ID or by looking up its name.
+fn resolve_id(value: &str, database: &str) -> anyhow::Result<u32> {
+    if let Ok(id) = value.parse() {
+        return Ok(id);
+    }
+
+    let content =
+        std::fs::read_to_string(database).with_context(|| format!("Reading {database}"))?;
+    lookup_id(&content, value).ok_or_else(|| anyhow!("Unable to find '{value}' in {database}"))
+}
+
+/// Look up an ID by name, from the content of a `passwd` or `group` file.
+fn lookup_id(content: &str, name: &str) -> Option<u32> {
+    content
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.first() == Some(&name))
+        .and_then(|fields| fields.get(2)?.parse().ok())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bind() {
+        use std::os::unix::fs::FileTypeExt;
+
+        let dir = std::env::temp_dir().join(format!("resymo-test-bind-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("agent.sock");
+
+        let _listener = bind(&path, Some("0600"), None, None).unwrap();
+        let meta = path.symlink_metadata().unwrap();
+        assert!(meta.file_type().is_socket());
+        assert_eq!(meta.permissions().mode() & 0o777, 0o600);
+        // only the socket is left
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        // replace the stale socket, but nothing else
+        bind(&path, None, None, None).unwrap();
+        let other = dir.join("other");
+        std::fs::write(&other, "").unwrap();
+        assert!(bind(&other, None, None, None).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_lookup_id() {
+        let passwd = r#"root:x:0:0:root:/root:/bin/bash
+resymo:x:990:985:ReSyMo Agent:/var/lib/resymo:/sbin/nologin
+"#;
+        assert_eq!(lookup_id(passwd, "root"), Some(0));
+        assert_eq!(lookup_id(passwd, "resymo"), Some(990));
+        assert_eq!(lookup_id(passwd, "resy"), None);
+    }
+}
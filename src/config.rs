@@ -8,8 +8,12 @@
 //! run --package resymo-agent --example gen_schema
 //! ```
 
-use crate::{collector, command};
+use crate::{
+    collector, command,
+    common::homeassistant::{Entity, EntityCategory},
+};
 use crate::{uplink, utils::is_default};
+use std::collections::BTreeMap;
 
 /// Agent configuration
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
@@ -33,6 +37,38 @@ pub struct Uplinks {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub homeassistant: Option<uplink::homeassistant::Options>,
+
+    /// Generic MQTT, publishing plain JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<uplink::mqtt::Options>,
+
+    /// InfluxDB, using the line protocol
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub influxdb: Option<uplink::influxdb::Options>,
+
+    /// Graphite, using the plaintext protocol or StatsD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graphite: Option<uplink::graphite::Options>,
+
+    /// OpenTelemetry, using OTLP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp: Option<uplink::otlp::Options>,
+
+    /// Webhooks, sending snapshots or alert events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<uplink::webhook::Options>,
+
+    /// Zabbix, as passive agent or active sender
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zabbix: Option<uplink::zabbix::Options>,
+
+    /// Checkmk, serving the agent output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkmk: Option<uplink::checkmk::Options>,
+
+    /// JSON lines, written to a file or stdout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<uplink::file::Options>,
 }
 
 /// Common collector settings
@@ -41,6 +77,35 @@ pub struct Uplinks {
 pub struct CommonCollector {
     #[serde(default, skip_serializing_if = "is_default")]
     pub disabled: bool,
+
+    /// Overrides for the Home Assistant entities, by entity ID
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub entities: BTreeMap<String, EntityOptions>,
+}
+
+/// Overrides for a Home Assistant entity
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityOptions {
+    /// Enable the entity when it gets added to Home Assistant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    /// The category of the entity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<EntityCategory>,
+}
+
+impl EntityOptions {
+    pub fn apply(&self, mut entity: Entity) -> Entity {
+        if let Some(enabled) = self.enabled {
+            entity.discovery.enabled_by_default = Some(enabled);
+        }
+        if let Some(category) = self.category {
+            entity.entity_category = Some(category);
+        }
+        entity
+    }
 }
 
 /// Collector configurations
@@ -63,6 +128,14 @@ pub struct Collectors {
     #[serde(default)]
     pub disk_free: CommonCollector,
 
+    /// Pending package updates
+    #[serde(default)]
+    pub packages: collector::packages::Configuration,
+
+    /// Reboot required
+    #[serde(default)]
+    pub reboot_required: collector::reboot_required::Configuration,
+
     /// Exec
     #[serde(default)]
     pub exec: collector::exec::Configuration,
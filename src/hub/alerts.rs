@@ -0,0 +1,120 @@
+//! Alerts of unreachable agents, sent to webhooks
+
+use crate::uplink::webhook::{Endpoint, Sender};
+use anyhow::Context;
+use std::{collections::BTreeMap, sync::Arc, time::SystemTime};
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertOptions {
+    /// Number of failed scrapes in a row, before an agent is considered unreachable
+    #[serde(default = "default::unreachable_after")]
+    pub unreachable_after: u32,
+
+    /// Webhooks receiving an event when an agent becomes unreachable, and when it is reachable again
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub webhooks: BTreeMap<String, Endpoint>,
+}
+
+impl Default for AlertOptions {
+    fn default() -> Self {
+        Self {
+            unreachable_after: default::unreachable_after(),
+            webhooks: Default::default(),
+        }
+    }
+}
+
+mod default {
+    pub const fn unreachable_after() -> u32 {
+        3
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Status {
+    Firing,
+    Resolved,
+}
+
+/// The body sent to the webhooks, in the same shape as the alerts of the webhook uplink
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Event {
+    pub alert: &'static str,
+    pub status: Status,
+    pub agent: String,
+    pub message: String,
+    pub timestamp: String,
+}
+
+impl Event {
+    pub fn unreachable(agent: &str, status: Status, err: Option<&str>) -> Self {
+        let message = match (status, err) {
+            (Status::Firing, Some(err)) => format!("Agent {agent} is unreachable: {err}"),
+            (Status::Firing, None) => format!("Agent {agent} is unreachable"),
+            (Status::Resolved, _) => format!("Agent {agent} is reachable again"),
+        };
+
+        Self {
+            alert: "unreachable",
+            status,
+            agent: agent.to_string(),
+            message,
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+        }
+    }
+}
+
+/// Sends events to all webhooks
+#[derive(Clone, Default)]
+pub struct Notifier {
+    senders: Arc<Vec<(String, Sender)>>,
+}
+
+impl Notifier {
+    pub fn new(options: &AlertOptions) -> anyhow::Result<Self> {
+        let senders = options
+            .webhooks
+            .iter()
+            .map(|(name, endpoint)| {
+                let sender = Sender::new(endpoint)
+                    .with_context(|| format!("Failed to set up webhook: {name}"))?;
+                Ok((name.clone(), sender))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            senders: Arc::new(senders),
+        })
+    }
+
+    /// Send the event in the background, as retries must not delay scraping
+    pub fn notify(&self, event: Event) {
+        match event.status {
+            Status::Firing => log::warn!("{}", event.message),
+            Status::Resolved => log::info!("{}", event.message),
+        }
+
+        if self.senders.is_empty() {
+            return;
+        }
+
+        let senders = self.senders.clone();
+        tokio::spawn(async move {
+            let body = match serde_json::to_string(&event) {
+                Ok(body) => body,
+                Err(err) => {
+                    log::warn!("Failed to encode event: {err}");
+                    return;
+                }
+            };
+
+            for (name, sender) in senders.iter() {
+                if let Err(err) = sender.send(body.clone()).await {
+                    log::warn!("Failed to send event to webhook '{name}': {err}");
+                }
+            }
+        });
+    }
+}
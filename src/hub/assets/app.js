@@ -0,0 +1,282 @@
+"use strict";
+
+const REFRESH_INTERVAL = 10000;
+const TOKEN_KEY = "resymo.hub.token";
+
+// fields which contain a number of bytes
+const BYTE_FIELDS = new Set(["free", "total", "used", "available"]);
+// fields which contain a fraction (0…1)
+const FRACTION_FIELDS = new Set(["usage", "percentage"]);
+
+const SVG = "http://www.w3.org/2000/svg";
+
+let token = localStorage.getItem(TOKEN_KEY);
+let selected = new URLSearchParams(location.hash.slice(1)).get("agent");
+
+class UnauthorizedError extends Error {
+}
+
+async function api(path) {
+    const headers = token ? {"Authorization": `Bearer ${token}`} : {};
+    const response = await fetch(`api/v1/${path}`, {headers});
+    if (response.status === 401) {
+        throw new UnauthorizedError("Unauthorized");
+    }
+    if (!response.ok) {
+        throw new Error(`${response.status} ${response.statusText}`);
+    }
+    return response.json();
+}
+
+function element(name, attributes = {}, ...children) {
+    const result = document.createElement(name);
+    for (const [key, value] of Object.entries(attributes)) {
+        result.setAttribute(key, value);
+    }
+    result.append(...children);
+    return result;
+}
+
+function svg(name, attributes = {}, ...children) {
+    const result = document.createElementNS(SVG, name);
+    for (const [key, value] of Object.entries(attributes)) {
+        result.setAttribute(key, value);
+    }
+    result.append(...children);
+    return result;
+}
+
+function formatBytes(value) {
+    const units = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let unit = 0;
+    while (value >= 1024 && unit < units.length - 1) {
+        value /= 1024;
+        unit++;
+    }
+    return `${value.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`;
+}
+
+function formatValue(key, value) {
+    if (FRACTION_FIELDS.has(key)) {
+        return `${(value * 100).toFixed(1)} %`;
+    }
+    if (BYTE_FIELDS.has(key)) {
+        return formatBytes(value);
+    }
+    return Number.isInteger(value) ? value.toString() : value.toFixed(2);
+}
+
+function formatTime(timestamp) {
+    return timestamp ? new Date(timestamp).toLocaleString() : "never";
+}
+
+function gauge(label, fraction) {
+    const radius = 40;
+    const length = Math.PI * radius;
+    const clamped = Math.min(Math.max(fraction, 0), 1);
+    const level = clamped > 0.9 ? "danger" : clamped > 0.75 ? "warning" : "";
+    const arc = `M 10 50 A ${radius} ${radius} 0 0 1 90 50`;
+
+    return element("div", {class: "gauge"},
+        svg("svg", {viewBox: "0 0 100 60", width: "100", height: "60"},
+            svg("path", {d: arc, class: "track", fill: "none", "stroke-width": "10"}),
+            svg("path", {
+                d: arc, class: `value ${level}`, fill: "none", "stroke-width": "10",
+                "stroke-dasharray": `${clamped * length} ${length}`
+            }),
+            svg("text", {x: "50", y: "48", "text-anchor": "middle"}, `${(clamped * 100).toFixed(0)}%`),
+        ),
+        element("div", {}, label),
+    );
+}
+
+function sparkline(values) {
+    const width = 100;
+    const height = 20;
+    const result = svg("svg", {class: "sparkline", viewBox: `0 0 ${width} ${height}`, width, height});
+    if (values.length < 2) {
+        return result;
+    }
+
+    const min = Math.min(...values);
+    const max = Math.max(...values);
+    const range = max - min || 1;
+    const points = values.map((value, i) => {
+        const x = i / (values.length - 1) * width;
+        const y = height - 1 - (value - min) / range * (height - 2);
+        return `${x.toFixed(1)},${y.toFixed(1)}`;
+    });
+
+    result.append(svg("polyline", {points: points.join(" ")}));
+    return result;
+}
+
+function lookup(value, path) {
+    for (const segment of path) {
+        if (value === null || typeof value !== "object") {
+            return undefined;
+        }
+        value = value[segment];
+    }
+    return value;
+}
+
+// walk the value tree, calling back for objects and leaves
+function walk(value, path, onObject, onLeaf) {
+    if (value !== null && typeof value === "object") {
+        onObject(value, path);
+        for (const key of Object.keys(value).sort()) {
+            walk(value[key], [...path, key], onObject, onLeaf);
+        }
+    } else {
+        onLeaf(value, path);
+    }
+}
+
+function row(label, value) {
+    return element("tr", {}, element("td", {}, label), element("td", {class: "value"}, value));
+}
+
+function renderAgent(agent) {
+    const summary = agent.summary;
+    const gauges = element("div", {class: "gauges"});
+    for (const [key, label] of [["memory", "memory"], ["swap", "swap"], ["disk", "disk"]]) {
+        if (typeof summary[key] === "number") {
+            gauges.append(gauge(label, summary[key]));
+        }
+    }
+
+    const rows = element("table");
+    if (typeof summary.load === "number") {
+        rows.append(row("load", summary.load.toFixed(2)));
+    }
+    if (typeof summary.updates === "number") {
+        rows.append(row("updates", `${summary.updates} (${summary.securityUpdates} security)`));
+    }
+    if (typeof summary.rebootRequired === "boolean") {
+        rows.append(row("reboot required", summary.rebootRequired ? "yes" : "no"));
+    }
+    rows.append(row("last seen", formatTime(agent.lastSeen)));
+
+    const card = element("article", {class: `card agent ${agent.health}`},
+        element("h2", {},
+            agent.name, " ",
+            element("span", {class: `badge ${agent.health}`}, agent.health),
+        ),
+        gauges, rows,
+    );
+    if (agent.error) {
+        card.append(element("pre", {}, agent.error));
+    }
+    if (agent.name === selected) {
+        card.classList.add("selected");
+    }
+    card.addEventListener("click", () => select(agent.name));
+    return card;
+}
+
+function renderCollector(name, value, history) {
+    const gauges = element("div", {class: "gauges"});
+    const rows = element("table");
+    const texts = element("div");
+
+    walk(value, [], (object, path) => {
+        if (typeof object.used === "number" && typeof object.total === "number" && object.total > 0) {
+            gauges.append(gauge(path.join(" ") || "used", object.used / object.total));
+        }
+    }, (leaf, path) => {
+        const key = path[path.length - 1] ?? name;
+        const label = path.join(" ") || name;
+
+        if (typeof leaf === "number") {
+            if (FRACTION_FIELDS.has(key)) {
+                gauges.append(gauge(path.slice(0, -1).join(" ") || key, leaf));
+            }
+            const values = history
+                .map((entry) => lookup(entry.values[name], path))
+                .filter((value) => typeof value === "number");
+            rows.append(element("tr", {},
+                element("td", {}, label),
+                element("td", {class: "value"}, formatValue(key, leaf)),
+                element("td", {}, sparkline(values)),
+            ));
+        } else if (key === "stdout" || key === "stderr") {
+            if (leaf) {
+                texts.append(element("pre", {}, leaf));
+            }
+        } else if (leaf !== null) {
+            rows.append(element("tr", {},
+                element("td", {}, label),
+                element("td", {class: "value"}, String(leaf)),
+                element("td"),
+            ));
+        }
+    });
+
+    return element("article", {class: "card"}, element("h2", {}, name), gauges, rows, texts);
+}
+
+async function refreshAgents() {
+    const [agents, alerts] = await Promise.all([api("agents"), api("alerts")]);
+
+    document.getElementById("alerts").replaceChildren(...alerts.map((alert) =>
+        element("li", {}, `${formatTime(alert.since)}: ${alert.message}`)));
+    document.getElementById("agents").replaceChildren(...agents.map(renderAgent));
+
+    const reachable = agents.filter((agent) => agent.health === "reachable").length;
+    document.getElementById("status").textContent =
+        `${reachable} of ${agents.length} agents reachable, last update: ${new Date().toLocaleTimeString()}`;
+}
+
+async function refreshDetails() {
+    const details = document.getElementById("details");
+    if (!selected) {
+        details.hidden = true;
+        return;
+    }
+
+    const name = encodeURIComponent(selected);
+    const [agent, history] = await Promise.all([api(`agents/${name}`), api(`agents/${name}/history`)]);
+    const values = agent.values ?? {};
+
+    document.getElementById("details-name").textContent = agent.name;
+    document.getElementById("collectors").replaceChildren(...Object.keys(values).sort()
+        .map((collector) => renderCollector(collector, values[collector], history)));
+    details.hidden = false;
+}
+
+function select(name) {
+    selected = selected === name ? null : name;
+    location.hash = selected ? `agent=${encodeURIComponent(selected)}` : "";
+    refresh();
+}
+
+function showLogin() {
+    const form = document.getElementById("login");
+    form.hidden = false;
+    document.getElementById("status").textContent = "Authentication required";
+}
+
+async function refresh() {
+    try {
+        await refreshAgents();
+        await refreshDetails();
+        document.getElementById("login").hidden = true;
+    } catch (err) {
+        if (err instanceof UnauthorizedError) {
+            showLogin();
+            return;
+        }
+        document.getElementById("status").textContent = `Failed to refresh: ${err.message}`;
+    }
+}
+
+document.getElementById("login").addEventListener("submit", (event) => {
+    event.preventDefault();
+    token = document.getElementById("token").value;
+    localStorage.setItem(TOKEN_KEY, token);
+    refresh();
+});
+
+refresh();
+setInterval(refresh, REFRESH_INTERVAL);
@@ -0,0 +1,356 @@
+//! Hub, scraping the HTTP API of many agents, and serving a combined dashboard and API
+
+mod alerts;
+mod scrape;
+mod server;
+mod store;
+mod summary;
+
+pub use alerts::AlertOptions;
+pub use scrape::TlsOptions;
+pub use server::ServerOptions;
+pub use store::{MemoryOptions, SqliteOptions, Storage};
+
+use alerts::{Event, Notifier, Status};
+use anyhow::Context;
+use scrape::Scraper;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+use store::{Entry, Store};
+use summary::Summary;
+use tokio::time::MissedTickBehavior;
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// The agents to scrape, by name
+    pub agents: BTreeMap<String, Agent>,
+
+    /// Interval of scraping the agents
+    #[serde(
+        with = "crate::utils::non_zero_duration",
+        default = "default::interval"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub interval: Duration,
+
+    /// Timeout of scraping a single agent
+    #[serde(with = "humantime_serde", default = "default::timeout")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub timeout: Duration,
+
+    /// Where to keep the history
+    #[serde(default)]
+    pub storage: Storage,
+
+    /// Alerting of unreachable agents
+    #[serde(default)]
+    pub alerts: AlertOptions,
+
+    /// The HTTP server, serving the dashboard and the API
+    #[serde(default)]
+    pub server: ServerOptions,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Agent {
+    /// The URL of the HTTP server uplink of the agent. For example: `https://my-server:4242`.
+    pub url: String,
+
+    /// The access token of the agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+
+    /// TLS options, e.g. for trusting a self-signed certificate, or presenting a client certificate
+    #[serde(default, skip_serializing_if = "crate::utils::is_default")]
+    pub tls: TlsOptions,
+}
+
+mod default {
+    use super::*;
+
+    pub const fn interval() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    pub const fn timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+enum Health {
+    /// Not scraped yet
+    #[default]
+    Unknown,
+    Reachable,
+    Unreachable,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentStatus {
+    name: String,
+    url: String,
+    health: Health,
+    /// Milliseconds since the epoch, of the last successful scrape
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_seen: Option<u64>,
+    /// Milliseconds since the epoch, of the scrape which made the agent unreachable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unreachable_since: Option<u64>,
+    /// Number of failed scrapes in a row
+    failures: u32,
+    /// The error of the last scrape
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    summary: Summary,
+}
+
+struct AgentState {
+    status: AgentStatus,
+    /// The state of the last successful scrape
+    values: Option<BTreeMap<String, Value>>,
+}
+
+/// The state of all agents
+struct Hub {
+    agents: Mutex<BTreeMap<String, AgentState>>,
+    store: Store,
+    notifier: Notifier,
+    unreachable_after: u32,
+}
+
+impl Hub {
+    fn new(
+        agents: &BTreeMap<String, Agent>,
+        store: Store,
+        notifier: Notifier,
+        unreachable_after: u32,
+    ) -> Self {
+        let agents = agents
+            .iter()
+            .map(|(name, agent)| {
+                let status = AgentStatus {
+                    name: name.clone(),
+                    url: agent.url.clone(),
+                    health: Health::Unknown,
+                    last_seen: None,
+                    unreachable_since: None,
+                    failures: 0,
+                    error: None,
+                    summary: Default::default(),
+                };
+                (
+                    name.clone(),
+                    AgentState {
+                        status,
+                        values: None,
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            agents: Mutex::new(agents),
+            store,
+            notifier,
+            unreachable_after: unreachable_after.max(1),
+        }
+    }
+
+    /// Restore the last state of the agents from the history, e.g. after a restart
+    async fn restore(&self) {
+        let names = self.lock().keys().cloned().collect::<Vec<_>>();
+        for name in names {
+            let entry = match self.store.history(&name, 1).await {
+                Ok(mut entries) => entries.pop(),
+                Err(err) => {
+                    log::warn!("Failed to restore state of '{name}': {err}");
+                    None
+                }
+            };
+
+            if let (Some(entry), Some(agent)) = (entry, self.lock().get_mut(&name)) {
+                agent.status.last_seen = Some(entry.timestamp);
+                agent.status.summary = Summary::new(&entry.values);
+                agent.values = Some(entry.values);
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, AgentState>> {
+        self.agents.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn agents(&self) -> Vec<AgentStatus> {
+        self.lock()
+            .values()
+            .map(|agent| agent.status.clone())
+            .collect()
+    }
+
+    fn agent(&self, name: &str) -> Option<(AgentStatus, Option<BTreeMap<String, Value>>)> {
+        self.lock()
+            .get(name)
+            .map(|agent| (agent.status.clone(), agent.values.clone()))
+    }
+
+    /// Record the result of scraping an agent
+    async fn scraped(&self, name: &str, result: anyhow::Result<BTreeMap<String, Value>>) {
+        let timestamp = now();
+
+        let event = {
+            let mut agents = self.lock();
+            let Some(agent) = agents.get_mut(name) else {
+                return;
+            };
+            let status = &mut agent.status;
+
+            match &result {
+                Ok(values) => {
+                    let recovered = status.health == Health::Unreachable;
+                    status.health = Health::Reachable;
+                    status.last_seen = Some(timestamp);
+                    status.unreachable_since = None;
+                    status.failures = 0;
+                    status.error = None;
+                    status.summary = Summary::new(values);
+                    agent.values = Some(values.clone());
+
+                    recovered.then(|| Event::unreachable(name, Status::Resolved, None))
+                }
+                Err(err) => {
+                    let err = format!("{err:#}");
+                    log::debug!("Failed to scrape '{name}': {err}");
+
+                    status.failures += 1;
+                    let event = (status.failures >= self.unreachable_after
+                        && status.health != Health::Unreachable)
+                        .then(|| Event::unreachable(name, Status::Firing, Some(&err)));
+                    if event.is_some() {
+                        status.health = Health::Unreachable;
+                        status.unreachable_since = Some(timestamp);
+                    }
+                    status.error = Some(err);
+
+                    event
+                }
+            }
+        };
+
+        if let Some(event) = event {
+            self.notifier.notify(event);
+        }
+
+        if let Ok(values) = result {
+            if let Err(err) = self.store.insert(name, Entry { timestamp, values }).await {
+                log::warn!("Failed to store state of '{name}': {err}");
+            }
+        }
+    }
+}
+
+/// Milliseconds since the epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+pub async fn run(config: Config) -> anyhow::Result<()> {
+    anyhow::ensure!(!config.agents.is_empty(), "No agents configured");
+
+    let scrapers = config
+        .agents
+        .iter()
+        .map(|(name, agent)| {
+            let scraper = Scraper::new(agent, config.timeout)
+                .with_context(|| format!("Failed to set up agent: {name}"))?;
+            Ok((name.clone(), scraper))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let hub = Arc::new(Hub::new(
+        &config.agents,
+        Store::open(config.storage).await?,
+        Notifier::new(&config.alerts)?,
+        config.alerts.unreachable_after,
+    ));
+    hub.restore().await;
+
+    log::info!("Scraping {} agents", scrapers.len());
+
+    let interval = config.interval;
+    let scraping = futures::future::join_all(
+        scrapers
+            .into_iter()
+            .map(|(name, scraper)| scrape(hub.clone(), name, scraper, interval)),
+    );
+
+    tokio::select! {
+        result = server::run(config.server, hub.clone()) => result,
+        _ = scraping => Ok(()),
+    }
+}
+
+async fn scrape(hub: Arc<Hub>, name: String, scraper: Scraper, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        hub.scraped(&name, scraper.scrape().await).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_unreachable() {
+        let agents = BTreeMap::from([(
+            "a".to_string(),
+            Agent {
+                url: "http://localhost:4242".into(),
+                token: None,
+                tls: Default::default(),
+            },
+        )]);
+        let store = Store::open(Default::default()).await.unwrap();
+        let hub = Hub::new(&agents, store, Notifier::default(), 2);
+
+        let health = || hub.agents()[0].health;
+        assert_eq!(health(), Health::Unknown);
+
+        hub.scraped("a", Err(anyhow::anyhow!("Connection refused")))
+            .await;
+        assert_eq!(health(), Health::Unknown);
+        hub.scraped("a", Err(anyhow::anyhow!("Connection refused")))
+            .await;
+        assert_eq!(health(), Health::Unreachable);
+        assert!(hub.agents()[0].unreachable_since.is_some());
+
+        let values = BTreeMap::from([(
+            "load_avg".to_string(),
+            json!({"one": 0.5, "five": 0.25, "fifteen": 0.125}),
+        )]);
+        hub.scraped("a", Ok(values.clone())).await;
+
+        let (status, latest) = hub.agent("a").unwrap();
+        assert_eq!(status.health, Health::Reachable);
+        assert_eq!(status.failures, 0);
+        assert_eq!(status.summary.load, Some(0.5));
+        assert_eq!(latest, Some(values));
+        assert_eq!(hub.store.history("a", 10).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,112 @@
+//! Scraping the state of an agent, from its HTTP API
+
+use super::Agent;
+use anyhow::Context;
+use serde_json::Value;
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+/// TLS options for connecting to an agent
+#[derive(
+    Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsOptions {
+    /// A CA certificate (PEM) to trust, in addition to the system's ones. For example, for self-signed certificates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_certificate: Option<PathBuf>,
+
+    /// A client certificate (PEM), for mutual TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<PathBuf>,
+
+    /// The key (PEM, PKCS #8) of the client certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+
+    /// Accept any certificate of the agent. This is discouraged, as it allows impersonating the agent.
+    #[serde(default, skip_serializing_if = "crate::utils::is_default")]
+    pub insecure: bool,
+}
+
+/// Fetches the state of all collectors of an agent
+pub struct Scraper {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl Scraper {
+    pub fn new(agent: &Agent, timeout: Duration) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder().timeout(timeout);
+        // same as for the HTTP server, rustls takes precedence
+        #[cfg(feature = "rustls")]
+        let client = client.use_rustls_tls();
+        let client = tls(client, &agent.tls)?;
+
+        Ok(Self {
+            client: client.build().context("Failed to create HTTP client")?,
+            url: format!("{}/api/v1/collect", agent.url.trim_end_matches('/')),
+            token: agent.token.clone(),
+        })
+    }
+
+    pub async fn scrape(&self) -> anyhow::Result<BTreeMap<String, Value>> {
+        let mut request = self.client.get(&self.url);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await?.error_for_status()?;
+        serde_json::from_slice(&response.bytes().await?).context("Failed to decode the state")
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+fn tls(
+    mut client: reqwest::ClientBuilder,
+    options: &TlsOptions,
+) -> anyhow::Result<reqwest::ClientBuilder> {
+    let read = |path: &PathBuf| {
+        std::fs::read(path).with_context(|| format!("Failed to read: {}", path.display()))
+    };
+
+    if let Some(path) = &options.ca_certificate {
+        client = client.add_root_certificate(
+            reqwest::Certificate::from_pem(&read(path)?)
+                .with_context(|| format!("Failed to load CA certificate: {}", path.display()))?,
+        );
+    }
+
+    match (&options.certificate, &options.key) {
+        (Some(certificate), Some(key)) => {
+            let (certificate, key) = (read(certificate)?, read(key)?);
+            #[cfg(feature = "rustls")]
+            let identity = reqwest::Identity::from_pem(&[certificate, key].concat());
+            #[cfg(all(feature = "openssl", not(feature = "rustls")))]
+            let identity = reqwest::Identity::from_pkcs8_pem(&certificate, &key);
+            client = client.identity(identity.context("Failed to load client certificate")?);
+        }
+        (None, None) => {}
+        _ => anyhow::bail!("A client certificate requires both the certificate and the key"),
+    }
+
+    if options.insecure {
+        log::warn!(
+            "Accepting any certificate. This is discouraged as it allows impersonating agents."
+        );
+        client = client.danger_accept_invalid_certs(true);
+    }
+
+    Ok(client)
+}
+
+#[cfg(not(any(feature = "openssl", feature = "rustls")))]
+fn tls(
+    client: reqwest::ClientBuilder,
+    options: &TlsOptions,
+) -> anyhow::Result<reqwest::ClientBuilder> {
+    if options != &TlsOptions::default() {
+        anyhow::bail!("Built without support for TLS");
+    }
+    Ok(client)
+}
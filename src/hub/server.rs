@@ -0,0 +1,199 @@
+//! HTTP server of the hub, serving the dashboard and the API
+
+use super::{AgentStatus, Health, Hub};
+use crate::{collector::tls, common::http};
+use actix_web::{get, http::header, middleware::Logger, web, App, HttpResponse, Responder};
+use actix_web_extras::middleware::Condition;
+use actix_web_httpauth::{
+    extractors::{
+        bearer::{self, BearerAuth},
+        AuthenticationError,
+    },
+    middleware::HttpAuthentication,
+};
+use anyhow::bail;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv6Addr},
+    sync::Arc,
+};
+
+const DEFAULT_BIND_PORT: u16 = 4243;
+const DEFAULT_BIND_HOST: IpAddr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+
+const DEFAULT_HISTORY: usize = 360;
+
+const INDEX_HTML: &str = include_str!("assets/index.html");
+const APP_JS: &str = include_str!("assets/app.js");
+// shared with the dashboard of the agent
+const STYLE_CSS: &str = include_str!("../uplink/http_server/assets/style.css");
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerOptions {
+    /// Access token for the dashboard and the API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+
+    /// Allow disabling the authentication
+    #[serde(default)]
+    disable_authentication: bool,
+
+    #[serde(flatten)]
+    pub http: http::Options,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct AgentDetails {
+    #[serde(flatten)]
+    status: AgentStatus,
+    /// The state of the last successful scrape
+    values: Option<BTreeMap<String, Value>>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct Alert {
+    alert: &'static str,
+    agent: String,
+    /// Milliseconds since the epoch
+    since: Option<u64>,
+    message: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+struct HistoryQuery {
+    /// Maximum number of entries, the most recent ones
+    limit: Option<usize>,
+}
+
+#[get("/agents")]
+async fn agents(hub: web::Data<Hub>) -> impl Responder {
+    HttpResponse::Ok().json(hub.agents())
+}
+
+#[get("/agents/{name}")]
+async fn agent_details(path: web::Path<String>, hub: web::Data<Hub>) -> impl Responder {
+    match hub.agent(&path) {
+        Some((status, values)) => HttpResponse::Ok().json(AgentDetails { status, values }),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+#[get("/agents/{name}/history")]
+async fn history(
+    path: web::Path<String>,
+    query: web::Query<HistoryQuery>,
+    hub: web::Data<Hub>,
+) -> actix_web::Result<HttpResponse> {
+    if hub.agent(&path).is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY);
+    let entries = hub
+        .store
+        .history(&path, limit)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+#[get("/alerts")]
+async fn alerts(hub: web::Data<Hub>) -> impl Responder {
+    let alerts = hub
+        .agents()
+        .into_iter()
+        .filter(|agent| agent.health == Health::Unreachable)
+        .map(|agent| Alert {
+            alert: "unreachable",
+            since: agent.unreachable_since,
+            message: match &agent.error {
+                Some(err) => format!("Agent {} is unreachable: {err}", agent.name),
+                None => format!("Agent {} is unreachable", agent.name),
+            },
+            agent: agent.name,
+        })
+        .collect::<Vec<_>>();
+
+    HttpResponse::Ok().json(alerts)
+}
+
+#[get("/")]
+async fn index() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(INDEX_HTML)
+}
+
+#[get("/assets/{name}")]
+async fn assets(path: web::Path<String>) -> impl Responder {
+    let (content_type, body) = match path.as_str() {
+        "app.js" => ("text/javascript; charset=utf-8", APP_JS),
+        "style.css" => ("text/css; charset=utf-8", STYLE_CSS),
+        _ => return HttpResponse::NotFound().finish(),
+    };
+
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .body(body)
+}
+
+pub async fn run(options: ServerOptions, hub: Arc<Hub>) -> anyhow::Result<()> {
+    let hub = web::Data::from(hub);
+
+    let token = options.token.map(Arc::new);
+    let auth = token.map(|token| {
+        HttpAuthentication::with_fn(move |req, credentials: Option<BearerAuth>| {
+            let token = token.clone();
+            async move {
+                if credentials.is_some_and(|credentials| credentials.token() == *token) {
+                    Ok(req)
+                } else {
+                    let config = req
+                        .app_data::<bearer::Config>()
+                        .cloned()
+                        .unwrap_or_default()
+                        .scope("api");
+
+                    Err((AuthenticationError::from(config).into(), req))
+                }
+            }
+        })
+    });
+
+    if auth.is_none() {
+        if options.disable_authentication {
+            log::warn!("Running without access token. This is discouraged as it exposes the state of all agents.");
+        } else {
+            bail!("Running without access token. This is discouraged as it exposes the state of all agents. If you really want to do it, set disableAuthentication");
+        }
+    }
+
+    http::run_server(
+        options.http,
+        http::Defaults {
+            port: DEFAULT_BIND_PORT,
+            host: DEFAULT_BIND_HOST,
+        },
+        tls::State::default(),
+        move || {
+            let api = web::scope("/api/v1")
+                .wrap(Condition::from_option(auth.clone()))
+                .service(agents)
+                .service(history)
+                .service(agent_details)
+                .service(alerts);
+
+            App::new()
+                .app_data(hub.clone())
+                .wrap(Logger::default())
+                .service(api)
+                .service(index)
+                .service(assets)
+        },
+    )
+    .await
+}
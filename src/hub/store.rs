@@ -0,0 +1,295 @@
+//! History of the scraped state, kept in memory or in SQLite
+
+#[cfg(feature = "sqlite")]
+use anyhow::Context;
+use serde_json::Value;
+#[cfg(feature = "sqlite")]
+use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+/// Where to keep the history
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Storage {
+    /// Keep the most recent entries in memory
+    Memory(MemoryOptions),
+    /// Keep the entries in a SQLite database, surviving restarts
+    Sqlite(SqliteOptions),
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::Memory(MemoryOptions {
+            history: default::history(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryOptions {
+    /// Number of entries kept per agent
+    #[serde(default = "default::history")]
+    pub history: usize,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SqliteOptions {
+    /// The database file, created if it doesn't exist
+    pub path: PathBuf,
+
+    /// How long entries are kept
+    #[serde(with = "humantime_serde", default = "default::retention")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub retention: Duration,
+}
+
+mod default {
+    use super::*;
+
+    pub const fn history() -> usize {
+        360
+    }
+
+    pub const fn retention() -> Duration {
+        Duration::from_secs(7 * 24 * 60 * 60)
+    }
+}
+
+/// The state of an agent, at a point in time
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct Entry {
+    /// Milliseconds since the epoch
+    pub timestamp: u64,
+    pub values: BTreeMap<String, Value>,
+}
+
+pub enum Store {
+    Memory(Memory),
+    #[cfg(feature = "sqlite")]
+    Sqlite(Sqlite),
+}
+
+impl Store {
+    pub async fn open(storage: Storage) -> anyhow::Result<Self> {
+        match storage {
+            Storage::Memory(options) => Ok(Self::Memory(Memory::new(options.history))),
+            #[cfg(feature = "sqlite")]
+            Storage::Sqlite(options) => Ok(Self::Sqlite(Sqlite::open(options).await?)),
+            #[cfg(not(feature = "sqlite"))]
+            Storage::Sqlite(_) => anyhow::bail!("Built without support for SQLite"),
+        }
+    }
+
+    /// Add an entry to the history of an agent
+    pub async fn insert(&self, agent: &str, entry: Entry) -> anyhow::Result<()> {
+        match self {
+            Self::Memory(memory) => {
+                memory.insert(agent, entry);
+                Ok(())
+            }
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(sqlite) => sqlite.insert(agent, entry).await,
+        }
+    }
+
+    /// The most recent entries of an agent, oldest first
+    pub async fn history(&self, agent: &str, limit: usize) -> anyhow::Result<Vec<Entry>> {
+        match self {
+            Self::Memory(memory) => Ok(memory.history(agent, limit)),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(sqlite) => sqlite.history(agent, limit).await,
+        }
+    }
+}
+
+pub struct Memory {
+    entries: Mutex<HashMap<String, VecDeque<Entry>>>,
+    size: usize,
+}
+
+impl Memory {
+    fn new(size: usize) -> Self {
+        Self {
+            entries: Default::default(),
+            size,
+        }
+    }
+
+    fn insert(&self, agent: &str, entry: Entry) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let entries = entries.entry(agent.to_string()).or_default();
+        while !entries.is_empty() && entries.len() >= self.size {
+            entries.pop_front();
+        }
+        if self.size > 0 {
+            entries.push_back(entry);
+        }
+    }
+
+    fn history(&self, agent: &str, limit: usize) -> Vec<Entry> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(entries) = entries.get(agent) else {
+            return vec![];
+        };
+        entries
+            .iter()
+            .skip(entries.len().saturating_sub(limit))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub struct Sqlite {
+    connection: Arc<Mutex<rusqlite::Connection>>,
+    retention: Duration,
+}
+
+#[cfg(feature = "sqlite")]
+impl Sqlite {
+    async fn open(options: SqliteOptions) -> anyhow::Result<Self> {
+        let path = options.path;
+        let connection = tokio::task::spawn_blocking(move || {
+            let connection = rusqlite::Connection::open(&path)
+                .with_context(|| format!("Failed to open database: {}", path.display()))?;
+            connection.execute_batch(
+                r#"
+CREATE TABLE IF NOT EXISTS history (
+    agent TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    "values" TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS history_agent_timestamp ON history (agent, timestamp);
+"#,
+            )?;
+            Ok::<_, anyhow::Error>(connection)
+        })
+        .await??;
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            retention: options.retention,
+        })
+    }
+
+    /// Run a blocking operation on the connection
+    async fn with<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&rusqlite::Connection) -> anyhow::Result<T> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let connection = connection.lock().unwrap_or_else(PoisonError::into_inner);
+            f(&connection)
+        })
+        .await?
+    }
+
+    async fn insert(&self, agent: &str, entry: Entry) -> anyhow::Result<()> {
+        let agent = agent.to_string();
+        let values = serde_json::to_string(&entry.values)?;
+        let expired = entry
+            .timestamp
+            .saturating_sub(self.retention.as_millis() as u64);
+
+        self.with(move |connection| {
+            connection.execute(
+                r#"INSERT INTO history (agent, timestamp, "values") VALUES (?1, ?2, ?3)"#,
+                rusqlite::params![agent, entry.timestamp as i64, values],
+            )?;
+            connection.execute(
+                "DELETE FROM history WHERE agent = ?1 AND timestamp < ?2",
+                rusqlite::params![agent, expired as i64],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn history(&self, agent: &str, limit: usize) -> anyhow::Result<Vec<Entry>> {
+        let agent = agent.to_string();
+
+        let mut result = self
+            .with(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    r#"SELECT timestamp, "values" FROM history WHERE agent = ?1 ORDER BY timestamp DESC LIMIT ?2"#,
+                )?;
+                let rows = statement.query_map(
+                    rusqlite::params![agent, limit as i64],
+                    |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+                )?;
+
+                let mut result = vec![];
+                for row in rows {
+                    let (timestamp, values) = row?;
+                    result.push(Entry {
+                        timestamp: timestamp as u64,
+                        values: serde_json::from_str(&values)?,
+                    });
+                }
+                Ok(result)
+            })
+            .await?;
+
+        result.reverse();
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(timestamp: u64) -> Entry {
+        Entry {
+            timestamp,
+            values: BTreeMap::from([("load_avg".to_string(), json!({"one": timestamp}))]),
+        }
+    }
+
+    async fn assert_store(store: Store) {
+        for timestamp in 1..=4 {
+            store.insert("a", entry(timestamp)).await.unwrap();
+        }
+        store.insert("b", entry(5)).await.unwrap();
+
+        assert_eq!(store.history("a", 10).await.unwrap(), [2, 3, 4].map(entry));
+        assert_eq!(store.history("a", 2).await.unwrap(), [3, 4].map(entry));
+        assert_eq!(store.history("b", 10).await.unwrap(), [entry(5)]);
+        assert!(store.history("c", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_memory() {
+        let store = Store::open(Storage::Memory(MemoryOptions { history: 3 }))
+            .await
+            .unwrap();
+        assert_store(store).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite() {
+        let dir = std::env::temp_dir().join(format!("resymo-hub-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let store = Store::open(Storage::Sqlite(SqliteOptions {
+            path: dir.join("hub.db"),
+            retention: Duration::from_millis(2),
+        }))
+        .await
+        .unwrap();
+        assert_store(store).await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+//! A summary of the state of an agent, decoded using the status types of the collectors
+
+use crate::collector::{disk_free, load_avg, memory, packages, reboot_required, swap};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Key figures of an agent, for the overview. Missing collectors, or ones using a custom name, are skipped.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    /// Used memory, as fraction of the total memory (0…1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<f64>,
+    /// Used swap space, as fraction of the total swap space (0…1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap: Option<f64>,
+    /// Load average over the last minute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load: Option<f64>,
+    /// Highest usage of all disks, as fraction (0…1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disk: Option<f64>,
+    /// Number of pending updates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updates: Option<usize>,
+    /// Number of pending security updates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_updates: Option<usize>,
+    /// If a reboot is required
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reboot_required: Option<bool>,
+}
+
+impl Summary {
+    pub fn new(state: &BTreeMap<String, Value>) -> Self {
+        let mut result = Self::default();
+
+        if let Some(memory) = decode::<memory::Status>(state, "memory") {
+            result.memory = fraction(memory.total.saturating_sub(memory.available), memory.total);
+        }
+        if let Some(swap) = decode::<swap::Status>(state, "swap") {
+            result.swap = fraction(swap.used, swap.total);
+        }
+        if let Some(load_avg) = decode::<load_avg::Status>(state, "load_avg") {
+            result.load = Some(load_avg.one);
+        }
+        if let Some(disk_free) = decode::<disk_free::Status>(state, "disk_free") {
+            result.disk = disk_free
+                .disks
+                .values()
+                .map(|disk| disk.usage)
+                .max_by(f64::total_cmp);
+        }
+        if let Some(packages) = decode::<packages::Status>(state, "packages") {
+            result.updates = Some(packages.pending);
+            result.security_updates = Some(packages.security);
+        }
+        if let Some(reboot_required) = decode::<reboot_required::Status>(state, "reboot_required") {
+            result.reboot_required = Some(reboot_required.required);
+        }
+
+        result
+    }
+}
+
+fn decode<T: DeserializeOwned>(state: &BTreeMap<String, Value>, name: &str) -> Option<T> {
+    let value = state.get(name)?;
+    match serde_json::from_value(value.clone()) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            // e.g. an agent of a different version
+            log::debug!("Failed to decode state of '{name}': {err}");
+            None
+        }
+    }
+}
+
+fn fraction(value: u64, total: u64) -> Option<f64> {
+    (total > 0).then(|| value as f64 / total as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_summary() {
+        let state = BTreeMap::from([
+            (
+                "memory".to_string(),
+                json!({"free": 100, "total": 1000, "used": 600, "available": 250}),
+            ),
+            (
+                "swap".to_string(),
+                json!({"free": 0, "total": 0, "used": 0, "percentage": 0.0}),
+            ),
+            (
+                "load_avg".to_string(),
+                json!({"one": 1.5, "five": 1.0, "fifteen": 0.5}),
+            ),
+            (
+                "disk_free".to_string(),
+                json!({"disks": {
+                    "/dev/sda1": {"total": 100, "free": 50, "usage": 0.5},
+                    "/dev/sdb1": {"total": 100, "free": 10, "usage": 0.9},
+                }}),
+            ),
+            ("reboot_required".to_string(), json!({"required": "maybe"})),
+        ]);
+
+        assert_eq!(
+            Summary::new(&state),
+            Summary {
+                memory: Some(0.75),
+                swap: None,
+                load: Some(1.5),
+                disk: Some(0.9),
+                updates: None,
+                security_updates: None,
+                reboot_required: None,
+            }
+        );
+    }
+}
@@ -2,6 +2,7 @@ pub mod collector;
 pub mod command;
 pub mod common;
 pub mod config;
+pub mod hub;
 pub mod manager;
 pub mod uplink;
 
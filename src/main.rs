@@ -1,6 +1,6 @@
 use anyhow::Context;
 use clap::Parser;
-use resymo_agent::{config::Config, uplink};
+use resymo_agent::{collector, config::Config, uplink};
 use std::{future::Future, path::PathBuf, pin::Pin, process::ExitCode, sync::Arc};
 use tokio::signal;
 
@@ -24,6 +24,15 @@ pub struct Cli {
     /// Path to the configuration file
     #[arg(short, long, env, default_value = config_file())]
     config: PathBuf,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+pub enum Command {
+    /// Print the output of the Checkmk agent, and exit
+    Checkmk,
 }
 
 fn config_file() -> String {
@@ -67,7 +76,24 @@ async fn main() -> anyhow::Result<ExitCode> {
             .with_context(|| format!("Reading configuration file: '{}'", cli.config.display()))?,
     )?;
 
-    let manager = Arc::new(Manager::try_from((config.collectors, config.commands))?);
+    let mut manager = Manager::try_from((config.collectors, config.commands))?;
+
+    let http_tls = collector::tls::State::default();
+    if let Some(options) = &config.uplinks.http_server {
+        if options.http.tls_enabled() {
+            manager.register_collector(
+                "http_server_tls",
+                collector::tls::Collector::new(http_tls.clone()),
+            );
+        }
+    }
+
+    let manager = Arc::new(manager);
+
+    if let Some(Command::Checkmk) = cli.command {
+        print!("{}", uplink::checkmk::output(&manager).await);
+        return Ok(ExitCode::SUCCESS);
+    }
 
     log::info!("Starting agent");
 
@@ -75,7 +101,7 @@ async fn main() -> anyhow::Result<ExitCode> {
     if let Some(options) = config.uplinks.http_server {
         log::info!("Starting HTTP server uplink");
         uplinks.push(Box::pin(async {
-            uplink::http_server::run(options, manager.clone()).await
+            uplink::http_server::run(options, manager.clone(), http_tls).await
         }));
     }
     if let Some(options) = config.uplinks.homeassistant {
@@ -84,9 +110,60 @@ async fn main() -> anyhow::Result<ExitCode> {
             uplink::homeassistant::run(options, manager.clone()).await
         }));
     }
+    if let Some(options) = config.uplinks.mqtt {
+        log::info!("Starting MQTT uplink");
+        uplinks.push(Box::pin(async {
+            uplink::mqtt::run(options, manager.clone()).await
+        }));
+    }
+    if let Some(options) = config.uplinks.influxdb {
+        log::info!("Starting InfluxDB uplink");
+        uplinks.push(Box::pin(async {
+            uplink::influxdb::run(options, manager.clone()).await
+        }));
+    }
+    if let Some(options) = config.uplinks.graphite {
+        log::info!("Starting Graphite uplink");
+        uplinks.push(Box::pin(async {
+            uplink::graphite::run(options, manager.clone()).await
+        }));
+    }
+    if let Some(options) = config.uplinks.otlp {
+        log::info!("Starting OpenTelemetry uplink");
+        uplinks.push(Box::pin(async {
+            uplink::otlp::run(options, manager.clone()).await
+        }));
+    }
+    if let Some(options) = config.uplinks.webhook {
+        log::info!("Starting webhook uplink");
+        uplinks.push(Box::pin(async {
+            uplink::webhook::run(options, manager.clone()).await
+        }));
+    }
+    if let Some(options) = config.uplinks.zabbix {
+        log::info!("Starting Zabbix uplink");
+        uplinks.push(Box::pin(async {
+            uplink::zabbix::run(options, manager.clone()).await
+        }));
+    }
+    if let Some(options) = config.uplinks.checkmk {
+        log::info!("Starting Checkmk uplink");
+        uplinks.push(Box::pin(async {
+            uplink::checkmk::run(options, manager.clone()).await
+        }));
+    }
+    if let Some(options) = config.uplinks.file {
+        log::info!("Starting file uplink");
+        uplinks.push(Box::pin(async {
+            uplink::file::run(options, manager.clone()).await
+        }));
+    }
 
     if uplinks.is_empty() {
-        log::warn!("No uplink configured");
+        log::warn!("No uplink configured, writing snapshots to stdout");
+        uplinks.push(Box::pin(async {
+            uplink::file::run(Default::default(), manager.clone()).await
+        }));
     }
 
     let mut tasks = uplinks;
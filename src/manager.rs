@@ -1,16 +1,20 @@
-use crate::collector::{self, Collector, Error};
-use crate::command::{self, Command};
+use crate::collector::{self, Collector, Customized, Error};
+use crate::command::{self, CallbackFn, Command};
 use crate::config::Commands;
 use crate::{
-    collector::{disk_free, load_avg, memory, swap},
+    collector::{disk_free, load_avg, memory, packages, reboot_required, swap},
     config::Collectors,
 };
+use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
 
 #[derive(Default)]
 pub struct Manager {
     pub collectors: HashMap<String, Box<dyn Collector>>,
     pub commands: HashMap<String, Box<dyn Command>>,
+    /// Number of currently running instances, by command
+    running: Arc<Mutex<HashMap<String, usize>>>,
 }
 
 impl Manager {
@@ -18,6 +22,7 @@ impl Manager {
         Self {
             collectors: Default::default(),
             commands: Default::default(),
+            running: Default::default(),
         }
     }
 
@@ -55,6 +60,53 @@ impl Manager {
         );
     }
 
+    /// Start a command, tracking it as running until it completed.
+    ///
+    /// Returns `false` if the command doesn't exist.
+    pub async fn start_command(
+        &self,
+        name: &str,
+        payload: Cow<'_, str>,
+        callback: Box<CallbackFn>,
+    ) -> bool {
+        let Some(command) = self.commands.get(name) else {
+            return false;
+        };
+
+        *self.lock_running().entry(name.to_string()).or_default() += 1;
+
+        let running = self.running.clone();
+        let name = name.to_string();
+        command
+            .start(
+                payload,
+                Box::new(move |result, value| {
+                    let mut running = running.lock().unwrap_or_else(|err| err.into_inner());
+                    if let Some(count) = running.get_mut(&name) {
+                        *count -= 1;
+                        if *count == 0 {
+                            running.remove(&name);
+                        }
+                    }
+                    drop(running);
+
+                    (callback)(result, value)
+                }),
+            )
+            .await;
+
+        true
+    }
+
+    /// Check if a command is currently running
+    pub fn is_running(&self, name: &str) -> bool {
+        self.lock_running().contains_key(name)
+    }
+
+    fn lock_running(&self) -> std::sync::MutexGuard<'_, HashMap<String, usize>> {
+        self.running.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
     pub async fn collect_one(&self, name: &str) -> Result<Option<serde_json::Value>, Error> {
         Ok(match self.collectors.get(name) {
             Some(collector) => Some(
@@ -91,19 +143,72 @@ impl TryFrom<(Collectors, Commands)> for Manager {
         // collectors
 
         if !collectors.memory.disabled {
-            manager.register_collector("memory", memory::Collector);
+            manager.register_collector(
+                "memory",
+                Customized::new(memory::Collector, collectors.memory.entities),
+            );
         }
         if !collectors.swap.disabled {
-            manager.register_collector("swap", swap::Collector);
+            manager.register_collector(
+                "swap",
+                Customized::new(swap::Collector, collectors.swap.entities),
+            );
         }
         if !collectors.disk_free.disabled {
-            manager.register_collector("disk_free", disk_free::Collector);
+            manager.register_collector(
+                "disk_free",
+                Customized::new(disk_free::Collector, collectors.disk_free.entities),
+            );
         }
         if !collectors.load_avg.disabled {
-            manager.register_collector("load_avg", load_avg::Collector);
+            manager.register_collector(
+                "load_avg",
+                Customized::new(load_avg::Collector, collectors.load_avg.entities),
+            );
+        }
+        if !collectors.packages.disabled {
+            let config = collectors.packages;
+            match config.backend.or_else(packages::BackendKind::detect) {
+                Some(kind) => {
+                    let packages = Arc::new(packages::Packages::new(
+                        kind,
+                        kind.backend(config.refresh),
+                        &config,
+                    ));
+                    manager.register_collector(
+                        "packages",
+                        Customized::new(
+                            packages::Collector::new(packages.clone()),
+                            config.common.entities,
+                        ),
+                    );
+                    if config.install {
+                        manager.register_command(
+                            packages::INSTALL_COMMAND,
+                            command::packages::Command::new(packages),
+                        );
+                    }
+                }
+                None => log::info!("No supported package manager found, not checking for updates"),
+            }
+        }
+        if !collectors.reboot_required.disabled {
+            let entities = collectors.reboot_required.common.entities.clone();
+            manager.register_collector(
+                "reboot_required",
+                Customized::new(
+                    reboot_required::Collector::new(collectors.reboot_required),
+                    entities,
+                ),
+            );
         }
         if !collectors.exec.disabled {
-            manager.extend_collectors(collector::exec::Collector::new(collectors.exec));
+            let entities = collectors.exec.common.entities.clone();
+            manager.extend_collectors(
+                collector::exec::Collector::new(collectors.exec)
+                    .into_iter()
+                    .map(|(name, collector)| (name, Customized::new(collector, entities.clone()))),
+            );
         }
 
         // commands
@@ -117,3 +222,47 @@ impl TryFrom<(Collectors, Commands)> for Manager {
         Ok(manager)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_trait::async_trait;
+
+    /// A command, which completes once its callback gets called
+    #[derive(Default)]
+    struct Pending(Arc<Mutex<Option<Box<CallbackFn>>>>);
+
+    #[async_trait]
+    impl Command for Pending {
+        async fn start(&self, _payload: Cow<'_, str>, callback: Box<CallbackFn>) {
+            *self.0.lock().unwrap() = Some(callback);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_running() {
+        let pending = Pending::default();
+        let callback = pending.0.clone();
+
+        let mut manager = Manager::new();
+        manager.register_command("test", pending);
+
+        assert!(!manager.is_running("test"));
+        assert!(
+            manager
+                .start_command("test", "".into(), Box::new(|_, _| Box::pin(async {})))
+                .await
+        );
+        assert!(manager.is_running("test"));
+
+        let callback = callback.lock().unwrap().take().unwrap();
+        (callback)(Ok(()), None).await;
+        assert!(!manager.is_running("test"));
+
+        assert!(
+            !manager
+                .start_command("other", "".into(), Box::new(|_, _| Box::pin(async {})))
+                .await
+        );
+    }
+}
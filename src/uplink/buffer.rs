@@ -0,0 +1,364 @@
+//! A ring buffer of timestamped snapshots, kept on disk until sent
+
+use anyhow::Context;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::PathBuf,
+    time::SystemTime,
+};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+/// Keeping snapshots on disk, replaying them once the target is reachable again
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// The file keeping the snapshots, must not be shared with other uplinks
+    pub path: PathBuf,
+
+    /// Maximum size of the file, in bytes, dropping the oldest snapshots when exceeded
+    #[serde(default = "default::max_size")]
+    pub max_size: u64,
+}
+
+mod default {
+    pub const fn max_size() -> u64 {
+        10 * 1024 * 1024
+    }
+}
+
+/// The state of all collectors, at a point in time
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Snapshot {
+    /// Nanoseconds since the UNIX epoch
+    pub timestamp: u64,
+    pub state: BTreeMap<String, Value>,
+}
+
+impl Snapshot {
+    pub fn now(state: BTreeMap<String, Value>) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64,
+            state,
+        }
+    }
+}
+
+struct Entry {
+    snapshot: Snapshot,
+    /// Size of the line in the file, including the newline
+    size: u64,
+}
+
+/// Snapshots, oldest first, mirrored to a file of JSON lines.
+///
+/// Sent snapshots stay in the file, skipped by a read offset kept next to it, until they take up
+/// half of the maximum size or nothing is left to send. Only then is the file rewritten.
+///
+/// Failing to write the file keeps the snapshots in memory, so that nothing is lost while the
+/// agent is running.
+pub struct Buffer {
+    path: PathBuf,
+    max_size: u64,
+    entries: VecDeque<Entry>,
+    size: u64,
+    /// Bytes at the start of the file, which already got sent
+    offset: u64,
+}
+
+impl Buffer {
+    /// Open the buffer, loading the snapshots of a previous run
+    pub async fn open(options: Options) -> anyhow::Result<Self> {
+        let mut buffer = Self {
+            path: options.path,
+            max_size: options.max_size,
+            entries: Default::default(),
+            size: 0,
+            offset: 0,
+        };
+
+        let content = match tokio::fs::read_to_string(&buffer.path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to read: {}", buffer.path.display()))
+            }
+        };
+
+        // a missing or invalid offset replays everything, rather than losing snapshots
+        let offset = match tokio::fs::read_to_string(buffer.offset_path()).await {
+            Ok(offset) => offset.trim().parse::<usize>().unwrap_or_default(),
+            Err(_) => 0,
+        };
+        let content = match offset {
+            0 => &content[..],
+            offset if content.as_bytes().get(offset - 1) == Some(&b'\n') => &content[offset..],
+            _ => {
+                log::warn!("Ignoring invalid offset of buffer: {offset}");
+                &content[..]
+            }
+        };
+
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(snapshot) => buffer.entries.push_back(Entry {
+                    snapshot,
+                    size: line.len() as u64 + 1,
+                }),
+                // e.g. the last line, when the agent was killed while writing
+                Err(err) => log::warn!("Skipping invalid snapshot: {err}"),
+            }
+        }
+        buffer.size = buffer.entries.iter().map(|entry| entry.size).sum();
+
+        if !buffer.entries.is_empty() {
+            log::info!(
+                "Loaded {} snapshots from: {}",
+                buffer.entries.len(),
+                buffer.path.display()
+            );
+        }
+
+        buffer.truncate();
+        buffer.persist().await?;
+
+        Ok(buffer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the snapshots, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Snapshot> {
+        self.entries.iter().map(|entry| &entry.snapshot)
+    }
+
+    /// Add a snapshot, dropping the oldest ones when the buffer is full
+    pub async fn push(&mut self, snapshot: Snapshot) -> anyhow::Result<()> {
+        let mut line = serde_json::to_string(&snapshot)?;
+        line.push('\n');
+
+        let size = line.len() as u64;
+        self.entries.push_back(Entry { snapshot, size });
+        self.size += size;
+
+        if self.truncate() {
+            return self.persist().await;
+        }
+
+        let result = async {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+            file.write_all(line.as_bytes()).await?;
+            file.flush().await
+        }
+        .await;
+
+        result.with_context(|| format!("Failed to write: {}", self.path.display()))
+    }
+
+    /// Remove the `n` oldest snapshots, once they got sent
+    pub async fn pop(&mut self, n: usize) -> anyhow::Result<()> {
+        let n = n.min(self.entries.len());
+        if n == 0 {
+            return Ok(());
+        }
+
+        let size = self.entries.drain(..n).map(|entry| entry.size).sum::<u64>();
+        self.size -= size;
+        self.offset += size;
+
+        if self.entries.is_empty() || self.offset > self.max_size / 2 {
+            return self.persist().await;
+        }
+
+        let path = self.offset_path();
+        tokio::fs::write(&path, self.offset.to_string())
+            .await
+            .with_context(|| format!("Failed to write: {}", path.display()))
+    }
+
+    /// Drop the oldest snapshots exceeding the maximum size, returning if any were dropped
+    fn truncate(&mut self) -> bool {
+        let mut dropped = 0;
+        // keep the latest snapshot, even if it alone exceeds the size
+        while self.size > self.max_size && self.entries.len() > 1 {
+            if let Some(entry) = self.entries.pop_front() {
+                self.size -= entry.size;
+                dropped += 1;
+            }
+        }
+
+        if dropped > 0 {
+            log::warn!("Buffer full, dropping {dropped} snapshots");
+        }
+
+        dropped > 0
+    }
+
+    /// The file keeping the offset, next to the snapshots
+    fn offset_path(&self) -> PathBuf {
+        let mut path = self.path.as_os_str().to_owned();
+        path.push(".offset");
+        PathBuf::from(path)
+    }
+
+    /// Replace the file with the current content, resetting the offset
+    async fn persist(&mut self) -> anyhow::Result<()> {
+        let mut content = String::with_capacity(self.size as usize);
+        for entry in &self.entries {
+            content.push_str(&serde_json::to_string(&entry.snapshot)?);
+            content.push('\n');
+        }
+
+        // write and rename, so that the file is never incomplete
+        let mut temp = self.path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        let offset = self.offset_path();
+
+        let result = async {
+            tokio::fs::write(&temp, content).await?;
+            // drop the offset first, being killed in between replays snapshots instead of losing them
+            match tokio::fs::remove_file(&offset).await {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+            tokio::fs::rename(&temp, &self.path).await
+        }
+        .await;
+
+        result.with_context(|| format!("Failed to write: {}", self.path.display()))?;
+        self.offset = 0;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn snapshot(timestamp: u64) -> Snapshot {
+        Snapshot {
+            timestamp,
+            state: BTreeMap::from([("memory".to_string(), json!({"free": timestamp}))]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_buffer() {
+        let dir = std::env::temp_dir().join(format!("resymo-buffer-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("buffer.jsonl");
+
+        // each line has 46 bytes
+        let options = Options {
+            path: path.clone(),
+            max_size: 100,
+        };
+
+        let mut buffer = Buffer::open(options.clone()).await.unwrap();
+        assert!(buffer.is_empty());
+        for timestamp in 1..=4 {
+            buffer.push(snapshot(timestamp)).await.unwrap();
+        }
+        assert_eq!(
+            buffer.iter().map(|s| s.timestamp).collect::<Vec<_>>(),
+            [3, 4]
+        );
+
+        // a previous run, killed while writing
+        tokio::fs::write(
+            &path,
+            std::fs::read_to_string(&path).unwrap() + r#"{"timestamp":5,"sta"#,
+        )
+        .await
+        .unwrap();
+
+        let mut buffer = Buffer::open(options.clone()).await.unwrap();
+        assert_eq!(
+            buffer.iter().cloned().collect::<Vec<_>>(),
+            [3, 4].map(snapshot)
+        );
+
+        buffer.pop(1).await.unwrap();
+        assert_eq!(buffer.len(), 1);
+        buffer.pop(5).await.unwrap();
+        assert!(buffer.is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_offset() {
+        let dir = std::env::temp_dir().join(format!("resymo-offset-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("buffer.jsonl");
+
+        // each line has 46 bytes, compacting after more than 250 bytes got sent
+        let options = Options {
+            path: path.clone(),
+            max_size: 500,
+        };
+
+        let mut buffer = Buffer::open(options.clone()).await.unwrap();
+        for timestamp in 1..=8 {
+            buffer.push(snapshot(timestamp)).await.unwrap();
+        }
+
+        // sending keeps the file, only moving the offset
+        buffer.pop(2).await.unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 8 * 46);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("buffer.jsonl.offset")).unwrap(),
+            "92"
+        );
+
+        // which survives a restart
+        let mut buffer = Buffer::open(options.clone()).await.unwrap();
+        assert_eq!(
+            buffer.iter().map(|s| s.timestamp).collect::<Vec<_>>(),
+            [3, 4, 5, 6, 7, 8]
+        );
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 6 * 46);
+        assert!(!dir.join("buffer.jsonl.offset").exists());
+
+        // compacting once half of the maximum size got sent, these lines have 48 bytes
+        for timestamp in 10..=11 {
+            buffer.push(snapshot(timestamp)).await.unwrap();
+        }
+        buffer.pop(5).await.unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 6 * 46 + 2 * 48);
+        buffer.pop(1).await.unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 2 * 48);
+        assert!(!dir.join("buffer.jsonl.offset").exists());
+
+        // an offset not at the start of a line replays everything
+        tokio::fs::write(dir.join("buffer.jsonl.offset"), "10")
+            .await
+            .unwrap();
+        let buffer = Buffer::open(options).await.unwrap();
+        assert_eq!(
+            buffer.iter().map(|s| s.timestamp).collect::<Vec<_>>(),
+            [10, 11]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
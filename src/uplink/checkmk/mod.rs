@@ -0,0 +1,155 @@
+//! Checkmk uplink, serving the output of the Checkmk agent
+
+mod sections;
+
+use crate::manager::Manager;
+use anyhow::Context;
+use sections::Processes;
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc, time::Duration};
+use sysinfo::{ProcessStatus, System};
+use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// The address to listen on
+    #[serde(default = "default::bind")]
+    pub bind: String,
+
+    /// Addresses of Checkmk sites allowed to connect, defaults to all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_hosts: Vec<IpAddr>,
+
+    /// Timeout of collecting and sending the output
+    #[serde(with = "humantime_serde", default = "default::timeout")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub timeout: Duration,
+}
+
+mod default {
+    use super::*;
+
+    pub fn bind() -> String {
+        "0.0.0.0:6556".into()
+    }
+
+    pub const fn timeout() -> Duration {
+        Duration::from_secs(30)
+    }
+}
+
+pub async fn run(options: Options, manager: Arc<Manager>) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(&options.bind)
+        .await
+        .with_context(|| format!("Failed to bind: {}", options.bind))?;
+    log::info!("Serving Checkmk agent output on: {}", options.bind);
+
+    if options.allowed_hosts.is_empty() {
+        log::warn!("Serving Checkmk agent output to all hosts");
+    }
+
+    loop {
+        let (mut stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                log::warn!("Failed to accept connection: {err}");
+                continue;
+            }
+        };
+
+        let ip = peer.ip().to_canonical();
+        if !options.allowed_hosts.is_empty() && !options.allowed_hosts.contains(&ip) {
+            log::warn!("Rejected connection from: {peer}");
+            continue;
+        }
+
+        let manager = manager.clone();
+        let timeout = options.timeout;
+        tokio::spawn(async move {
+            // the agent sends its output right away, without a request
+            let result = tokio::time::timeout(timeout, async {
+                let output = output(&manager).await;
+                stream.write_all(output.as_bytes()).await?;
+                stream.shutdown().await?;
+                Ok::<_, anyhow::Error>(())
+            })
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("Timeout")));
+
+            if let Err(err) = result {
+                log::info!("Failed to serve {peer}: {err}");
+            }
+        });
+    }
+}
+
+/// Render the agent output, from the state of all collectors
+pub async fn output(manager: &Manager) -> String {
+    // a single failing collector must not fail the whole output
+    let mut names = manager.collectors.keys().collect::<Vec<_>>();
+    names.sort();
+    let mut state = BTreeMap::new();
+    let mut failed = vec![];
+    for name in names {
+        match manager.collect_one(name).await {
+            Ok(Some(value)) => {
+                state.insert(name.clone(), value);
+            }
+            Ok(None) => {}
+            Err(err) => failed.push(sections::failed(name, &err.to_string())),
+        }
+    }
+
+    let mut lines = vec![
+        "<<<check_mk>>>".to_string(),
+        format!("Version: resymo-agent-{}", env!("CARGO_PKG_VERSION")),
+        format!("AgentOS: {}", std::env::consts::OS),
+    ];
+
+    if let Some(memory) = state.get("memory") {
+        lines.extend(sections::mem(memory, state.get("swap")).unwrap_or_default());
+    }
+    if let Some(disk_free) = state.get("disk_free") {
+        lines.extend(sections::df(disk_free).unwrap_or_default());
+    }
+    if let Some(load_avg) = state.get("load_avg") {
+        lines.extend(sections::cpu(load_avg, &processes()).unwrap_or_default());
+    }
+    lines.extend(sections::uptime(System::uptime()));
+
+    let local = state
+        .iter()
+        .filter(|(_, state)| sections::is_exec(state))
+        .map(|(name, state)| sections::local(name, state))
+        .chain(failed)
+        .collect::<Vec<_>>();
+    if !local.is_empty() {
+        lines.push("<<<local>>>".to_string());
+        lines.extend(local);
+    }
+
+    let mut output = lines.join("\n");
+    output.push('\n');
+    output
+}
+
+fn processes() -> Processes {
+    let mut system = System::new();
+    system.refresh_cpu();
+    system.refresh_processes();
+
+    let processes = system.processes();
+    Processes {
+        running: processes
+            .values()
+            .filter(|process| process.status() == ProcessStatus::Run)
+            .count(),
+        total: processes.len(),
+        last_pid: processes
+            .keys()
+            .map(|pid| pid.as_u32())
+            .max()
+            .unwrap_or_default(),
+        cpus: system.cpus().len(),
+    }
+}
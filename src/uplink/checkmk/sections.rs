@@ -0,0 +1,243 @@
+//! Rendering collector state as sections of the Checkmk agent output
+
+use serde_json::Value;
+
+/// Process information of the `cpu` section, which isn't part of any collector
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Processes {
+    pub running: usize,
+    pub total: usize,
+    pub last_pid: u32,
+    pub cpus: usize,
+}
+
+fn number(value: &Value, key: &str) -> Option<f64> {
+    value.get(key).and_then(Value::as_f64)
+}
+
+fn kb(value: f64) -> u64 {
+    (value / 1024.0) as u64
+}
+
+/// The `mem` section, in the format of `/proc/meminfo`
+pub fn mem(memory: &Value, swap: Option<&Value>) -> Option<Vec<String>> {
+    let total = number(memory, "total")?;
+    let free = number(memory, "free")?;
+    let used = number(memory, "used")?;
+
+    let mut result = vec![
+        "<<<mem>>>".to_string(),
+        format!("MemTotal: {} kB", kb(total)),
+        format!("MemFree: {} kB", kb(free)),
+    ];
+    if let Some(available) = number(memory, "available") {
+        result.push(format!("MemAvailable: {} kB", kb(available)));
+    }
+    // Checkmk considers buffers and caches as free, which is what's neither free nor used
+    result.push("Buffers: 0 kB".to_string());
+    result.push(format!("Cached: {} kB", kb((total - free - used).max(0.0))));
+
+    if let Some((total, free)) =
+        swap.and_then(|swap| Some((number(swap, "total")?, number(swap, "free")?)))
+    {
+        result.push(format!("SwapTotal: {} kB", kb(total)));
+        result.push(format!("SwapFree: {} kB", kb(free)));
+    }
+
+    Some(result)
+}
+
+/// The `df` section, in the format of `df -PTlk`
+pub fn df(disk_free: &Value) -> Option<Vec<String>> {
+    let disks = disk_free.get("disks")?.as_object()?;
+
+    let mut result = vec!["<<<df>>>".to_string()];
+    for (name, disk) in disks {
+        let (Some(total), Some(free)) = (number(disk, "total"), number(disk, "free")) else {
+            continue;
+        };
+        let used = (total - free).max(0.0);
+        let capacity = if total > 0.0 {
+            (used / total * 100.0).ceil() as u64
+        } else {
+            0
+        };
+        let file_system = disk
+            .get("file_system")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown");
+        let mount_point = disk
+            .get("mount_point")
+            .and_then(Value::as_str)
+            .unwrap_or(name);
+
+        // the output is split by whitespace, `df` escapes it
+        result.push(format!(
+            "{} {file_system} {} {} {} {capacity}% {}",
+            name.replace(' ', "\\040"),
+            kb(total),
+            kb(used),
+            kb(free),
+            mount_point.replace(' ', "\\040"),
+        ));
+    }
+
+    Some(result)
+}
+
+/// The `cpu` section, in the format of `/proc/loadavg`, followed by the number of CPUs
+pub fn cpu(load_avg: &Value, processes: &Processes) -> Option<Vec<String>> {
+    let (one, five, fifteen) = (
+        number(load_avg, "one")?,
+        number(load_avg, "five")?,
+        number(load_avg, "fifteen")?,
+    );
+
+    Some(vec![
+        "<<<cpu>>>".to_string(),
+        format!(
+            "{one:.2} {five:.2} {fifteen:.2} {}/{} {} {}",
+            processes.running, processes.total, processes.last_pid, processes.cpus
+        ),
+    ])
+}
+
+/// The `uptime` section, in seconds
+pub fn uptime(uptime: u64) -> Vec<String> {
+    vec!["<<<uptime>>>".to_string(), uptime.to_string()]
+}
+
+/// Check if the state is the one of an exec item
+pub fn is_exec(state: &Value) -> bool {
+    ["stdout", "stderr", "status"]
+        .iter()
+        .all(|key| state.get(key).is_some())
+}
+
+/// A local check (`<status> <service> <metrics> <details>`), for the state of an exec item
+pub fn local(name: &str, state: &Value) -> String {
+    let code = state.get("status").and_then(Value::as_i64);
+    // same as plugins of Nagios: 0 = OK, 1 = WARN, 2 = CRIT, others are UNKNOWN
+    let status = code.filter(|code| (0..=2).contains(code)).unwrap_or(3);
+
+    let stdout = state
+        .get("stdout")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .trim();
+    let metric = match stdout.parse::<f64>() {
+        Ok(value) => format!("value={value}"),
+        Err(_) => "-".to_string(),
+    };
+    let details = match stdout.lines().next() {
+        Some(line) if !line.trim().is_empty() => line.trim().to_string(),
+        _ => match code {
+            Some(code) => format!("Exit code {code}"),
+            None => "Terminated by signal".to_string(),
+        },
+    };
+
+    format!("{status} {} {metric} {details}", service(name))
+}
+
+/// A critical local check, for a collector which failed
+pub fn failed(name: &str, err: &str) -> String {
+    let details = err.lines().next().unwrap_or_default();
+    format!("2 {} - Failed to collect: {details}", service(name))
+}
+
+/// The name of the service, quoted if necessary
+fn service(name: &str) -> String {
+    if name.contains(' ') {
+        format!("\"{name}\"")
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sections() {
+        let mut out = vec![];
+        out.extend(
+            mem(
+                &json!({"total": 4096000, "free": 1024000, "used": 2048000, "available": 2048000}),
+                Some(&json!({"total": 2048000, "free": 2048000, "used": 0, "percentage": 0.0})),
+            )
+            .unwrap(),
+        );
+        out.extend(
+            df(&json!({"disks": {"/dev/sda1": {
+                "total": 4096000, "free": 1024000, "usage": 0.75,
+                "mount_point": "/", "file_system": "ext4"
+            }}}))
+            .unwrap(),
+        );
+        out.extend(
+            cpu(
+                &json!({"one": 0.5, "five": 0.25, "fifteen": 0.15}),
+                &Processes {
+                    running: 1,
+                    total: 200,
+                    last_pid: 4242,
+                    cpus: 4,
+                },
+            )
+            .unwrap(),
+        );
+        out.extend(uptime(3600));
+
+        assert_eq!(
+            out,
+            vec![
+                "<<<mem>>>",
+                "MemTotal: 4000 kB",
+                "MemFree: 1000 kB",
+                "MemAvailable: 2000 kB",
+                "Buffers: 0 kB",
+                "Cached: 1000 kB",
+                "SwapTotal: 2000 kB",
+                "SwapFree: 2000 kB",
+                "<<<df>>>",
+                "/dev/sda1 ext4 4000 3000 1000 75% /",
+                "<<<cpu>>>",
+                "0.50 0.25 0.15 1/200 4242 4",
+                "<<<uptime>>>",
+                "3600",
+            ]
+        );
+        assert_eq!(mem(&json!({}), None), None);
+    }
+
+    #[test]
+    fn test_local() {
+        let temperature = json!({"stdout": "42.5\n", "stderr": "", "status": 0});
+        assert!(is_exec(&temperature));
+        assert_eq!(
+            local("temperature", &temperature),
+            "0 temperature value=42.5 42.5"
+        );
+        assert_eq!(
+            local(
+                "backup job",
+                &json!({"stdout": "Last backup failed\nsee logs", "stderr": "", "status": 2}),
+            ),
+            r#"2 "backup job" - Last backup failed"#
+        );
+        assert_eq!(
+            local(
+                "broken",
+                &json!({"stdout": "", "stderr": "", "status": 127})
+            ),
+            "3 broken - Exit code 127"
+        );
+        assert_eq!(
+            failed("failing job", "Collector error: Command failed"),
+            r#"2 "failing job" - Failed to collect: Collector error: Command failed"#
+        );
+    }
+}
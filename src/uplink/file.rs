@@ -0,0 +1,326 @@
+//! File uplink, writing snapshots as JSON lines to a file or stdout
+
+use crate::manager::Manager;
+use crate::uplink::default_device_id;
+use anyhow::Context;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncWrite, AsyncWriteExt},
+    time::MissedTickBehavior,
+};
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// The device ID. Will default to the value of the `HOSTNAME` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+
+    /// The file to append to, defaults to stdout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+
+    /// Interval of collecting and writing a snapshot
+    #[serde(
+        with = "crate::utils::non_zero_duration",
+        default = "default::interval"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub interval: Duration,
+
+    /// Rotation of the file
+    #[serde(default)]
+    pub rotation: Rotation,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            device_id: None,
+            path: None,
+            interval: default::interval(),
+            rotation: Default::default(),
+        }
+    }
+}
+
+/// Rotating the file, renaming it to `<path>.1`, `<path>.2`, …
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Rotation {
+    /// Rotate when the file would exceed this size, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+
+    /// Rotate when the file was written to for this long
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub max_age: Option<Duration>,
+
+    /// Number of rotated files to keep
+    #[serde(default = "default::keep")]
+    pub keep: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            max_size: None,
+            max_age: None,
+            keep: default::keep(),
+        }
+    }
+}
+
+mod default {
+    use super::*;
+
+    pub const fn interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub const fn keep() -> usize {
+        5
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct Snapshot<'a> {
+    timestamp: String,
+    device: &'a str,
+    state: BTreeMap<String, Value>,
+}
+
+pub async fn run(options: Options, manager: Arc<Manager>) -> anyhow::Result<()> {
+    let device_id = options.device_id.unwrap_or_else(default_device_id);
+
+    let mut writer = match options.path {
+        Some(path) => Writer::File(RotatingFile::new(path, options.rotation)),
+        None => Writer::Stdout(tokio::io::stdout()),
+    };
+
+    let mut interval = tokio::time::interval(options.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let state = match manager.collect_all().await {
+            Ok(state) => state,
+            Err(err) => {
+                log::warn!("Failed to collect state: {err}");
+                continue;
+            }
+        };
+
+        let snapshot = Snapshot {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            device: &device_id,
+            state,
+        };
+        let mut line = serde_json::to_vec(&snapshot)?;
+        line.push(b'\n');
+
+        if let Err(err) = writer.write(&line).await {
+            log::warn!("Failed to write snapshot: {err}");
+        }
+    }
+}
+
+enum Writer {
+    Stdout(tokio::io::Stdout),
+    File(RotatingFile),
+}
+
+impl Writer {
+    async fn write(&mut self, line: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Self::Stdout(stdout) => write(stdout, line).await,
+            Self::File(file) => file.write(line).await,
+        }
+    }
+}
+
+async fn write<W: AsyncWrite + Unpin>(writer: &mut W, line: &[u8]) -> anyhow::Result<()> {
+    writer.write_all(line).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// A file, appended to and rotated when required
+struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    current: Option<Current>,
+}
+
+struct Current {
+    file: File,
+    size: u64,
+    /// When the file was started, surviving restarts of the agent
+    started: SystemTime,
+}
+
+impl RotatingFile {
+    fn new(path: PathBuf, rotation: Rotation) -> Self {
+        Self {
+            path,
+            rotation,
+            current: None,
+        }
+    }
+
+    async fn write(&mut self, line: &[u8]) -> anyhow::Result<()> {
+        let rotate = match &self.current {
+            Some(current) => self.needs_rotation(current, line.len() as u64),
+            None => {
+                let current = self.open().await?;
+                let rotate = self.needs_rotation(&current, line.len() as u64);
+                self.current = Some(current);
+                rotate
+            }
+        };
+
+        if rotate {
+            self.current = None;
+            rotate_files(&self.path, self.rotation.keep)
+                .await
+                .with_context(|| format!("Failed to rotate: {}", self.path.display()))?;
+        }
+
+        let current = match &mut self.current {
+            Some(current) => current,
+            None => self.current.insert(self.open().await?),
+        };
+
+        let result = write(&mut current.file, line).await;
+        match result {
+            Ok(()) => current.size += line.len() as u64,
+            // re-open with the next attempt
+            Err(_) => self.current = None,
+        }
+        result
+    }
+
+    async fn open(&self) -> anyhow::Result<Current> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open: {}", self.path.display()))?;
+        let metadata = file.metadata().await?;
+        let size = metadata.len();
+        // not all platforms and file systems report the creation time
+        let started = match size {
+            0 => SystemTime::now(),
+            _ => metadata
+                .created()
+                .or_else(|_| metadata.modified())
+                .unwrap_or_else(|_| SystemTime::now()),
+        };
+
+        Ok(Current {
+            file,
+            size,
+            started,
+        })
+    }
+
+    fn needs_rotation(&self, current: &Current, len: u64) -> bool {
+        let size = self
+            .rotation
+            .max_size
+            .is_some_and(|max| current.size > 0 && current.size + len > max);
+        let age = self.rotation.max_age.is_some_and(|max| {
+            current.size > 0 && current.started.elapsed().unwrap_or_default() >= max
+        });
+        size || age
+    }
+}
+
+/// Rotate `<path>` to `<path>.1`, `<path>.1` to `<path>.2`, …, dropping files beyond `keep`
+async fn rotate_files(path: &Path, keep: usize) -> std::io::Result<()> {
+    let rotated = |n: usize| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    };
+
+    if keep == 0 {
+        return tokio::fs::remove_file(path).await;
+    }
+
+    for n in (1..keep).rev() {
+        match tokio::fs::rename(rotated(n), rotated(n + 1)).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+    tokio::fs::rename(path, rotated(1)).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rotate() {
+        let dir = std::env::temp_dir().join(format!("resymo-file-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("agent.jsonl");
+
+        let mut file = RotatingFile::new(
+            path.clone(),
+            Rotation {
+                max_size: Some(8),
+                max_age: None,
+                keep: 2,
+            },
+        );
+        for line in ["1111\n", "2222\n", "3333\n", "4444\n"] {
+            file.write(line.as_bytes()).await.unwrap();
+        }
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap_or_default();
+        assert_eq!(read("agent.jsonl"), "4444\n");
+        assert_eq!(read("agent.jsonl.1"), "3333\n");
+        assert_eq!(read("agent.jsonl.2"), "2222\n");
+        assert!(!dir.join("agent.jsonl.3").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_started() {
+        let dir = std::env::temp_dir().join(format!("resymo-file-started-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("agent.jsonl");
+
+        let mut file = RotatingFile::new(path.clone(), Default::default());
+        file.write(b"1111\n").await.unwrap();
+        drop(file);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let restarted = SystemTime::now();
+
+        // opening an existing file, e.g. after a restart, must not reset its age
+        let file = RotatingFile::new(path, Default::default());
+        let current = file.open().await.unwrap();
+        assert_eq!(current.size, 5);
+        assert!(current.started < restarted);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
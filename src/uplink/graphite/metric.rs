@@ -0,0 +1,28 @@
+//! Encoding of metrics
+
+/// Encode a metric in the Graphite plaintext protocol
+pub fn plaintext(path: &str, value: f64, timestamp: u64) -> String {
+    format!("{path} {value} {timestamp}")
+}
+
+/// Encode a metric as StatsD gauge
+pub fn statsd(path: &str, value: f64) -> Vec<String> {
+    if value < 0.0 {
+        // a signed value would modify the current value, so it needs to be reset first
+        vec![format!("{path}:0|g"), format!("{path}:{value}|g")]
+    } else {
+        vec![format!("{path}:{value}|g")]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(plaintext("a.b", 0.5, 1700000000), "a.b 0.5 1700000000");
+        assert_eq!(statsd("a.b", 42.0), vec!["a.b:42|g"]);
+        assert_eq!(statsd("a.b", -3.0), vec!["a.b:0|g", "a.b:-3|g"]);
+    }
+}
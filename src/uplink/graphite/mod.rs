@@ -0,0 +1,310 @@
+//! Graphite uplink, sending metrics using the plaintext protocol, or as StatsD gauges
+
+mod metric;
+
+use crate::manager::Manager;
+use crate::uplink::{
+    buffer::{self, Buffer, Snapshot},
+    default_device_id, metrics, udp, validate_topic,
+};
+use anyhow::{bail, Context};
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{io::AsyncWriteExt, net::TcpStream, time::MissedTickBehavior};
+
+const DEFAULT_PREFIX: &str = "resymo.{device}";
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// The device ID. Will default to the value of the `HOSTNAME` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+
+    /// Prefix of the metric paths, defaults to `resymo.{device}`.
+    ///
+    /// Placeholders: `{device}`, others are rejected. Metrics are added as `<prefix>.<collector>.<field>`, e.g.
+    /// `servers.{device}` results in `servers.my-host.disk_free.disks.root.usage`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+
+    /// Interval of collecting and sending the metrics
+    #[serde(
+        with = "crate::utils::non_zero_duration",
+        default = "default::interval"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub interval: Duration,
+
+    /// Maximum number of lines kept while the server is not available, dropping the oldest ones
+    #[serde(default = "default::buffer_size")]
+    pub buffer_size: usize,
+
+    /// Keep the state on disk instead of `bufferSize` lines in memory, requires the plaintext target
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_buffer: Option<buffer::Options>,
+
+    /// Where to send to
+    pub target: Target,
+}
+
+/// Where to send the metrics to
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Target {
+    /// The Graphite plaintext protocol, over TCP
+    Plaintext(PlaintextTarget),
+    /// StatsD gauges, over UDP
+    Statsd(StatsdTarget),
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaintextTarget {
+    /// The address of the carbon receiver, e.g. `localhost:2003`
+    pub address: String,
+
+    /// Timeout of connecting and sending
+    #[serde(with = "humantime_serde", default = "default::timeout")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub timeout: Duration,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsdTarget {
+    /// The address of the StatsD server, e.g. `localhost:8125`
+    pub address: String,
+
+    /// Maximum size of a datagram, metrics are split across multiple datagrams
+    #[serde(default = "default::max_packet_size")]
+    pub max_packet_size: usize,
+}
+
+mod default {
+    use super::*;
+
+    pub const fn interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub const fn buffer_size() -> usize {
+        10_000
+    }
+
+    pub const fn timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub const fn max_packet_size() -> usize {
+        1400
+    }
+}
+
+/// Sends lines to the configured target
+enum Sender {
+    Plaintext {
+        target: PlaintextTarget,
+        stream: Option<TcpStream>,
+    },
+    Statsd(udp::Sender),
+}
+
+impl Sender {
+    async fn send(&mut self, lines: &[String]) -> anyhow::Result<()> {
+        match self {
+            Self::Plaintext { target, stream } => {
+                let result = tokio::time::timeout(target.timeout, async {
+                    let connected = match stream {
+                        Some(stream) => stream,
+                        None => {
+                            log::debug!("Connecting to: {}", target.address);
+                            let new =
+                                TcpStream::connect(&target.address).await.with_context(|| {
+                                    format!("Failed to connect to: {}", target.address)
+                                })?;
+                            stream.insert(new)
+                        }
+                    };
+
+                    let mut data = lines.join("\n");
+                    data.push('\n');
+                    connected.write_all(data.as_bytes()).await?;
+                    connected.flush().await?;
+
+                    Ok::<_, anyhow::Error>(())
+                })
+                .await
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Timeout sending metrics")));
+
+                if result.is_err() {
+                    // reconnect with the next attempt
+                    *stream = None;
+                }
+                result
+            }
+            Self::Statsd(sender) => sender.send(lines).await,
+        }
+    }
+}
+
+/// Build the prefix of the metric paths, rejecting unknown placeholders
+fn prefix(prefix: Option<&str>, device_id: &str) -> anyhow::Result<String> {
+    let prefix = prefix.unwrap_or(DEFAULT_PREFIX);
+    validate_topic(prefix, &["device"], &[])?;
+
+    Ok(prefix.replace("{device}", device_id))
+}
+
+pub async fn run(options: Options, manager: Arc<Manager>) -> anyhow::Result<()> {
+    let device_id = options
+        .device_id
+        .unwrap_or_else(default_device_id)
+        .replace('.', "_");
+    let prefix = prefix(options.prefix.as_deref(), &device_id)?;
+
+    let mut sender = match options.target {
+        Target::Plaintext(target) => Sender::Plaintext {
+            target,
+            stream: None,
+        },
+        Target::Statsd(target) => {
+            Sender::Statsd(udp::Sender::new(target.address, target.max_packet_size))
+        }
+    };
+    let statsd = matches!(sender, Sender::Statsd(_));
+
+    let mut disk_buffer = match options.disk_buffer {
+        // StatsD has no timestamps, replaying would record all values at the time of replaying
+        Some(_) if statsd => bail!("The disk buffer requires the plaintext target"),
+        Some(options) => Some(Buffer::open(options).await?),
+        None => None,
+    };
+
+    let mut buffer = VecDeque::<String>::new();
+
+    let mut interval = tokio::time::interval(options.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        if let Some(disk_buffer) = &mut disk_buffer {
+            match manager.collect_all().await {
+                Ok(state) => {
+                    if let Err(err) = disk_buffer.push(Snapshot::now(state)).await {
+                        log::warn!("Failed to buffer state: {err}");
+                    }
+                }
+                Err(err) => {
+                    log::warn!("Failed to collect state: {err}");
+                }
+            }
+
+            replay(&mut sender, disk_buffer, &prefix, options.buffer_size).await;
+            continue;
+        }
+
+        match manager.collect_all().await {
+            Ok(state) => {
+                let timestamp = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+
+                for (name, state) in state {
+                    for (path, value) in metrics::metrics(&prefix, &name, &state) {
+                        if statsd {
+                            buffer.extend(metric::statsd(&path, value));
+                        } else {
+                            buffer.push_back(metric::plaintext(&path, value, timestamp));
+                        }
+                    }
+                }
+            }
+            Err(err) => {
+                log::warn!("Failed to collect state: {err}");
+            }
+        }
+
+        let overflow = buffer.len().saturating_sub(options.buffer_size);
+        if overflow > 0 {
+            log::warn!("Buffer full, dropping {overflow} lines");
+            buffer.drain(..overflow);
+        }
+
+        if buffer.is_empty() {
+            continue;
+        }
+
+        match sender.send(buffer.make_contiguous()).await {
+            Ok(()) => {
+                log::debug!("Sent {} lines", buffer.len());
+                buffer.clear();
+            }
+            Err(err) => {
+                log::warn!(
+                    "Failed to send metrics, keeping {} lines: {err}",
+                    buffer.len()
+                );
+            }
+        }
+    }
+}
+
+/// Send the buffered snapshots, oldest first, in batches of whole snapshots
+async fn replay(sender: &mut Sender, disk_buffer: &mut Buffer, prefix: &str, batch_size: usize) {
+    loop {
+        let mut batch = vec![];
+        let mut n = 0;
+        for Snapshot { timestamp, state } in disk_buffer.iter() {
+            let timestamp = timestamp / 1_000_000_000;
+            let lines = state
+                .iter()
+                .flat_map(|(name, state)| metrics::metrics(prefix, name, state))
+                .map(|(path, value)| metric::plaintext(&path, value, timestamp))
+                .collect::<Vec<_>>();
+            if n > 0 && batch.len() + lines.len() > batch_size {
+                break;
+            }
+            batch.extend(lines);
+            n += 1;
+        }
+        if n == 0 {
+            return;
+        }
+
+        if !batch.is_empty() {
+            if let Err(err) = sender.send(&batch).await {
+                log::warn!(
+                    "Failed to send metrics, keeping {} snapshots: {err}",
+                    disk_buffer.len()
+                );
+                return;
+            }
+            log::debug!("Sent {} lines", batch.len());
+        }
+
+        if let Err(err) = disk_buffer.pop(n).await {
+            log::warn!("Failed to update buffer: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(prefix(None, "host").unwrap(), "resymo.host");
+        assert_eq!(
+            prefix(Some("servers.{device}"), "host").unwrap(),
+            "servers.host"
+        );
+        assert!(prefix(Some("servers.{device_id}"), "host").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Device metadata
+
+use crate::{common::homeassistant::Device, utils::is_default};
+use std::path::Path;
+
+const DEFAULT_NAME: &str = "ReSyMo: {device_id}";
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceOptions {
+    /// Name of the device. `{device_id}` will be replaced with the device ID. Defaults to `ReSyMo: {device_id}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Manufacturer of the device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+
+    /// Model of the device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// Hardware version of the device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hw_version: Option<String>,
+
+    /// Area the device should be assigned to, when it is added to Home Assistant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_area: Option<String>,
+
+    /// URL for configuring the device, for example the agent's HTTP dashboard
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub configuration_url: Option<String>,
+
+    /// Additional connections of the device, as pairs of type and value. For example: `["mac", "02:42:ac:11:00:02"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub connections: Vec<(String, String)>,
+
+    /// Disable the detection of MAC addresses of the network interfaces
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub disable_mac_detection: bool,
+}
+
+impl DeviceOptions {
+    /// Create the device information for a device ID
+    pub fn build(self, device_id: &str) -> Device {
+        let mut connections = self.connections;
+        if !self.disable_mac_detection {
+            connections.extend(
+                mac_addresses(Path::new("/sys/class/net"))
+                    .into_iter()
+                    .map(|mac| ("mac".to_string(), mac)),
+            );
+        }
+        connections.sort();
+        connections.dedup();
+
+        Device {
+            identifiers: vec![device_id.to_string()],
+            connections,
+            name: Some(
+                self.name
+                    .as_deref()
+                    .unwrap_or(DEFAULT_NAME)
+                    .replace("{device_id}", device_id),
+            ),
+            manufacturer: self.manufacturer,
+            model: self.model,
+            hw_version: self.hw_version,
+            sw_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            suggested_area: self.suggested_area,
+            configuration_url: self.configuration_url,
+        }
+    }
+}
+
+/// Detect the MAC addresses of physical network interfaces.
+///
+/// Virtual interfaces (bridges, veth pairs, …) are skipped, as they may change and carry random addresses.
+fn mac_addresses(root: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return vec![];
+    };
+
+    let mut result = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.join("device").exists())
+        .filter_map(|path| std::fs::read_to_string(path.join("address")).ok())
+        .map(|mac| mac.trim().to_lowercase())
+        .filter(|mac| !mac.is_empty() && mac != "00:00:00:00:00:00")
+        .collect::<Vec<_>>();
+
+    result.sort();
+    result.dedup();
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mac_addresses() {
+        let dir = std::env::temp_dir().join(format!("resymo-device-{}", std::process::id()));
+        let root = dir.join("sys/class/net");
+
+        // (interface, address, physical)
+        for (name, address, physical) in [
+            ("eth0", "02:42:AC:11:00:02\n", true),
+            ("veth1234", "ee:c5:1a:00:00:01\n", false),
+            ("lo", "00:00:00:00:00:00\n", true),
+        ] {
+            let interface = root.join(name);
+            std::fs::create_dir_all(&interface).unwrap();
+            std::fs::write(interface.join("address"), address).unwrap();
+            if physical {
+                std::fs::create_dir(interface.join("device")).unwrap();
+            }
+        }
+
+        assert_eq!(mac_addresses(&root), ["02:42:ac:11:00:02"]);
+        assert!(mac_addresses(&dir.join("missing")).is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
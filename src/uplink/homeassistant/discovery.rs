@@ -1,14 +1,22 @@
 use homeassistant_agent::model::{Availability, AvailabilityMode, Discovery};
-use std::fmt::Display;
 
 pub trait MixinAvailability: Sized {
-    fn mixin_availability(self, base: impl Display, global: impl Into<String>) -> Self;
+    /// Add the global availability topic, resolving the relative topics of the entity
+    fn mixin_availability(
+        self,
+        resolve: impl Fn(&str) -> String,
+        global: impl Into<String>,
+    ) -> Self;
 }
 
 impl MixinAvailability for Discovery {
-    fn mixin_availability(mut self, base: impl Display, global: impl Into<String>) -> Self {
+    fn mixin_availability(
+        mut self,
+        resolve: impl Fn(&str) -> String,
+        global: impl Into<String>,
+    ) -> Self {
         for entry in &mut self.availability {
-            entry.topic = format!("{base}/{}", entry.topic);
+            entry.topic = resolve(&entry.topic);
         }
         self.availability.push(Availability::new(global));
         self.availability_mode = AvailabilityMode::All;
@@ -0,0 +1,167 @@
+//! Unique IDs and object IDs of entities
+
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter},
+};
+
+/// Scheme for building the unique IDs and object IDs of entities
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Deserialize,
+    serde::Serialize,
+    schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum IdScheme {
+    /// `{device}_{collector}_{entity}` for collectors and `{device}_{entity}` for commands.
+    ///
+    /// Entities of commands may collide with entities of collectors. Kept as the default, so that upgrading doesn't
+    /// replace the entities of existing setups.
+    #[default]
+    Legacy,
+    /// `{device}_{kind}_{name}_{entity}`, with kind being `collector` or `command`. Also used as object ID.
+    ///
+    /// Inside the parts, `-` is replaced by `--` and `_` by `-_`, so that IDs can't collide.
+    Unified,
+    /// Use the unified scheme, and remove entities which had been announced using the legacy scheme.
+    Migrate,
+}
+
+/// Kind of the source of an entity
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Collector,
+    Command,
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Collector => f.write_str("collector"),
+            Self::Command => f.write_str("command"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntityId {
+    pub unique_id: String,
+    pub object_id: Option<String>,
+}
+
+impl IdScheme {
+    /// Build the ID of an entity.
+    ///
+    /// The `name` is the name of the collector or command, the `entity` the ID of the entity inside it.
+    pub fn entity_id(&self, device_id: &str, kind: Kind, name: &str, entity: &str) -> EntityId {
+        match self {
+            Self::Legacy => EntityId {
+                unique_id: legacy(device_id, kind, name, entity),
+                object_id: None,
+            },
+            Self::Unified | Self::Migrate => {
+                let id = format!(
+                    "{}_{kind}_{}_{}",
+                    escape(device_id),
+                    escape(name),
+                    escape(entity)
+                );
+                EntityId {
+                    unique_id: id.clone(),
+                    object_id: Some(id),
+                }
+            }
+        }
+    }
+
+    /// The legacy unique ID of an entity, in case it needs to be removed.
+    pub fn migrate(&self, device_id: &str, kind: Kind, name: &str, entity: &str) -> Option<String> {
+        match self {
+            Self::Migrate => Some(legacy(device_id, kind, name, entity)),
+            Self::Legacy | Self::Unified => None,
+        }
+    }
+}
+
+/// Escape a part of a unified ID, so that a single `_` only separates parts.
+///
+/// IDs are part of the discovery topics, which only allow `[a-zA-Z0-9_-]`.
+fn escape(part: &str) -> Cow<'_, str> {
+    if !part.contains(['-', '_']) {
+        return part.into();
+    }
+
+    let mut result = String::with_capacity(part.len() * 2);
+    for c in part.chars() {
+        if c == '-' || c == '_' {
+            result.push('-');
+        }
+        result.push(c);
+    }
+    result.into()
+}
+
+fn legacy(device_id: &str, kind: Kind, name: &str, entity: &str) -> String {
+    match kind {
+        Kind::Collector => format!("{device_id}_{name}_{entity}"),
+        Kind::Command => format!("{device_id}_{entity}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_entity_id() {
+        // an untouched configuration keeps the entities of existing setups
+        assert_eq!(IdScheme::default(), IdScheme::Legacy);
+
+        // a command named "memory_free" collides with the "free" entity of the "memory" collector
+        assert_eq!(
+            IdScheme::Legacy.entity_id("host", Kind::Collector, "memory", "free"),
+            IdScheme::Legacy.entity_id("host", Kind::Command, "memory_free", "memory_free"),
+        );
+
+        let collector = IdScheme::Unified.entity_id("host", Kind::Collector, "memory", "free");
+        let command =
+            IdScheme::Unified.entity_id("host", Kind::Command, "memory_free", "memory_free");
+        assert_eq!(collector.unique_id, "host_collector_memory_free");
+        assert_eq!(
+            collector.object_id.as_deref(),
+            Some("host_collector_memory_free")
+        );
+        assert_eq!(command.unique_id, "host_command_memory-_free_memory-_free");
+
+        // underscores in the parts don't let IDs collide
+        assert_ne!(
+            IdScheme::Unified.entity_id("host", Kind::Collector, "memory", "free_x"),
+            IdScheme::Unified.entity_id("host", Kind::Collector, "memory_free", "x"),
+        );
+        assert_ne!(
+            IdScheme::Unified.entity_id("my_host", Kind::Collector, "collector_x", "y"),
+            IdScheme::Unified.entity_id("my", Kind::Collector, "host_collector_x", "y"),
+        );
+        assert_eq!(
+            IdScheme::Unified
+                .entity_id("my-host", Kind::Command, "reboot", "reboot_running")
+                .unique_id,
+            "my--host_command_reboot_reboot-_running"
+        );
+
+        assert_eq!(
+            IdScheme::Migrate.migrate("host", Kind::Command, "reboot", "reboot_running"),
+            Some("host_reboot_running".to_string())
+        );
+        assert_eq!(
+            IdScheme::Unified.migrate("host", Kind::Command, "reboot", "reboot"),
+            None
+        );
+    }
+}
@@ -1,16 +1,39 @@
+mod device;
 mod discovery;
+mod ids;
+mod publish;
+mod topics;
 
+pub use device::DeviceOptions;
+pub use ids::IdScheme;
+pub use publish::{Deadband, PublishOptions};
+pub use topics::TopicOptions;
+
+use crate::command::Command;
+use crate::common::homeassistant::{Component, Device, Entity};
 use crate::manager::Manager;
 use crate::uplink::homeassistant::discovery::MixinAvailability;
+use crate::uplink::{
+    buffer::{self, Buffer, Snapshot},
+    default_device_id,
+};
+use crate::utils::is_default;
 use actix_web::web::Bytes;
-use gethostname::gethostname;
+use anyhow::bail;
 use homeassistant_agent::{
     connector::{AvailabilityOptions, Client, Connector, ConnectorHandler, ConnectorOptions},
-    model::{Component, Device, DeviceId, Discovery},
+    model::Discovery,
 };
+use ids::Kind;
+use publish::{Policies, Published};
 use rumqttc::QoS;
-use std::{borrow::Cow, sync::Arc, time::Duration};
-use tokio::{sync::oneshot, time::MissedTickBehavior};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+};
+use tokio::{sync::oneshot, time::Instant};
+use topics::Topics;
 
 pub const PAYLOAD_RUNNING: &str = "ON";
 pub const PAYLOAD_STOPPED: &str = "OFF";
@@ -24,6 +47,38 @@ pub struct UplinkOptions {
     /// Base topic
     #[serde(default = "default_base")]
     pub base: String,
+
+    /// Layout of the topics
+    #[serde(default)]
+    pub topics: TopicOptions,
+
+    /// Prefix of the Home Assistant discovery topics, defaults to `homeassistant`.
+    ///
+    /// Same as `connector.topicBase`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovery_prefix: Option<String>,
+
+    /// Device information
+    #[serde(default)]
+    pub device: DeviceOptions,
+
+    /// Scheme for building unique IDs and object IDs of entities
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub id_scheme: IdScheme,
+
+    /// Publishing of the state
+    #[serde(flatten)]
+    pub publish: PublishOptions,
+
+    /// Per-collector overrides of the publishing options
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub collectors: BTreeMap<String, PublishOptions>,
+
+    /// Keep the state on disk while the broker is not reachable, replaying it afterwards.
+    ///
+    /// Home Assistant records the replayed states at the time it receives them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_buffer: Option<buffer::Options>,
 }
 
 fn default_base() -> String {
@@ -43,24 +98,39 @@ pub struct Options {
 pub enum Error {
     #[error(transparent)]
     Client(#[from] homeassistant_agent::connector::ClientError),
+    #[error(transparent)]
+    Mqtt(#[from] rumqttc::ClientError),
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
 }
 
 pub struct ResymoUplink {
     client: Client,
     manager: Arc<Manager>,
     options: RunnerOptions,
+    published: Published,
+    /// Discovery config topics, announced by the last call to `announce`
+    announced: BTreeSet<String>,
     _tx: oneshot::Sender<()>,
 }
 
 impl ResymoUplink {
-    fn new(client: Client, manager: Arc<Manager>, options: RunnerOptions) -> Self {
+    fn new(
+        client: Client,
+        manager: Arc<Manager>,
+        options: RunnerOptions,
+        disk_buffer: Option<Buffer>,
+    ) -> Self {
         let (tx, rx) = oneshot::channel::<()>();
+        let published = Published::default();
 
         let runner = Runner {
             shutdown: rx,
             client: client.clone(),
             manager: manager.clone(),
             options: options.clone(),
+            published: published.clone(),
+            disk_buffer,
         };
 
         tokio::spawn({
@@ -73,6 +143,8 @@ impl ResymoUplink {
             client,
             manager,
             options,
+            published,
+            announced: Default::default(),
             _tx: tx,
         }
     }
@@ -99,14 +171,23 @@ impl ConnectorHandler for ResymoUplink {
     }
 
     async fn message(&mut self, topic: String, payload: Bytes) -> Result<(), Self::Error> {
-        match topic.split('/').collect::<Vec<_>>().as_slice() {
-            [base, device_id, name, "command"]
-                if format!("{base}/{device_id}") == self.options.base =>
-            {
+        if topic == self.registry_topic() {
+            return self.remove_stale(&payload).await;
+        }
+
+        let name = self
+            .manager
+            .commands
+            .keys()
+            .find(|name| self.command_topic(name) == topic)
+            .cloned();
+
+        match name {
+            Some(name) => {
                 let payload = String::from_utf8_lossy(&payload);
-                self.handle_command(name, payload).await;
+                self.handle_command(&name, payload).await;
             }
-            _ => {
+            None => {
                 log::warn!("received message for unknown topic: {topic}");
             }
         }
@@ -117,14 +198,29 @@ impl ConnectorHandler for ResymoUplink {
 
 impl ResymoUplink {
     fn state_topic(&self, name: &str) -> String {
-        format!("{base}/{name}/state", base = self.options.base)
+        self.options.topics.entity(name, "state")
     }
 
     fn command_topic(&self, name: &str) -> String {
-        format!("{base}/{name}/command", base = self.options.base)
+        self.options.topics.entity(name, "command")
+    }
+
+    /// Topic of the current value of commands taking input
+    fn value_topic(&self, name: &str) -> String {
+        self.options.topics.entity(name, "value")
+    }
+
+    /// Retained topic, holding the discovery config topics announced by this agent
+    fn registry_topic(&self) -> String {
+        self.options.topics.device("discovery")
     }
 
     async fn subscribe(&self) -> Result<(), Error> {
+        // subscribe before announcing, so that we receive the entities of the previous run first
+        self.client
+            .subscribe(self.registry_topic(), QoS::AtLeastOnce)
+            .await?;
+
         for (name, command) in &self.manager.commands {
             if command.describe_ha().is_none() {
                 continue;
@@ -140,40 +236,63 @@ impl ResymoUplink {
         Ok(())
     }
 
-    async fn announce(&self) -> Result<(), Error> {
-        let device = Device {
-            identifiers: vec![self.options.device_id.clone()],
-            name: Some(format!("ReSyMo: {}", self.options.device_id)),
-            base_topic: None,
-            sw_version: Some(env!("CARGO_PKG_VERSION").to_string()),
-            support_url: None,
-        };
+    async fn announce(&mut self) -> Result<(), Error> {
+        // (re-)publish the full state with the next update
+        self.published.reset();
+
+        let mut announced = BTreeSet::new();
+
+        let device = &self.options.device;
 
         for (name, collector) in &self.manager.collectors {
             let state_topic = self.state_topic(name);
             let entities = collector.describe_ha();
 
             for entity in entities {
-                let Some(unique_id) = entity
-                    .unique_id
-                    .as_ref()
-                    .map(|id| format!("{}_{name}_{id}", self.options.device_id,))
-                else {
+                let Some(local_id) = entity.discovery.unique_id.clone() else {
                     continue;
                 };
 
-                let entity = Discovery {
-                    state_topic: Some(state_topic.clone()),
-                    device: Some(device.clone()),
-                    unique_id: Some(unique_id.clone()),
-                    ..(entity.clone())
+                let id = self.entity_id(Kind::Collector, name, &local_id);
+
+                // entities may trigger a command, like installing updates
+                let command_topic = entity
+                    .command
+                    .as_deref()
+                    .filter(|command| self.manager.commands.contains_key(*command))
+                    .map(|command| self.command_topic(command));
+
+                let component = entity.component;
+                let mut entity = Entity {
+                    discovery: Discovery {
+                        state_topic: Some(state_topic.clone()),
+                        command_topic: command_topic.or(entity.discovery.command_topic),
+                        unique_id: Some(id.unique_id.clone()),
+                        ..entity.discovery
+                    }
+                    .mixin_availability(
+                        |kind| self.options.topics.entity(name, kind),
+                        &self.options.availability_topic,
+                    ),
+                    object_id: id.object_id,
+                    ..entity
                 };
+                // attributes are extracted from the state
+                if entity.extra.contains_key("json_attributes_template") {
+                    entity
+                        .extra
+                        .insert("json_attributes_topic".into(), state_topic.clone().into());
+                }
 
-                let base = format!("{base}/{name}", base = self.options.base);
-                let entity = entity.mixin_availability(&base, &self.options.availability_topic);
-
-                let id = DeviceId::new(unique_id.clone(), Component::Sensor);
-                self.client.announce(&id, &entity).await?;
+                self.remove_legacy(Kind::Collector, name, &local_id, component)
+                    .await?;
+                self.announce_entity(
+                    &mut announced,
+                    component,
+                    &id.unique_id,
+                    &entity.device(device),
+                )
+                .await?;
             }
         }
 
@@ -184,68 +303,233 @@ impl ResymoUplink {
                 let command_topic = self.command_topic(name);
                 let state_topic = self.state_topic(name);
 
-                let Some(unique_id) = entity
-                    .unique_id
-                    .as_ref()
-                    .map(|id| format!("{}_{id}", self.options.device_id))
-                else {
+                let Some(local_id) = entity.discovery.unique_id.clone() else {
                     continue;
                 };
 
-                let entity = Discovery {
+                let id = self.entity_id(Kind::Command, name, &local_id);
+
+                // entities taking input, or reporting an external state, have a value
+                let component = entity.component;
+                let reports_state =
+                    component == Component::Button && command.value_period().is_some();
+                let value_topic = (component != Component::Button || reports_state)
+                    .then(|| self.value_topic(name));
+
+                let label = entity.discovery.name.clone();
+                let discovery = Discovery {
                     command_topic: Some(command_topic.clone()),
-                    device: Some(device.clone()),
-                    unique_id: Some(unique_id.clone()),
-                    ..(entity.clone())
+                    state_topic: value_topic.clone().filter(|_| !reports_state),
+                    unique_id: Some(id.unique_id.clone()),
+                    ..entity.discovery.clone()
                 };
 
-                let entity = entity.mixin_availability(
-                    &format!("{base}/{name}", base = self.options.base),
+                let discovery = discovery.mixin_availability(
+                    |kind| self.options.topics.entity(name, kind),
                     &self.options.availability_topic,
                 );
 
-                let id = DeviceId::new(unique_id.clone(), Component::Button);
-                self.client.announce(&id, &entity).await?;
+                self.remove_legacy(Kind::Command, name, &local_id, component)
+                    .await?;
+                self.announce_entity(
+                    &mut announced,
+                    component,
+                    &id.unique_id,
+                    &Entity {
+                        discovery: discovery.clone(),
+                        object_id: id.object_id,
+                        ..entity.clone()
+                    }
+                    .device(device),
+                )
+                .await?;
 
                 // state entity
 
-                let unique_id = format!("{unique_id}_running");
+                let running_id = format!("{local_id}_running");
+                let id = self.entity_id(Kind::Command, name, &running_id);
 
                 let entity = Discovery {
                     state_topic: Some(state_topic.clone()),
-                    device: Some(device.clone()),
-                    unique_id: Some(unique_id.clone()),
+                    unique_id: Some(id.unique_id.clone()),
                     device_class: None,
                     value_template: None,
                     command_topic: None,
+                    unit_of_measurement: None,
                     availability: vec![],
-                    ..(entity.clone())
+                    ..discovery
                 };
 
-                let entity =
-                    entity.mixin_availability(&self.options.base, &self.options.availability_topic);
+                let entity = entity.mixin_availability(
+                    |kind| self.options.topics.entity(name, kind),
+                    &self.options.availability_topic,
+                );
 
-                let id = DeviceId::new(unique_id, Component::BinarySensor);
-                self.client.announce(&id, &entity).await?;
+                self.remove_legacy(Kind::Command, name, &running_id, Component::BinarySensor)
+                    .await?;
+                self.announce_entity(
+                    &mut announced,
+                    Component::BinarySensor,
+                    &id.unique_id,
+                    &Entity {
+                        object_id: id.object_id,
+                        ..Entity::from(entity)
+                    }
+                    .device(device),
+                )
+                .await?;
+
+                // external state entity, buttons don't have a state of their own
 
-                // update initial state
+                if reports_state {
+                    let state_id = format!("{local_id}_state");
+                    let id = self.entity_id(Kind::Command, name, &state_id);
+
+                    let entity = Discovery {
+                        name: label.map(|label| format!("{label} state")),
+                        state_topic: value_topic.clone(),
+                        unique_id: Some(id.unique_id.clone()),
+                        ..Default::default()
+                    }
+                    .mixin_availability(
+                        |kind| self.options.topics.entity(name, kind),
+                        &self.options.availability_topic,
+                    );
+
+                    self.announce_entity(
+                        &mut announced,
+                        Component::BinarySensor,
+                        &id.unique_id,
+                        &Entity {
+                            object_id: id.object_id,
+                            ..Entity::from(entity)
+                        }
+                        .device(device),
+                    )
+                    .await?;
+                }
 
-                // FIXME: we might need to check the actual state
+                // update initial state, a command may still be running from before reconnecting
 
+                let running = if self.manager.is_running(name) {
+                    PAYLOAD_RUNNING
+                } else {
+                    PAYLOAD_STOPPED
+                };
                 self.client
-                    .update_state(self.state_topic(name), PAYLOAD_STOPPED)
+                    .update_state(self.state_topic(name), running)
                     .await?;
+
+                if let (Some(value_topic), Some(value)) = (value_topic, command.value().await) {
+                    self.client.update_state(value_topic, value).await?;
+                }
             }
         }
 
+        self.client
+            .mqtt
+            .publish(
+                self.registry_topic(),
+                QoS::AtLeastOnce,
+                true,
+                serde_json::to_vec(&announced)?,
+            )
+            .await?;
+        self.announced = announced;
+
+        Ok(())
+    }
+
+    async fn announce_entity(
+        &self,
+        announced: &mut BTreeSet<String>,
+        component: Component,
+        id: &str,
+        entity: &Entity,
+    ) -> Result<(), Error> {
+        let topic = self.config_topic(component, id);
+        log::info!("Announce {id} on {topic}");
+
+        self.client
+            .mqtt
+            .publish(&topic, QoS::AtLeastOnce, false, serde_json::to_vec(entity)?)
+            .await?;
+        announced.insert(topic);
+
+        Ok(())
+    }
+
+    fn config_topic(&self, component: Component, id: &str) -> String {
+        format!(
+            "{prefix}/{component}/{id}/config",
+            prefix = self.options.discovery_prefix
+        )
+    }
+
+    fn entity_id(&self, kind: Kind, name: &str, local_id: &str) -> ids::EntityId {
+        self.options
+            .id_scheme
+            .entity_id(&self.options.device_id, kind, name, local_id)
+    }
+
+    /// When migrating, remove an entity which was announced using the legacy ID.
+    async fn remove_legacy(
+        &self,
+        kind: Kind,
+        name: &str,
+        local_id: &str,
+        component: Component,
+    ) -> Result<(), Error> {
+        let Some(legacy) =
+            self.options
+                .id_scheme
+                .migrate(&self.options.device_id, kind, name, local_id)
+        else {
+            return Ok(());
+        };
+
+        let topic = self.config_topic(component, &legacy);
+        log::info!("Removing legacy entity: {topic}");
+
+        self.client
+            .mqtt
+            .publish(topic, QoS::AtLeastOnce, true, vec![])
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete entities of a previous run, which are no longer announced.
+    async fn remove_stale(&self, payload: &[u8]) -> Result<(), Error> {
+        if payload.is_empty() {
+            return Ok(());
+        }
+
+        let previous = match serde_json::from_slice::<BTreeSet<String>>(payload) {
+            Ok(previous) => previous,
+            Err(err) => {
+                log::warn!("Ignoring invalid list of announced entities: {err}");
+                return Ok(());
+            }
+        };
+
+        for topic in previous.difference(&self.announced) {
+            log::info!("Removing stale entity: {topic}");
+            // an empty, retained payload deletes the entity, as well as a retained config
+            self.client
+                .mqtt
+                .publish(topic, QoS::AtLeastOnce, true, vec![])
+                .await?;
+        }
+
         Ok(())
     }
 
     async fn handle_command(&mut self, name: &str, payload: Cow<'_, str>) {
-        let Some(command) = self.manager.commands.get(name) else {
+        if !self.manager.commands.contains_key(name) {
             log::warn!("Received trigger for unknown command: {name}");
             return;
-        };
+        }
 
         let state_topic = self.state_topic(name);
         let _ = self
@@ -253,13 +537,18 @@ impl ResymoUplink {
             .update_state(state_topic.clone(), PAYLOAD_RUNNING)
             .await;
 
+        let value_topic = self.value_topic(name);
         let client = self.client.clone();
 
-        command
-            .start(
+        self.manager
+            .start_command(
+                name,
                 payload,
-                Box::new(move |result| {
+                Box::new(move |result, value| {
                     Box::pin(async move {
+                        if let Some(value) = value {
+                            let _ = client.update_state(value_topic, value).await;
+                        }
                         let _ = client.update_state(state_topic, PAYLOAD_STOPPED).await;
 
                         if result.is_ok() {
@@ -277,8 +566,12 @@ impl ResymoUplink {
 #[derive(Clone, Debug)]
 struct RunnerOptions {
     device_id: String,
-    base: String,
+    topics: Topics,
     availability_topic: String,
+    discovery_prefix: String,
+    device: Device,
+    id_scheme: IdScheme,
+    policies: Arc<Policies>,
 }
 
 struct Runner {
@@ -286,19 +579,59 @@ struct Runner {
     pub client: Client,
     pub manager: Arc<Manager>,
     pub options: RunnerOptions,
+    pub published: Published,
+    /// States which could not be published yet, oldest first
+    pub disk_buffer: Option<Buffer>,
 }
 
 impl Runner {
     async fn run(mut self) {
-        let mut interval = tokio::time::interval(Duration::from_secs(10));
-        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let now = Instant::now();
+        let manager = self.manager.clone();
+        let policies = self.options.policies.clone();
+        let mut next = manager
+            .collectors
+            .keys()
+            .map(|name| (name.as_str(), now))
+            .collect::<HashMap<_, _>>();
+        // commands reporting an external state
+        let mut next_value = manager
+            .commands
+            .iter()
+            .filter_map(|(name, command)| command.value_period().map(|_| (name.as_str(), now)))
+            .collect::<HashMap<_, _>>();
 
         loop {
+            let due = next.values().chain(next_value.values()).min().copied();
+
             tokio::select! {
-                _ = interval.tick() => {
-                    log::info!("Update state");
-                    if let Err(err) = self.collect().await {
-                        log::warn!("Failed to collect state: {err}");
+                _ = async {
+                    match due {
+                        Some(due) => tokio::time::sleep_until(due).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    log::debug!("Update state");
+                    self.replay().await;
+
+                    let now = Instant::now();
+                    for (name, next) in next.iter_mut().filter(|(_, next)| **next <= now) {
+                        let policy = policies.get(name);
+                        *next = now + policy.interval;
+
+                        if let Err(err) = self.update(name, policy).await {
+                            log::warn!("Failed to collect state of '{name}': {err}");
+                        }
+                    }
+                    for (name, next) in next_value.iter_mut().filter(|(_, next)| **next <= now) {
+                        let Some(command) = self.manager.commands.get(*name) else {
+                            continue;
+                        };
+                        *next = now + command.value_period().unwrap_or_default();
+
+                        if let Err(err) = self.update_value(name, command.as_ref()).await {
+                            log::warn!("Failed to query state of '{name}': {err}");
+                        }
                     }
                 }
                 _ = &mut self.shutdown => {
@@ -309,61 +642,126 @@ impl Runner {
         }
     }
 
-    async fn collect(&self) -> anyhow::Result<()> {
-        for (collector, state) in self.manager.collect_all().await? {
-            let topic = format!("{base}/{collector}/state", base = self.options.base);
+    async fn update_value(&self, name: &str, command: &dyn Command) -> anyhow::Result<()> {
+        let Some(value) = command.value().await else {
+            return Ok(());
+        };
 
-            self.client
-                .update_state(topic, serde_json::to_vec(&state)?)
-                .await?;
-        }
+        let topic = self.options.topics.entity(name, "value");
+        self.client.update_state(topic, value).await?;
 
         Ok(())
     }
+
+    async fn update(&mut self, name: &str, policy: &publish::Policy) -> anyhow::Result<()> {
+        let Some(state) = self.manager.collect_one(name).await? else {
+            return Ok(());
+        };
+
+        if !self.published.check(name, &state, policy) {
+            log::debug!("Skipping unchanged state: {name}");
+            return Ok(());
+        }
+
+        let topic = self.options.topics.entity(name, "state");
+        let payload = serde_json::to_vec(&state)?;
+
+        let Some(disk_buffer) = &mut self.disk_buffer else {
+            self.client.update_state(topic, payload).await?;
+            return Ok(());
+        };
+
+        // keep the order, states still waiting in the buffer go first
+        if disk_buffer.is_empty() && self.client.update_state(topic, payload).await.is_ok() {
+            return Ok(());
+        }
+
+        disk_buffer
+            .push(Snapshot::now(BTreeMap::from([(name.to_string(), state)])))
+            .await
+    }
+
+    /// Publish the buffered states, oldest first, as long as the client takes them
+    async fn replay(&mut self) {
+        let Some(disk_buffer) = &mut self.disk_buffer else {
+            return;
+        };
+
+        let mut sent = 0;
+        'replay: for snapshot in disk_buffer.iter() {
+            for (name, state) in &snapshot.state {
+                // the collector may be gone since the previous run
+                if !self.manager.collectors.contains_key(name) {
+                    continue;
+                }
+                let topic = self.options.topics.entity(name, "state");
+                let payload = match serde_json::to_vec(state) {
+                    Ok(payload) => payload,
+                    Err(err) => {
+                        log::warn!("Dropping buffered state of '{name}': {err}");
+                        continue;
+                    }
+                };
+                if self.client.update_state(topic, payload).await.is_err() {
+                    break 'replay;
+                }
+            }
+            sent += 1;
+        }
+
+        if sent > 0 {
+            log::info!(
+                "Replayed {sent} buffered states, {} left",
+                disk_buffer.len() - sent
+            );
+            if let Err(err) = disk_buffer.pop(sent).await {
+                log::warn!("Failed to update buffer: {err}");
+            }
+        }
+    }
 }
 
 pub async fn run(options: Options, manager: Arc<Manager>) -> anyhow::Result<()> {
     let Options { options, connector } = options;
 
     let device_id = options.device_id.unwrap_or_else(default_device_id);
+    let options_disk_buffer = options.disk_buffer;
 
-    let availability_topic = format!("{base}/{device_id}/availability", base = options.base);
+    let topics = Topics::new(&options.base, &device_id, options.topics)?;
+    let availability_topic = topics.device("availability");
     let availability = AvailabilityOptions::new(availability_topic.clone());
 
-    let base = format!("{base}/{device_id}", base = options.base);
+    let mut connector = connector;
+    let discovery_prefix = match (options.discovery_prefix, &connector.topic_base) {
+        (Some(prefix), Some(topic_base)) if &prefix != topic_base => {
+            bail!("Conflicting discovery prefixes: '{prefix}' and '{topic_base}' (connector)")
+        }
+        (Some(prefix), _) => prefix,
+        (None, Some(topic_base)) => topic_base.clone(),
+        (None, None) => "homeassistant".to_string(),
+    };
+    connector.topic_base = Some(discovery_prefix.clone());
+
     let options = RunnerOptions {
+        device: options.device.build(&device_id),
+        id_scheme: options.id_scheme,
         device_id,
-        base,
+        topics,
         availability_topic,
+        discovery_prefix,
+        policies: Arc::new(Policies::new(&options.publish, &options.collectors)),
+    };
+
+    let disk_buffer = match options_disk_buffer {
+        Some(options) => Some(Buffer::open(options).await?),
+        None => None,
     };
 
     let connector = Connector::new(connector, |client| {
-        ResymoUplink::new(client, manager, options)
+        ResymoUplink::new(client, manager, options, disk_buffer)
     })
     .availability(availability);
     connector.run().await?;
 
     Ok(())
 }
-
-// generate a default device id
-fn default_device_id() -> String {
-    scrub_device_id(&gethostname().to_string_lossy())
-}
-
-fn scrub_device_id(device_id: &str) -> String {
-    device_id.replace(
-        |c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'),
-        "_",
-    )
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    #[test]
-    fn test_scrub_device_id() {
-        assert_eq!(scrub_device_id("foo.bar.baz"), "foo_bar_baz")
-    }
-}
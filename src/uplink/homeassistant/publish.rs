@@ -0,0 +1,287 @@
+//! Publishing policy of collected state
+
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_MAX_SILENCE: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishOptions {
+    /// Interval of collecting and publishing the state, defaults to 10 seconds.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::utils::non_zero_duration"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub interval: Option<Duration>,
+
+    /// Only publish the state when it changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_change: Option<bool>,
+
+    /// Publish the state after this time, even if it didn't change. Defaults to 5 minutes.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::utils::non_zero_duration"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub max_silence: Option<Duration>,
+
+    /// Minimum change of a numeric field, before the state is considered changed.
+    ///
+    /// Fields are selected by their name (e.g. `usage`) or their path, separated by dots
+    /// (e.g. `disks./dev/sda.usage`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub deadband: BTreeMap<String, Deadband>,
+}
+
+#[derive(
+    Copy, Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema,
+)]
+#[serde(untagged)]
+pub enum Deadband {
+    /// Absolute difference to the last published value
+    Absolute(f64),
+    /// Difference relative to the last published value, as a fraction (e.g. `0.05` for 5%)
+    Relative { relative: f64 },
+}
+
+impl Deadband {
+    fn exceeded(&self, old: f64, new: f64) -> bool {
+        let diff = (new - old).abs();
+        diff > 0.0
+            && match self {
+                Self::Absolute(deadband) => diff >= *deadband,
+                Self::Relative { relative } => diff >= old.abs() * relative,
+            }
+    }
+}
+
+/// Effective publishing policy of a collector
+#[derive(Clone, Debug)]
+pub struct Policy {
+    pub interval: Duration,
+    pub on_change: bool,
+    pub max_silence: Duration,
+    pub deadband: BTreeMap<String, Deadband>,
+}
+
+impl Policy {
+    fn new(options: &PublishOptions, overrides: Option<&PublishOptions>) -> Self {
+        let mut deadband = options.deadband.clone();
+        if let Some(overrides) = overrides {
+            deadband.extend(overrides.deadband.clone());
+        }
+
+        Self {
+            interval: overrides
+                .and_then(|o| o.interval)
+                .or(options.interval)
+                .unwrap_or(DEFAULT_INTERVAL),
+            on_change: overrides
+                .and_then(|o| o.on_change)
+                .or(options.on_change)
+                .unwrap_or_default(),
+            max_silence: overrides
+                .and_then(|o| o.max_silence)
+                .or(options.max_silence)
+                .unwrap_or(DEFAULT_MAX_SILENCE),
+            deadband,
+        }
+    }
+
+    fn deadband(&self, path: &[&str]) -> Option<&Deadband> {
+        self.deadband
+            .get(&path.join("."))
+            .or_else(|| path.last().and_then(|name| self.deadband.get(*name)))
+    }
+
+    /// Check if the new value differs from the old one, taking the deadband into account.
+    fn changed(&self, old: &Value, new: &Value) -> bool {
+        self.changed_at(&mut vec![], old, new)
+    }
+
+    fn changed_at<'a>(&self, path: &mut Vec<&'a str>, old: &'a Value, new: &'a Value) -> bool {
+        match (old, new) {
+            (Value::Object(old), Value::Object(new)) => {
+                old.len() != new.len()
+                    || new.iter().any(|(key, new)| match old.get(key) {
+                        Some(old) => {
+                            path.push(key);
+                            let changed = self.changed_at(path, old, new);
+                            path.pop();
+                            changed
+                        }
+                        None => true,
+                    })
+            }
+            (Value::Array(old), Value::Array(new)) => {
+                old.len() != new.len()
+                    || old
+                        .iter()
+                        .zip(new)
+                        .any(|(old, new)| self.changed_at(path, old, new))
+            }
+            (Value::Number(old_number), Value::Number(new_number)) => {
+                match (
+                    self.deadband(path),
+                    old_number.as_f64(),
+                    new_number.as_f64(),
+                ) {
+                    (Some(deadband), Some(old), Some(new)) => deadband.exceeded(old, new),
+                    _ => old != new,
+                }
+            }
+            (old, new) => old != new,
+        }
+    }
+}
+
+/// Publishing policies for all collectors
+#[derive(Clone, Debug)]
+pub struct Policies {
+    default: Policy,
+    collectors: HashMap<String, Policy>,
+}
+
+impl Policies {
+    pub fn new(options: &PublishOptions, overrides: &BTreeMap<String, PublishOptions>) -> Self {
+        Self {
+            default: Policy::new(options, None),
+            collectors: overrides
+                .iter()
+                .map(|(name, overrides)| (name.clone(), Policy::new(options, Some(overrides))))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, collector: &str) -> &Policy {
+        self.collectors.get(collector).unwrap_or(&self.default)
+    }
+}
+
+#[derive(Debug)]
+struct Last {
+    value: Value,
+    timestamp: Instant,
+}
+
+/// Last published state of the collectors
+#[derive(Clone, Debug, Default)]
+pub struct Published(Arc<Mutex<HashMap<String, Last>>>);
+
+impl Published {
+    /// Forget all published values, so that the next state will be published in any case.
+    pub fn reset(&self) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Check if the value of a collector must be published, recording it if it must.
+    pub fn check(&self, collector: &str, value: &Value, policy: &Policy) -> bool {
+        let mut published = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+
+        let publish = match published.get(collector) {
+            Some(last) if policy.on_change => {
+                now.duration_since(last.timestamp) >= policy.max_silence
+                    || policy.changed(&last.value, value)
+            }
+            _ => true,
+        };
+
+        if publish {
+            published.insert(
+                collector.to_string(),
+                Last {
+                    value: value.clone(),
+                    timestamp: now,
+                },
+            );
+        }
+
+        publish
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_changed() {
+        let policy = Policy::new(
+            &serde_json::from_value(json!({
+                "onChange": true,
+                "deadband": {
+                    "usage": 0.01,
+                    "used": { "relative": 0.1 },
+                }
+            }))
+            .unwrap(),
+            None,
+        );
+
+        let old = json!({"disks": {"/dev/sda": {"usage": 0.5, "used": 1000, "total": 2000}}});
+
+        let changed = |new: Value| policy.changed(&old, &new);
+
+        assert!(!changed(old.clone()));
+        assert!(!changed(
+            json!({"disks": {"/dev/sda": {"usage": 0.505, "used": 1099, "total": 2000}}})
+        ));
+        assert!(changed(
+            json!({"disks": {"/dev/sda": {"usage": 0.51, "used": 1000, "total": 2000}}})
+        ));
+        assert!(changed(
+            json!({"disks": {"/dev/sda": {"usage": 0.5, "used": 1100, "total": 2000}}})
+        ));
+        assert!(changed(
+            json!({"disks": {"/dev/sda": {"usage": 0.5, "used": 1000, "total": 2001}}})
+        ));
+        assert!(changed(json!({"disks": {}})));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let options: PublishOptions = serde_json::from_value(json!({
+            "interval": "30s",
+            "deadband": { "usage": 0.01 },
+        }))
+        .unwrap();
+        let overrides = serde_json::from_value(json!({
+            "disk_free": {
+                "onChange": true,
+                "deadband": { "usage": 0.05 },
+            }
+        }))
+        .unwrap();
+
+        let policies = Policies::new(&options, &overrides);
+
+        let default = policies.get("memory");
+        assert_eq!(default.interval, Duration::from_secs(30));
+        assert!(!default.on_change);
+
+        let disk_free = policies.get("disk_free");
+        assert_eq!(disk_free.interval, Duration::from_secs(30));
+        assert!(disk_free.on_change);
+        assert_eq!(disk_free.deadband["usage"], Deadband::Absolute(0.05));
+
+        for field in ["interval", "maxSilence"] {
+            assert!(serde_json::from_value::<PublishOptions>(json!({ field: "0s" })).is_err());
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Topic layout
+
+use crate::uplink::validate_topic;
+
+const DEFAULT_ENTITY: &str = "{base}/{device}/{collector}/{kind}";
+const DEFAULT_DEVICE: &str = "{base}/{device}/{kind}";
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TopicOptions {
+    /// Template of topics of collectors and commands, defaults to `{base}/{device}/{collector}/{kind}`.
+    ///
+    /// Placeholders: `{base}`, `{device}`, `{collector}` (name of the collector or command),
+    /// `{kind}` (`state`, `command`, or `value`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<String>,
+
+    /// Template of topics of the device, defaults to `{base}/{device}/{kind}`.
+    ///
+    /// Placeholders: `{base}`, `{device}`, `{kind}` (`availability` or `discovery`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+}
+
+/// Renders the topics of a device
+#[derive(Clone, Debug)]
+pub struct Topics {
+    base: String,
+    device_id: String,
+    entity: String,
+    device: String,
+}
+
+impl Topics {
+    pub fn new(base: &str, device_id: &str, options: TopicOptions) -> anyhow::Result<Self> {
+        let entity = options.entity.unwrap_or_else(|| DEFAULT_ENTITY.to_string());
+        let device = options.device.unwrap_or_else(|| DEFAULT_DEVICE.to_string());
+
+        validate_topic(
+            &entity,
+            &["base", "device", "collector", "kind"],
+            &["collector", "kind"],
+        )?;
+        validate_topic(&device, &["base", "device", "kind"], &["kind"])?;
+
+        Ok(Self {
+            base: base.to_string(),
+            device_id: device_id.to_string(),
+            entity,
+            device,
+        })
+    }
+
+    /// Topic of a collector or command
+    pub fn entity(&self, collector: &str, kind: &str) -> String {
+        self.entity
+            .replace("{base}", &self.base)
+            .replace("{device}", &self.device_id)
+            .replace("{collector}", collector)
+            .replace("{kind}", kind)
+    }
+
+    /// Topic of the device
+    pub fn device(&self, kind: &str) -> String {
+        self.device
+            .replace("{base}", &self.base)
+            .replace("{device}", &self.device_id)
+            .replace("{kind}", kind)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_topics() {
+        let topics = Topics::new("resymo", "host", Default::default()).unwrap();
+        assert_eq!(topics.entity("memory", "state"), "resymo/host/memory/state");
+        assert_eq!(topics.device("availability"), "resymo/host/availability");
+
+        let topics = Topics::new(
+            "resymo",
+            "host",
+            TopicOptions {
+                entity: Some("acl/{device}/{kind}/{collector}".into()),
+                device: Some("acl/{device}/{kind}".into()),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            topics.entity("reboot", "command"),
+            "acl/host/command/reboot"
+        );
+
+        assert!(Topics::new(
+            "resymo",
+            "host",
+            TopicOptions {
+                entity: Some("{base}/{device}/{collector}".into()),
+                device: None,
+            },
+        )
+        .is_err());
+        assert!(Topics::new(
+            "resymo",
+            "host",
+            TopicOptions {
+                entity: Some("{base}/{host}/{collector}/{kind}".into()),
+                device: None,
+            },
+        )
+        .is_err());
+    }
+}
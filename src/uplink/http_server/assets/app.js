@@ -0,0 +1,249 @@
+"use strict";
+
+const REFRESH_INTERVAL = 10000;
+const TOKEN_KEY = "resymo.token";
+
+// fields which contain a number of bytes
+const BYTE_FIELDS = new Set(["free", "total", "used", "available"]);
+// fields which contain a fraction (0…1)
+const FRACTION_FIELDS = new Set(["usage", "percentage"]);
+
+const SVG = "http://www.w3.org/2000/svg";
+
+let token = localStorage.getItem(TOKEN_KEY);
+
+class UnauthorizedError extends Error {
+}
+
+async function api(path, options = {}) {
+    const headers = {"Content-Type": "application/json"};
+    if (token) {
+        headers["Authorization"] = `Bearer ${token}`;
+    }
+    const response = await fetch(`api/v1/${path}`, {...options, headers});
+    if (response.status === 401) {
+        throw new UnauthorizedError("Unauthorized");
+    }
+    if (!response.ok) {
+        throw new Error(`${response.status} ${response.statusText}`);
+    }
+    if (response.status === 202 || response.status === 204) {
+        return null;
+    }
+    return response.json();
+}
+
+function element(name, attributes = {}, ...children) {
+    const result = document.createElement(name);
+    for (const [key, value] of Object.entries(attributes)) {
+        result.setAttribute(key, value);
+    }
+    result.append(...children);
+    return result;
+}
+
+function svg(name, attributes = {}, ...children) {
+    const result = document.createElementNS(SVG, name);
+    for (const [key, value] of Object.entries(attributes)) {
+        result.setAttribute(key, value);
+    }
+    result.append(...children);
+    return result;
+}
+
+function formatBytes(value) {
+    const units = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let unit = 0;
+    while (value >= 1024 && unit < units.length - 1) {
+        value /= 1024;
+        unit++;
+    }
+    return `${value.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`;
+}
+
+function formatValue(key, value) {
+    if (FRACTION_FIELDS.has(key)) {
+        return `${(value * 100).toFixed(1)} %`;
+    }
+    if (BYTE_FIELDS.has(key)) {
+        return formatBytes(value);
+    }
+    return Number.isInteger(value) ? value.toString() : value.toFixed(2);
+}
+
+function gauge(label, fraction) {
+    const radius = 40;
+    const length = Math.PI * radius;
+    const clamped = Math.min(Math.max(fraction, 0), 1);
+    const level = clamped > 0.9 ? "danger" : clamped > 0.75 ? "warning" : "";
+    const arc = `M 10 50 A ${radius} ${radius} 0 0 1 90 50`;
+
+    return element("div", {class: "gauge"},
+        svg("svg", {viewBox: "0 0 100 60", width: "100", height: "60"},
+            svg("path", {d: arc, class: "track", fill: "none", "stroke-width": "10"}),
+            svg("path", {
+                d: arc, class: `value ${level}`, fill: "none", "stroke-width": "10",
+                "stroke-dasharray": `${clamped * length} ${length}`
+            }),
+            svg("text", {x: "50", y: "48", "text-anchor": "middle"}, `${(clamped * 100).toFixed(0)}%`),
+        ),
+        element("div", {}, label),
+    );
+}
+
+function sparkline(values) {
+    const width = 100;
+    const height = 20;
+    const result = svg("svg", {class: "sparkline", viewBox: `0 0 ${width} ${height}`, width, height});
+    if (values.length < 2) {
+        return result;
+    }
+
+    const min = Math.min(...values);
+    const max = Math.max(...values);
+    const range = max - min || 1;
+    const points = values.map((value, i) => {
+        const x = i / (values.length - 1) * width;
+        const y = height - 1 - (value - min) / range * (height - 2);
+        return `${x.toFixed(1)},${y.toFixed(1)}`;
+    });
+
+    result.append(svg("polyline", {points: points.join(" ")}));
+    return result;
+}
+
+function lookup(value, path) {
+    for (const segment of path) {
+        if (value === null || typeof value !== "object") {
+            return undefined;
+        }
+        value = value[segment];
+    }
+    return value;
+}
+
+// walk the value tree, calling back for objects and leaves
+function walk(value, path, onObject, onLeaf) {
+    if (value !== null && typeof value === "object") {
+        onObject(value, path);
+        for (const key of Object.keys(value).sort()) {
+            walk(value[key], [...path, key], onObject, onLeaf);
+        }
+    } else {
+        onLeaf(value, path);
+    }
+}
+
+function renderCollector(name, value, history) {
+    const gauges = element("div", {class: "gauges"});
+    const rows = element("table");
+    const texts = element("div");
+
+    walk(value, [], (object, path) => {
+        if (typeof object.used === "number" && typeof object.total === "number" && object.total > 0) {
+            gauges.append(gauge(path.join(" ") || "used", object.used / object.total));
+        }
+    }, (leaf, path) => {
+        const key = path[path.length - 1] ?? name;
+        const label = path.join(" ") || name;
+
+        if (typeof leaf === "number") {
+            if (FRACTION_FIELDS.has(key)) {
+                gauges.append(gauge(path.slice(0, -1).join(" ") || key, leaf));
+            }
+            const values = history
+                .map((entry) => lookup(entry.values[name], path))
+                .filter((value) => typeof value === "number");
+            rows.append(element("tr", {},
+                element("td", {}, label),
+                element("td", {class: "value"}, formatValue(key, leaf)),
+                element("td", {}, sparkline(values)),
+            ));
+        } else if (key === "stdout" || key === "stderr") {
+            if (leaf) {
+                texts.append(element("pre", {}, leaf));
+            }
+        } else if (leaf !== null) {
+            rows.append(element("tr", {},
+                element("td", {}, label),
+                element("td", {class: "value"}, String(leaf)),
+                element("td"),
+            ));
+        }
+    });
+
+    return element("article", {class: "card"}, element("h2", {}, name), gauges, rows, texts);
+}
+
+async function refreshCollectors() {
+    let history = await api("history");
+    if (history.length === 0) {
+        history = [{timestamp: Date.now(), values: await api("collect")}];
+    }
+
+    const latest = history[history.length - 1];
+    const collectors = document.getElementById("collectors");
+    collectors.replaceChildren(...Object.keys(latest.values).sort()
+        .map((name) => renderCollector(name, latest.values[name], history)));
+
+    document.getElementById("status").textContent =
+        `Last update: ${new Date(latest.timestamp).toLocaleTimeString()}`;
+}
+
+async function refreshCommands() {
+    const commands = await api("commands");
+    const list = document.getElementById("commands");
+
+    list.replaceChildren(...commands.map((command) => {
+        const result = element("span", {class: "result"});
+        const button = element("button", {type: "button"}, command.label ?? command.name);
+        button.addEventListener("click", async () => {
+            button.disabled = true;
+            try {
+                await api(`commands/${encodeURIComponent(command.name)}`, {method: "POST", body: "{}"});
+                result.textContent = "started";
+            } catch (err) {
+                result.textContent = `failed: ${err.message}`;
+            } finally {
+                button.disabled = false;
+            }
+        });
+        return element("li", {}, button, result);
+    }));
+}
+
+function showLogin() {
+    const form = document.getElementById("login");
+    form.hidden = false;
+    document.getElementById("status").textContent = "Authentication required";
+}
+
+async function refresh() {
+    try {
+        await refreshCollectors();
+    } catch (err) {
+        if (err instanceof UnauthorizedError) {
+            showLogin();
+            return false;
+        }
+        document.getElementById("status").textContent = `Failed to refresh: ${err.message}`;
+    }
+    return true;
+}
+
+async function start() {
+    if (await refresh()) {
+        document.getElementById("login").hidden = true;
+        await refreshCommands().catch((err) => console.warn("Failed to load commands", err));
+    }
+}
+
+document.getElementById("login").addEventListener("submit", (event) => {
+    event.preventDefault();
+    token = document.getElementById("token").value;
+    localStorage.setItem(TOKEN_KEY, token);
+    start();
+});
+
+start();
+setInterval(refresh, REFRESH_INTERVAL);
@@ -0,0 +1,289 @@
+//! Embedded web dashboard
+
+use crate::{manager::Manager, utils::is_default};
+use actix_web::{get, http::header, post, web, HttpResponse, Responder};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+use tokio::time::{Instant, MissedTickBehavior};
+
+const INDEX_HTML: &str = include_str!("assets/index.html");
+const APP_JS: &str = include_str!("assets/app.js");
+const STYLE_CSS: &str = include_str!("assets/style.css");
+
+/// Stop collecting the history, when the dashboard didn't request it for this long
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// Disable the dashboard
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub disabled: bool,
+
+    /// Period of collecting values for the history, defaults to 10 seconds. Values are only
+    /// collected while the dashboard is open.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::utils::non_zero_duration"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub period: Option<Duration>,
+
+    /// Number of entries kept in the history, defaults to 60.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<usize>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Entry {
+    /// Milliseconds since the epoch
+    pub timestamp: u128,
+    pub values: BTreeMap<String, serde_json::Value>,
+}
+
+/// Short, in-memory history of collected values
+pub struct History {
+    entries: Mutex<VecDeque<Entry>>,
+    size: usize,
+    /// Last time the dashboard requested the history
+    viewed: Mutex<Option<Instant>>,
+}
+
+impl History {
+    pub fn new(size: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(size)),
+            size,
+            viewed: Default::default(),
+        }
+    }
+
+    /// Check if the dashboard requested the history recently
+    fn in_use(&self) -> bool {
+        self.viewed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some_and(|viewed| viewed.elapsed() < IDLE_TIMEOUT)
+    }
+
+    fn push(&self, entry: Entry) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        while !entries.is_empty() && entries.len() >= self.size {
+            entries.pop_front();
+        }
+        if self.size > 0 {
+            entries.push_back(entry);
+        }
+    }
+
+    /// The entries of the history, marking it as being in use
+    fn entries(&self) -> Vec<Entry> {
+        *self.viewed.lock().unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
+
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Periodically collect values into the history, while the dashboard is in use
+    pub async fn run(&self, manager: Arc<Manager>, period: Duration) {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+
+            if !self.in_use() {
+                // don't show a gap when the dashboard is opened again
+                self.entries
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clear();
+                continue;
+            }
+
+            match manager.collect_all().await {
+                Ok(values) => self.push(Entry {
+                    timestamp: SystemTime::now()
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis(),
+                    values,
+                }),
+                Err(err) => log::info!("Failed to collect history: {err}"),
+            }
+        }
+    }
+}
+
+#[get("/")]
+pub async fn index() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(INDEX_HTML)
+}
+
+#[get("/assets/{name}")]
+pub async fn assets(path: web::Path<String>) -> impl Responder {
+    let (content_type, body) = match path.as_str() {
+        "app.js" => ("text/javascript; charset=utf-8", APP_JS),
+        "style.css" => ("text/css; charset=utf-8", STYLE_CSS),
+        _ => return HttpResponse::NotFound().finish(),
+    };
+
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .body(body)
+}
+
+#[get("/history")]
+pub async fn history(history: web::Data<History>) -> impl Responder {
+    HttpResponse::Ok().json(history.entries())
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct CommandInfo {
+    name: String,
+    label: Option<String>,
+}
+
+#[get("/commands")]
+pub async fn commands(manager: web::Data<Manager>) -> impl Responder {
+    let mut commands = manager
+        .commands
+        .iter()
+        .map(|(name, command)| CommandInfo {
+            name: name.clone(),
+            label: command
+                .describe_ha()
+                .and_then(|entity| entity.discovery.name),
+        })
+        .collect::<Vec<_>>();
+    commands.sort_by(|a, b| a.name.cmp(&b.name));
+
+    HttpResponse::Ok().json(commands)
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+pub struct RunCommand {
+    /// Input of the command
+    #[serde(default)]
+    payload: String,
+}
+
+/// Run a command. The request must be JSON, which a plain HTML form of another site can't send.
+#[post("/commands/{name}")]
+pub async fn run_command(
+    path: web::Path<String>,
+    request: web::Json<RunCommand>,
+    manager: web::Data<Manager>,
+) -> impl Responder {
+    let name = path.into_inner();
+
+    if !manager.commands.contains_key(&name) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    log::info!("Running command from dashboard: {name}");
+
+    manager
+        .start_command(
+            &name,
+            Cow::Owned(request.into_inner().payload),
+            Box::new(move |result, _| {
+                Box::pin(async move {
+                    if result.is_ok() {
+                        log::info!("completed: ok");
+                    } else {
+                        log::info!("completed: failed");
+                    }
+                })
+            }),
+        )
+        .await;
+
+    HttpResponse::Accepted().finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(timestamp: u128) -> Entry {
+        Entry {
+            timestamp,
+            values: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        let store = History::new(2);
+        for timestamp in 0..3 {
+            store.push(entry(timestamp));
+        }
+        let timestamps = store
+            .entries()
+            .into_iter()
+            .map(|entry| entry.timestamp)
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps, vec![1, 2]);
+
+        let store = History::new(0);
+        store.push(entry(0));
+        assert!(store.entries().is_empty());
+    }
+
+    #[test]
+    fn test_in_use() {
+        let store = History::new(2);
+        assert!(!store.in_use());
+        store.entries();
+        assert!(store.in_use());
+    }
+
+    #[actix_web::test]
+    async fn test_run_command() {
+        use actix_web::{http::StatusCode, test, App};
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Manager::new()))
+                .service(run_command),
+        )
+        .await;
+
+        // like a form, posted by another site
+        let request = test::TestRequest::post()
+            .uri("/commands/reboot")
+            .insert_header((header::CONTENT_TYPE, "text/plain"))
+            .set_payload("")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = test::TestRequest::post()
+            .uri("/commands/reboot")
+            .set_json(serde_json::json!({}))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_zero_period() {
+        let options: Options = serde_json::from_value(serde_json::json!({"period": "5s"})).unwrap();
+        assert_eq!(options.period, Some(Duration::from_secs(5)));
+
+        assert!(serde_json::from_value::<Options>(serde_json::json!({"period": "0s"})).is_err());
+    }
+}
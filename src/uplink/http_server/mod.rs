@@ -0,0 +1,191 @@
+mod dashboard;
+mod openapi;
+
+use crate::{collector::tls, common::http, manager::Manager};
+use actix_web::{get, middleware::Logger, web, App, HttpResponse};
+use actix_web_extras::middleware::Condition;
+use actix_web_httpauth::{
+    extractors::{
+        bearer::{self, BearerAuth},
+        AuthenticationError,
+    },
+    middleware::HttpAuthentication,
+};
+use anyhow::bail;
+use std::{
+    net::{IpAddr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+const DEFAULT_BIND_PORT: u16 = 4242;
+const DEFAULT_BIND_HOST: IpAddr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// Remote access token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+
+    /// Allow disabling the authentication
+    #[serde(default)]
+    disable_authentication: bool,
+
+    /// Allow local callers, connected through the Unix domain socket, without an access token
+    #[serde(default, skip_serializing_if = "PeerCredentials::is_empty")]
+    peer_credentials: PeerCredentials,
+
+    /// Web dashboard
+    #[serde(default)]
+    dashboard: dashboard::Options,
+
+    #[serde(flatten)]
+    pub http: http::Options,
+}
+
+/// Allow-list of peer credentials
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerCredentials {
+    /// Allowed user IDs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    uids: Vec<u32>,
+
+    /// Allowed group IDs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    gids: Vec<u32>,
+}
+
+impl PeerCredentials {
+    fn is_empty(&self) -> bool {
+        self.uids.is_empty() && self.gids.is_empty()
+    }
+
+    fn allows(&self, peer: &http::PeerCredentials) -> bool {
+        self.uids.contains(&peer.uid) || self.gids.contains(&peer.gid)
+    }
+}
+
+#[get("/collect")]
+async fn collect_all(manager: web::Data<Manager>) -> actix_web::Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(manager.collect_all().await?))
+}
+
+#[get("/collect/{collector}")]
+async fn collect(
+    path: web::Path<String>,
+    manager: web::Data<Manager>,
+) -> actix_web::Result<HttpResponse> {
+    let collector = path.into_inner();
+
+    log::info!("Collecting: {collector}");
+
+    Ok(match manager.collect_one(&collector).await? {
+        Some(result) => HttpResponse::Ok().json(result),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+pub async fn run(options: Options, manager: Arc<Manager>, tls: tls::State) -> anyhow::Result<()> {
+    let manager = web::Data::from(manager);
+
+    let token = options.token.map(Arc::new);
+    let peers = Arc::new(options.peer_credentials);
+
+    let auth = (token.is_some() || !peers.is_empty()).then(|| {
+        HttpAuthentication::with_fn(move |req, credentials: Option<BearerAuth>| {
+            let token = token.clone();
+            let peers = peers.clone();
+            async move {
+                let peer = req
+                    .conn_data::<http::PeerCredentials>()
+                    .is_some_and(|peer| peers.allows(peer));
+                let bearer = matches!(
+                    (credentials, token),
+                    (Some(credentials), Some(token)) if credentials.token() == *token
+                );
+
+                if peer || bearer {
+                    Ok(req)
+                } else {
+                    let config = req
+                        .app_data::<bearer::Config>()
+                        .cloned()
+                        .unwrap_or_default()
+                        .scope("api");
+
+                    Err((AuthenticationError::from(config).into(), req))
+                }
+            }
+        })
+    });
+
+    if auth.is_none() {
+        if options.disable_authentication {
+            log::warn!("Running without access token. This is discouraged as it may compromise your system.");
+        } else {
+            bail!("Running without access token or peer credentials. This is discouraged as it may compromise your system. If you really want to do it, use --disable-authentication");
+        }
+    }
+
+    let document = web::Data::new(openapi::document(&manager, auth.is_some()));
+
+    let dashboard = !options.dashboard.disabled;
+    let history = web::Data::new(dashboard::History::new(
+        options.dashboard.history.unwrap_or(60),
+    ));
+    let period = options.dashboard.period.unwrap_or(Duration::from_secs(10));
+
+    let server = http::run_server(
+        options.http,
+        http::Defaults {
+            port: DEFAULT_BIND_PORT,
+            host: DEFAULT_BIND_HOST,
+        },
+        tls,
+        {
+            let manager = manager.clone();
+            let history = history.clone();
+            move || {
+                let api = web::scope("/api/v1")
+                    .wrap(Condition::from_option(auth.clone()))
+                    .app_data(document.clone())
+                    .service(openapi::openapi)
+                    .service(collect)
+                    .service(collect_all);
+
+                let api = if dashboard {
+                    api.app_data(history.clone())
+                        .service(dashboard::history)
+                        .service(dashboard::commands)
+                        .service(dashboard::run_command)
+                } else {
+                    api
+                };
+
+                let app = App::new()
+                    .app_data(manager.clone())
+                    .wrap(Logger::default())
+                    .service(api);
+
+                if dashboard {
+                    app.service(dashboard::index).service(dashboard::assets)
+                } else {
+                    app
+                }
+            }
+        },
+    );
+
+    if dashboard {
+        tokio::select! {
+            result = server => result?,
+            _ = history.run(manager.into_inner(), period) => {},
+        }
+    } else {
+        server.await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,183 @@
+//! OpenAPI description of the HTTP API
+
+use crate::manager::Manager;
+use actix_web::{get, web, HttpResponse, Responder};
+use schemars::gen::SchemaSettings;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// Generate the OpenAPI 3 document, describing the collect endpoints of the registered collectors.
+pub fn document(manager: &Manager, authentication: bool) -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+
+    let collectors = manager
+        .collectors
+        .iter()
+        .map(|(name, collector)| {
+            let schema = collector
+                .schema(&mut gen)
+                .and_then(|schema| serde_json::to_value(schema).ok())
+                .unwrap_or_else(|| json!({"type": "object"}));
+            (name.clone(), schema)
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let mut paths = Map::new();
+
+    paths.insert(
+        "/collect".into(),
+        json!({
+            "get": {
+                "operationId": "collectAll",
+                "summary": "Collect the values of all collectors",
+                "responses": {
+                    "200": response("Values, by collector", json!({"$ref": "#/components/schemas/Collected"})),
+                    "500": error(),
+                }
+            }
+        }),
+    );
+
+    for (name, schema) in &collectors {
+        paths.insert(
+            format!("/collect/{name}"),
+            json!({
+                "get": {
+                    "operationId": format!("collect_{name}"),
+                    "summary": format!("Collect the value of '{name}'"),
+                    "responses": {
+                        "200": response("Collected value", schema.clone()),
+                        "500": error(),
+                    }
+                }
+            }),
+        );
+    }
+
+    paths.insert(
+        "/collect/{collector}".into(),
+        json!({
+            "get": {
+                "operationId": "collect",
+                "summary": "Collect the value of a single collector",
+                "parameters": [{
+                    "name": "collector",
+                    "in": "path",
+                    "required": true,
+                    "schema": {"type": "string"},
+                }],
+                "responses": {
+                    "200": response("Collected value", json!({"type": "object"})),
+                    "404": {"description": "Unknown collector"},
+                    "500": error(),
+                }
+            }
+        }),
+    );
+
+    let mut schemas = gen
+        .take_definitions()
+        .into_iter()
+        .filter_map(|(name, schema)| Some((name, serde_json::to_value(schema).ok()?)))
+        .collect::<Map<_, _>>();
+
+    schemas.insert(
+        "Collected".into(),
+        json!({
+            "type": "object",
+            "properties": collectors,
+        }),
+    );
+    schemas.insert(
+        "Error".into(),
+        json!({
+            "type": "object",
+            "required": ["type", "message"],
+            "properties": {
+                "type": {"type": "string"},
+                "message": {"type": "string"},
+            }
+        }),
+    );
+
+    let mut document = json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "ReSyMo Agent",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{"url": "/api/v1"}],
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+        },
+    });
+
+    if authentication {
+        document["components"]["securitySchemes"] = json!({
+            "bearer": {"type": "http", "scheme": "bearer"}
+        });
+        document["security"] = json!([{"bearer": []}]);
+    }
+
+    document
+}
+
+fn response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": {
+            "application/json": {
+                "schema": schema
+            }
+        }
+    })
+}
+
+fn error() -> Value {
+    response(
+        "Failed to collect",
+        json!({"$ref": "#/components/schemas/Error"}),
+    )
+}
+
+#[get("/openapi.json")]
+pub async fn openapi(document: web::Data<Value>) -> impl Responder {
+    HttpResponse::Ok().json(document.as_ref())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::collector::{disk_free, memory};
+
+    #[test]
+    fn test_document() {
+        let mut manager = Manager::new();
+        manager.register_collector("memory", memory::Collector);
+        manager.register_collector("disk_free", disk_free::Collector);
+
+        let document = document(&manager, true);
+
+        assert_eq!(
+            document["paths"]["/collect/memory"]["get"]["responses"]["200"]["content"]
+                ["application/json"]["schema"]["$ref"],
+            "#/components/schemas/MemoryStatus"
+        );
+
+        let schemas = &document["components"]["schemas"];
+        assert_eq!(
+            schemas["MemoryStatus"]["properties"]["total"]["type"],
+            "integer"
+        );
+        assert_eq!(
+            schemas["DiskFreeStatus"]["properties"]["disks"]["additionalProperties"]["$ref"],
+            "#/components/schemas/DiskStatus"
+        );
+        assert_eq!(
+            schemas["Collected"]["properties"]["memory"]["$ref"],
+            "#/components/schemas/MemoryStatus"
+        );
+        assert_eq!(document["security"][0]["bearer"], json!([]));
+    }
+}
@@ -0,0 +1,392 @@
+//! InfluxDB uplink, pushing the state in the line protocol
+
+mod point;
+mod target;
+
+pub use target::{Api, ApiV1, ApiV2, HttpTarget, Target, UdpTarget};
+
+use crate::manager::Manager;
+use crate::uplink::{
+    buffer::{self, Buffer, Snapshot},
+    default_device_id,
+};
+use crate::utils::is_default;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+use target::{Failure, Writer};
+use tokio::time::MissedTickBehavior;
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// The device ID, used as `host` tag. Will default to the value of the `HOSTNAME` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+
+    /// Additional tags, added to all points
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+
+    /// Interval of collecting the state
+    #[serde(
+        with = "crate::utils::non_zero_duration",
+        default = "default::interval"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub interval: Duration,
+
+    /// Interval of writing the collected points, defaults to the collection interval
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::utils::non_zero_duration"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub flush_interval: Option<Duration>,
+
+    /// Maximum number of lines written with a single request
+    #[serde(default = "default::batch_size")]
+    pub batch_size: usize,
+
+    /// Maximum number of lines kept while the target is not available, dropping the oldest ones
+    #[serde(default = "default::buffer_size")]
+    pub buffer_size: usize,
+
+    /// Keep the state on disk instead of `bufferSize` lines in memory, surviving restarts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_buffer: Option<buffer::Options>,
+
+    /// Per-collector options
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub collectors: BTreeMap<String, CollectorOptions>,
+
+    /// Where to write to
+    pub target: Target,
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectorOptions {
+    /// Don't write the state of this collector
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub disabled: bool,
+
+    /// The measurement, defaults to the name of the collector
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measurement: Option<String>,
+}
+
+mod default {
+    use super::*;
+
+    pub const fn interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub const fn batch_size() -> usize {
+        5_000
+    }
+
+    pub const fn buffer_size() -> usize {
+        100_000
+    }
+}
+
+pub async fn run(options: Options, manager: Arc<Manager>) -> anyhow::Result<()> {
+    let mut tags = options.tags.clone();
+    tags.insert(
+        "host".into(),
+        options.device_id.clone().unwrap_or_else(default_device_id),
+    );
+
+    let disk_buffer = match options.disk_buffer.clone() {
+        Some(options) => Some(Buffer::open(options).await?),
+        None => None,
+    };
+
+    let mut uplink = Uplink {
+        writer: Writer::new(options.target.clone())?,
+        buffer: Default::default(),
+        disk_buffer,
+        tags,
+        options,
+    };
+
+    let mut collect = tokio::time::interval(uplink.options.interval);
+    collect.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut flush = tokio::time::interval(
+        uplink
+            .options
+            .flush_interval
+            .unwrap_or(uplink.options.interval),
+    );
+    flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = collect.tick() => {
+                uplink.collect(&manager).await;
+            }
+            _ = flush.tick() => {
+                uplink.flush().await;
+            }
+        }
+    }
+}
+
+struct Uplink {
+    options: Options,
+    writer: Writer,
+    /// Lines which have not been written yet
+    buffer: VecDeque<String>,
+    /// Snapshots which have not been written yet, replacing the buffer of lines
+    disk_buffer: Option<Buffer>,
+    tags: BTreeMap<String, String>,
+}
+
+impl Uplink {
+    async fn collect(&mut self, manager: &Manager) {
+        let state = match manager.collect_all().await {
+            Ok(state) => state,
+            Err(err) => {
+                log::warn!("Failed to collect state: {err}");
+                return;
+            }
+        };
+        let snapshot = Snapshot::now(state);
+
+        if let Some(disk_buffer) = &mut self.disk_buffer {
+            if let Err(err) = disk_buffer.push(snapshot).await {
+                log::warn!("Failed to buffer state: {err}");
+            }
+            return;
+        }
+
+        let lines = self.lines(&snapshot);
+        self.buffer.extend(lines);
+
+        let overflow = self.buffer.len().saturating_sub(self.options.buffer_size);
+        if overflow > 0 {
+            log::warn!("Buffer full, dropping {overflow} lines");
+            self.buffer.drain(..overflow);
+        }
+    }
+
+    /// Encode the state as lines, with the timestamp of the snapshot
+    fn lines(&self, snapshot: &Snapshot) -> Vec<String> {
+        let mut lines = vec![];
+        for (name, state) in &snapshot.state {
+            let options = self.options.collectors.get(name);
+            if options.is_some_and(|options| options.disabled) {
+                continue;
+            }
+
+            let measurement = options.and_then(|options| options.measurement.as_deref());
+            lines.extend(
+                point::points(name, measurement, state)
+                    .iter()
+                    .filter_map(|point| point.encode(&self.tags, snapshot.timestamp as u128)),
+            );
+        }
+        lines
+    }
+
+    /// Write the buffered lines in batches, keeping them in case of a temporary error. Lines
+    /// rejected by InfluxDB are dropped, as they would block all later ones.
+    async fn flush(&mut self) {
+        if self.disk_buffer.is_some() {
+            return self.flush_disk().await;
+        }
+
+        while !self.buffer.is_empty() {
+            let len = self.buffer.len().min(self.options.batch_size.max(1));
+            let batch = &self.buffer.make_contiguous()[..len];
+
+            match self.writer.write(batch).await {
+                Ok(()) => {
+                    log::debug!("Wrote {len} lines");
+                    self.buffer.drain(..len);
+                }
+                Err(Failure::Temporary(err)) => {
+                    log::warn!(
+                        "Failed to write to InfluxDB, keeping {} lines: {err}",
+                        self.buffer.len()
+                    );
+                    break;
+                }
+                Err(Failure::Permanent(err)) => {
+                    log::warn!("InfluxDB rejected {len} lines, dropping them: {err}");
+                    self.buffer.drain(..len);
+                }
+            }
+        }
+    }
+
+    /// Write the snapshots on disk, oldest first, in batches of whole snapshots.
+    async fn flush_disk(&mut self) {
+        let batch_size = self.options.batch_size.max(1);
+
+        loop {
+            let Some(disk_buffer) = &self.disk_buffer else {
+                return;
+            };
+
+            let mut batch = vec![];
+            let mut n = 0;
+            for snapshot in disk_buffer.iter() {
+                let lines = self.lines(snapshot);
+                if n > 0 && batch.len() + lines.len() > batch_size {
+                    break;
+                }
+                batch.extend(lines);
+                n += 1;
+            }
+            if n == 0 {
+                return;
+            }
+
+            match self.writer.write(&batch).await {
+                Ok(()) => log::debug!("Wrote {} lines", batch.len()),
+                Err(Failure::Temporary(err)) => {
+                    log::warn!(
+                        "Failed to write to InfluxDB, keeping {} snapshots: {err}",
+                        disk_buffer.len()
+                    );
+                    return;
+                }
+                Err(Failure::Permanent(err)) => {
+                    log::warn!("InfluxDB rejected {n} snapshots, dropping them: {err}");
+                }
+            }
+
+            if let Some(disk_buffer) = &mut self.disk_buffer {
+                if let Err(err) = disk_buffer.pop(n).await {
+                    log::warn!("Failed to update buffer: {err}");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Accept a single request, returning the request line, headers, and body
+    async fn accept(listener: TcpListener, response: &[u8]) -> (String, Vec<String>, Vec<u8>) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = BufReader::new(stream);
+
+        let mut request = String::new();
+        stream.read_line(&mut request).await.unwrap();
+
+        let mut headers = vec![];
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            let line = line.trim_end().to_lowercase();
+            if line.is_empty() {
+                break;
+            }
+            headers.push(line);
+        }
+
+        let len = headers
+            .iter()
+            .find_map(|header| header.strip_prefix("content-length: "))
+            .and_then(|len| len.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body).await.unwrap();
+
+        stream.write_all(response).await.unwrap();
+
+        (request.trim_end().to_string(), headers, body)
+    }
+
+    fn writer(url: String, gzip: bool) -> Writer {
+        Writer::new(Target::Http(HttpTarget {
+            url,
+            api: Api::V2(ApiV2 {
+                org: "my org".into(),
+                bucket: "resymo".into(),
+                token: Some("secret".into()),
+            }),
+            gzip,
+            timeout: Duration::from_secs(5),
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_write_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(accept(
+            listener,
+            b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n",
+        ));
+
+        let mut writer = writer(url, true);
+
+        let lines = ["memory,host=a free=1i 1", "memory,host=a free=2i 2"].map(String::from);
+        writer.write(&lines).await.unwrap();
+
+        let (request, headers, body) = server.await.unwrap();
+        assert_eq!(
+            request,
+            "POST /api/v2/write?org=my+org&bucket=resymo&precision=ns HTTP/1.1"
+        );
+        assert!(headers.contains(&"authorization: token secret".to_string()));
+        assert!(headers.contains(&"content-encoding: gzip".to_string()));
+
+        let mut content = String::new();
+        GzDecoder::new(body.as_slice())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(
+            content,
+            "memory,host=a free=1i 1\nmemory,host=a free=2i 2\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_failure() {
+        let lines = ["memory,host=a free=1i 1".to_string()];
+
+        for (response, permanent) in [
+            (
+                &b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n"[..],
+                true,
+            ),
+            (
+                b"HTTP/1.1 429 Too Many Requests\r\nContent-Length: 0\r\n\r\n",
+                false,
+            ),
+            (
+                b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+                false,
+            ),
+        ] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let server = tokio::spawn(async move { accept(listener, response).await });
+
+            let result = writer(url, false).write(&lines).await;
+            server.await.unwrap();
+            assert!(result.is_err());
+            assert_eq!(
+                matches!(result, Err(Failure::Permanent(_))),
+                permanent,
+                "{result:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,286 @@
+//! Mapping collector state to points, encoded in the line protocol
+
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, fmt::Write};
+
+/// A field value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    Float(f64),
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl Field {
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(match value {
+            Value::Bool(value) => Self::Boolean(*value),
+            Value::Number(value) => match value.as_i64() {
+                Some(value) => Self::Integer(value),
+                // the line protocol doesn't support NaN or infinity
+                None => Self::Float(value.as_f64().filter(|value| value.is_finite())?),
+            },
+            Value::String(value) => Self::String(value.clone()),
+            Value::Null | Value::Array(_) | Value::Object(_) => return None,
+        })
+    }
+}
+
+/// A single point
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    pub measurement: String,
+    pub tags: BTreeMap<String, String>,
+    pub fields: BTreeMap<String, Field>,
+}
+
+impl Point {
+    fn new(measurement: impl Into<String>) -> Self {
+        Self {
+            measurement: measurement.into(),
+            tags: Default::default(),
+            fields: Default::default(),
+        }
+    }
+
+    fn tag(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(name.into(), value.into());
+        self
+    }
+
+    /// Add all scalar values of an object as fields, nested objects are joined with `_`.
+    fn fields(mut self, value: &Map<String, Value>) -> Self {
+        fn add(fields: &mut BTreeMap<String, Field>, prefix: &str, value: &Map<String, Value>) {
+            for (name, value) in value {
+                let name = match prefix {
+                    "" => name.clone(),
+                    prefix => format!("{prefix}_{name}"),
+                };
+                match value {
+                    Value::Object(value) => add(fields, &name, value),
+                    value => {
+                        if let Some(field) = Field::from_value(value) {
+                            fields.insert(name, field);
+                        }
+                    }
+                }
+            }
+        }
+
+        add(&mut self.fields, "", value);
+        self
+    }
+
+    /// Encode as line protocol, with a timestamp in nanoseconds. Returns `None` for a point
+    /// without fields, as it cannot be written.
+    pub fn encode(&self, tags: &BTreeMap<String, String>, timestamp: u128) -> Option<String> {
+        if self.fields.is_empty() {
+            return None;
+        }
+
+        let mut line = escape(&self.measurement, &[',', ' ']);
+
+        let mut all = tags.clone();
+        all.extend(self.tags.clone());
+        for (name, value) in all.iter().filter(|(_, value)| !value.is_empty()) {
+            let _ = write!(
+                line,
+                ",{}={}",
+                escape(name, &[',', '=', ' ']),
+                escape(value, &[',', '=', ' '])
+            );
+        }
+
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            line.push(if i == 0 { ' ' } else { ',' });
+            line.push_str(&escape(name, &[',', '=', ' ']));
+            line.push('=');
+            match value {
+                Field::Float(value) => {
+                    let _ = write!(line, "{value}");
+                }
+                Field::Integer(value) => {
+                    let _ = write!(line, "{value}i");
+                }
+                Field::Boolean(value) => {
+                    let _ = write!(line, "{value}");
+                }
+                Field::String(value) => {
+                    let _ = write!(line, "\"{}\"", escape(value, &['"']));
+                }
+            }
+        }
+
+        let _ = write!(line, " {timestamp}");
+
+        Some(line)
+    }
+}
+
+/// Escape the special characters, as well as backslashes and line breaks
+fn escape(value: &str, special: &[char]) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => {}
+            c if special.contains(&c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Map the state of a collector to points.
+///
+/// The built-in collectors get a mapping of their own, others get all their scalar values as fields.
+pub fn points(name: &str, measurement: Option<&str>, state: &Value) -> Vec<Point> {
+    let measurement = measurement.unwrap_or(name);
+    let Value::Object(state) = state else {
+        return Field::from_value(state)
+            .map(|field| {
+                let mut point = Point::new(measurement);
+                point.fields.insert("value".into(), field);
+                point
+            })
+            .into_iter()
+            .collect();
+    };
+
+    match name {
+        // one point per disk
+        "disk_free" => state
+            .get("disks")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(disk, status)| {
+                status.as_object().map(|status| {
+                    Point::new(measurement)
+                        .tag("disk", disk)
+                        .fields(&select(status, &["free", "total", "usage"]))
+                })
+            })
+            .collect(),
+        // the list of packages is too large for a single point
+        "packages" => vec![Point::new(measurement)
+            .tag(
+                "backend",
+                state
+                    .get("backend")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default(),
+            )
+            .fields(&select(
+                state,
+                &["pending", "security", "installed_version"],
+            ))],
+        "reboot_required" => {
+            let mut point = Point::new(measurement).fields(&select(state, &["required"]));
+            for list in ["reasons", "services", "processes"] {
+                if let Some(len) = state.get(list).and_then(Value::as_array).map(Vec::len) {
+                    point.fields.insert(list.into(), Field::Integer(len as _));
+                }
+            }
+            vec![point]
+        }
+        // exec items, the output is used as value if it is numeric
+        _ if ["stdout", "stderr", "status"]
+            .iter()
+            .all(|key| state.contains_key(*key)) =>
+        {
+            let mut point = Point::new(measurement).fields(&select(state, &["status"]));
+            if let Some(stdout) = state.get("stdout").and_then(Value::as_str) {
+                let stdout = stdout.trim();
+                match stdout.parse::<f64>() {
+                    Ok(value) if value.is_finite() => {
+                        point.fields.insert("value".into(), Field::Float(value))
+                    }
+                    _ => point
+                        .fields
+                        .insert("stdout".into(), Field::String(stdout.into())),
+                };
+            }
+            vec![point]
+        }
+        _ => vec![Point::new(measurement).fields(state)],
+    }
+}
+
+fn select(state: &Map<String, Value>, keys: &[&str]) -> Map<String, Value> {
+    keys.iter()
+        .filter_map(|key| {
+            state
+                .get(*key)
+                .map(|value| (key.to_string(), value.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn encode(name: &str, state: Value) -> Vec<String> {
+        let tags = BTreeMap::from([("host".to_string(), "my host".to_string())]);
+        points(name, None, &state)
+            .iter()
+            .filter_map(|point| point.encode(&tags, 1))
+            .collect()
+    }
+
+    #[test]
+    fn test_builtin() {
+        assert_eq!(
+            encode(
+                "memory",
+                json!({"free": 1, "total": 2, "used": 1, "available": 1})
+            ),
+            vec!["memory,host=my\\ host available=1i,free=1i,total=2i,used=1i 1"]
+        );
+        assert_eq!(
+            encode(
+                "disk_free",
+                json!({"disks": {
+                    "/dev/sda1": {"free": 1, "total": 4, "usage": 0.75, "mount_point": "/"},
+                    "tank, data": {"free": 0, "total": 0, "usage": 0.0},
+                }})
+            ),
+            vec![
+                "disk_free,disk=/dev/sda1,host=my\\ host free=1i,total=4i,usage=0.75 1",
+                "disk_free,disk=tank\\,\\ data,host=my\\ host free=0i,total=0i,usage=0 1"
+            ]
+        );
+        assert_eq!(
+            encode(
+                "reboot_required",
+                json!({"required": true, "reasons": ["kernel"], "services": [], "processes": [], "running_kernel": "6.1"})
+            ),
+            vec!["reboot_required,host=my\\ host processes=0i,reasons=1i,required=true,services=0i 1"]
+        );
+    }
+
+    #[test]
+    fn test_exec() {
+        assert_eq!(
+            encode(
+                "temperature",
+                json!({"stdout": "42.5\n", "stderr": "", "status": 0})
+            ),
+            vec!["temperature,host=my\\ host status=0i,value=42.5 1"]
+        );
+        assert_eq!(
+            encode(
+                "greeting",
+                json!({"stdout": "say \"hi\"\n", "stderr": "", "status": 0})
+            ),
+            vec!["greeting,host=my\\ host status=0i,stdout=\"say \\\"hi\\\"\" 1"]
+        );
+    }
+}
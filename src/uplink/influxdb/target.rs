@@ -0,0 +1,226 @@
+//! Writing lines to InfluxDB
+
+use crate::{uplink::udp, utils::is_default};
+use anyhow::{anyhow, Context};
+use flate2::{write::GzEncoder, Compression};
+use reqwest::StatusCode;
+use std::{io::Write, time::Duration};
+
+/// Where to write the data to
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Target {
+    /// The HTTP API
+    Http(HttpTarget),
+    /// The UDP listener (InfluxDB 1.x and Telegraf)
+    Udp(UdpTarget),
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpTarget {
+    /// The base URL of the server, e.g. `http://localhost:8086`
+    pub url: String,
+
+    /// The version of the API
+    #[serde(flatten)]
+    pub api: Api,
+
+    /// Compress the request body using gzip
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub gzip: bool,
+
+    /// Timeout of a request
+    #[serde(with = "humantime_serde", default = "default::timeout")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub timeout: Duration,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "version", rename_all = "camelCase")]
+pub enum Api {
+    /// InfluxDB 1.x, using `/write`
+    V1(ApiV1),
+    /// InfluxDB 2.x (and 3.x), using `/api/v2/write`
+    V2(ApiV2),
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiV1 {
+    /// The database
+    pub database: String,
+
+    /// The retention policy, defaults to the one of the database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_policy: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiV2 {
+    /// The organization
+    pub org: String,
+
+    /// The bucket
+    pub bucket: String,
+
+    /// The API token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UdpTarget {
+    /// The address of the UDP listener, e.g. `localhost:8089`
+    pub address: String,
+
+    /// Maximum size of a datagram, lines are split across multiple datagrams
+    #[serde(default = "default::max_packet_size")]
+    pub max_packet_size: usize,
+}
+
+mod default {
+    use super::*;
+
+    pub const fn timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub const fn max_packet_size() -> usize {
+        1400
+    }
+}
+
+/// A failed write
+#[derive(Debug, thiserror::Error)]
+pub enum Failure {
+    /// Worth trying again, e.g. a connection error or an unavailable server
+    #[error(transparent)]
+    Temporary(anyhow::Error),
+    /// Will fail again, e.g. lines rejected by InfluxDB
+    #[error(transparent)]
+    Permanent(anyhow::Error),
+}
+
+/// Writes lines to the configured target
+pub enum Writer {
+    Http {
+        client: reqwest::Client,
+        target: HttpTarget,
+    },
+    Udp(udp::Sender),
+}
+
+impl Writer {
+    pub fn new(target: Target) -> anyhow::Result<Self> {
+        Ok(match target {
+            Target::Http(target) => {
+                let client = reqwest::Client::builder().timeout(target.timeout);
+                // same as for the HTTP server, rustls takes precedence
+                #[cfg(feature = "rustls")]
+                let client = client.use_rustls_tls();
+
+                Self::Http {
+                    client: client.build().context("Failed to create HTTP client")?,
+                    target,
+                }
+            }
+            Target::Udp(target) => {
+                Self::Udp(udp::Sender::new(target.address, target.max_packet_size))
+            }
+        })
+    }
+
+    pub async fn write(&mut self, lines: &[String]) -> Result<(), Failure> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        match self {
+            Self::Http { client, target } => write_http(client, target, lines).await,
+            Self::Udp(sender) => sender.send(lines).await.map_err(Failure::Temporary),
+        }
+    }
+}
+
+async fn write_http(
+    client: &reqwest::Client,
+    target: &HttpTarget,
+    lines: &[String],
+) -> Result<(), Failure> {
+    let url = target.url.trim_end_matches('/');
+
+    let mut body = lines.join("\n").into_bytes();
+    body.push(b'\n');
+
+    let mut request = match &target.api {
+        Api::V1(api) => {
+            let mut request = client
+                .post(format!("{url}/write"))
+                .query(&[("db", api.database.as_str()), ("precision", "ns")]);
+            if let Some(rp) = &api.retention_policy {
+                request = request.query(&[("rp", rp)]);
+            }
+            if let Some(username) = &api.username {
+                request = request.basic_auth(username, api.password.as_ref());
+            }
+            request
+        }
+        Api::V2(api) => {
+            let mut request = client.post(format!("{url}/api/v2/write")).query(&[
+                ("org", api.org.as_str()),
+                ("bucket", api.bucket.as_str()),
+                ("precision", "ns"),
+            ]);
+            if let Some(token) = &api.token {
+                request = request.header("Authorization", format!("Token {token}"));
+            }
+            request
+        }
+    };
+
+    request = request.header("Content-Type", "text/plain; charset=utf-8");
+    if target.gzip {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&body)
+            .map_err(|err| Failure::Temporary(err.into()))?;
+        body = encoder
+            .finish()
+            .map_err(|err| Failure::Temporary(err.into()))?;
+        request = request.header("Content-Encoding", "gzip");
+    }
+
+    let response = request
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| Failure::Temporary(err.into()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    let err = anyhow!("Failed to write: {status}: {}", text.trim());
+    // failed credentials may get fixed on the side of InfluxDB, so keep the lines until then
+    if status.is_server_error()
+        || matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        )
+    {
+        Err(Failure::Temporary(err))
+    } else {
+        Err(Failure::Permanent(err))
+    }
+}
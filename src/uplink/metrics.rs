@@ -0,0 +1,98 @@
+//! Flattening collector state into numeric metrics
+
+use serde_json::Value;
+
+/// Flatten the state of a collector into metrics, below `{prefix}.{name}`.
+///
+/// Only numeric values are used. Booleans become `1` or `0`, strings are used when they are numeric
+/// (like the output of an exec item). Lists, NaN, and infinite values are skipped.
+pub fn metrics(prefix: &str, name: &str, state: &Value) -> Vec<(String, f64)> {
+    let mut result = vec![];
+    add(&mut result, format!("{prefix}.{}", segment(name)), state);
+    result
+}
+
+fn add(result: &mut Vec<(String, f64)>, path: String, value: &Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                add(result, format!("{path}.{}", segment(key)), value);
+            }
+        }
+        Value::Number(value) => {
+            if let Some(value) = value.as_f64() {
+                result.push((path, value));
+            }
+        }
+        Value::Bool(value) => result.push((path, if *value { 1.0 } else { 0.0 })),
+        Value::String(value) => {
+            // neither carbon nor StatsD accept NaN or infinity
+            if let Some(value) = value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+            {
+                result.push((path, value));
+            }
+        }
+        Value::Null | Value::Array(_) => {}
+    }
+}
+
+/// Turn a key into a single path segment. Keys may be paths themselves, like `/dev/sda1`.
+pub fn segment(key: &str) -> String {
+    let segment = key
+        .trim_matches('/')
+        .replace(['.', '/', ' ', ':'], "_")
+        .replace(|c: char| c.is_control(), "");
+    if segment.is_empty() {
+        // the root file system
+        "root".into()
+    } else {
+        segment
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_metrics() {
+        let result = metrics(
+            "servers.host",
+            "disk_free",
+            &json!({"disks": {"/": {"usage": 0.5, "total": 100}, "/dev/sda1": {"usage": 0.25}}}),
+        );
+        assert_eq!(
+            result,
+            vec![
+                ("servers.host.disk_free.disks.root.total".into(), 100.0),
+                ("servers.host.disk_free.disks.root.usage".into(), 0.5),
+                ("servers.host.disk_free.disks.dev_sda1.usage".into(), 0.25),
+            ]
+        );
+
+        let result = metrics(
+            "servers.host",
+            "temperature",
+            &json!({"stdout": "42.5\n", "stderr": "", "status": 0, "reasons": [1]}),
+        );
+        assert_eq!(
+            result,
+            vec![
+                ("servers.host.temperature.status".into(), 0.0),
+                ("servers.host.temperature.stdout".into(), 42.5),
+            ]
+        );
+
+        let result = metrics(
+            "servers.host",
+            "sensors",
+            &json!({"a": {"stdout": "NaN"}, "b": {"stdout": "-inf"}}),
+        );
+        assert_eq!(result, vec![]);
+    }
+}
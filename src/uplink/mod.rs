@@ -1,2 +1,67 @@
+pub mod buffer;
+pub mod checkmk;
+pub mod file;
+pub mod graphite;
 pub mod homeassistant;
 pub mod http_server;
+pub mod influxdb;
+pub mod mqtt;
+pub mod otlp;
+pub mod webhook;
+pub mod zabbix;
+
+mod metrics;
+mod udp;
+
+use gethostname::gethostname;
+
+/// Generate a default device id, from the hostname
+pub(crate) fn default_device_id() -> String {
+    scrub_device_id(&gethostname().to_string_lossy())
+}
+
+fn scrub_device_id(device_id: &str) -> String {
+    device_id.replace(
+        |c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "_",
+    )
+}
+
+/// Ensure a topic template only uses known placeholders, and contains the required ones.
+pub(crate) fn validate_topic(
+    template: &str,
+    known: &[&str],
+    required: &[&str],
+) -> anyhow::Result<()> {
+    for placeholder in template
+        .split('{')
+        .skip(1)
+        .filter_map(|s| s.split_once('}').map(|(name, _)| name))
+    {
+        if !known.contains(&placeholder) {
+            anyhow::bail!("Unknown placeholder '{{{placeholder}}}' in topic template: {template}");
+        }
+    }
+
+    for placeholder in required {
+        if !template.contains(&format!("{{{placeholder}}}")) {
+            anyhow::bail!("Topic template must contain '{{{placeholder}}}': {template}");
+        }
+    }
+
+    if template.contains(['+', '#']) {
+        anyhow::bail!("Topic template must not contain wildcards: {template}");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scrub_device_id() {
+        assert_eq!(scrub_device_id("foo.bar.baz"), "foo_bar_baz")
+    }
+}
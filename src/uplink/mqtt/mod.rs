@@ -0,0 +1,505 @@
+//! Generic MQTT uplink, publishing plain JSON without any Home Assistant discovery
+
+mod publish;
+
+pub use publish::{PublishOptions, Qos};
+
+use crate::manager::Manager;
+use crate::uplink::{
+    buffer::{self, Buffer, Snapshot},
+    default_device_id, validate_topic,
+};
+use crate::utils::is_default;
+use publish::Policy;
+use rumqttc::{AsyncClient, ClientError, Event, EventLoop, Incoming, LastWill, MqttOptions, QoS};
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use rumqttc::{TlsConfiguration, Transport};
+use serde_json::Value;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::time::Instant;
+
+const DEFAULT_COMMAND_TOPIC: &str = "{base}/{device}/command/{command}";
+
+const PAYLOAD_ONLINE: &str = "online";
+const PAYLOAD_OFFLINE: &str = "offline";
+
+const PAYLOAD_RUNNING: &str = "running";
+const PAYLOAD_SUCCEEDED: &str = "succeeded";
+const PAYLOAD_FAILED: &str = "failed";
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// The device ID. Will default to the value of the `HOSTNAME` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+
+    /// Base topic
+    #[serde(default = "default::base")]
+    pub base: String,
+
+    /// Publishing of the state
+    #[serde(flatten)]
+    pub publish: PublishOptions,
+
+    /// Per-collector overrides of the publishing options
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub collectors: BTreeMap<String, PublishOptions>,
+
+    /// Commands, triggered by publishing to their topic
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub commands: CommandOptions,
+
+    /// Keep the state on disk while the broker is not reachable, replaying it afterwards.
+    ///
+    /// The replayed messages don't carry the time the state was collected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_buffer: Option<buffer::Options>,
+
+    /// MQTT connection
+    pub connector: ConnectorOptions,
+}
+
+#[derive(
+    Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandOptions {
+    /// Don't subscribe to any commands
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub disabled: bool,
+
+    /// Template of the command topics, defaults to `{base}/{device}/command/{command}`.
+    ///
+    /// Placeholders: `{base}`, `{device}`, `{command}` (name of the command). The state of the
+    /// command is published to `<topic>/state`, its value (if any) to `<topic>/value`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectorOptions {
+    /// The MQTT client id, defaults to `resymo-<device id>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+
+    /// The MQTT server's hostname
+    pub host: String,
+
+    /// The MQTT server's port, defaults to 1883 without TLS and 8883 with TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+
+    /// TLS is used by default, you can disable it here.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub disable_tls: bool,
+
+    #[serde(with = "humantime_serde", default = "default::keep_alive")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub keep_alive: Duration,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+mod default {
+    use super::*;
+
+    pub fn base() -> String {
+        "resymo".to_string()
+    }
+
+    pub const fn keep_alive() -> Duration {
+        Duration::from_secs(5)
+    }
+}
+
+impl ConnectorOptions {
+    fn mqtt_options(
+        &self,
+        device_id: &str,
+        availability_topic: &str,
+    ) -> anyhow::Result<MqttOptions> {
+        let client_id = self
+            .client_id
+            .clone()
+            .unwrap_or_else(|| format!("resymo-{device_id}"));
+        let port = self
+            .port
+            .unwrap_or(if self.disable_tls { 1883 } else { 8883 });
+
+        let mut options = MqttOptions::new(client_id, &self.host, port);
+        options.set_keep_alive(self.keep_alive);
+
+        if !self.disable_tls {
+            #[cfg(any(feature = "openssl", feature = "rustls"))]
+            options.set_transport(Transport::Tls(tls_configuration()?));
+            #[cfg(not(any(feature = "openssl", feature = "rustls")))]
+            anyhow::bail!("Built without support for TLS, set disableTls to connect without it");
+        }
+
+        if let Some(username) = &self.username {
+            options.set_credentials(username, self.password.clone().unwrap_or_default());
+        }
+
+        options.set_last_will(LastWill::new(
+            availability_topic,
+            PAYLOAD_OFFLINE,
+            QoS::AtLeastOnce,
+            true,
+        ));
+
+        Ok(options)
+    }
+}
+
+// same as for the HTTP server, rustls takes precedence
+#[cfg(feature = "rustls")]
+fn tls_configuration() -> anyhow::Result<TlsConfiguration> {
+    use anyhow::Context;
+
+    let mut roots = rustls::RootCertStore::empty();
+    for certificate in rustls_native_certs::load_native_certs()
+        .context("Failed to load the system's certificates")?
+    {
+        roots.add(certificate)?;
+    }
+
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    Ok(TlsConfiguration::Rustls(Arc::new(config)))
+}
+
+#[cfg(all(feature = "openssl", not(feature = "rustls")))]
+fn tls_configuration() -> anyhow::Result<TlsConfiguration> {
+    Ok(TlsConfiguration::Native)
+}
+
+pub async fn run(options: Options, manager: Arc<Manager>) -> anyhow::Result<()> {
+    let device_id = options.device_id.unwrap_or_else(default_device_id);
+    let base = options.base;
+
+    let mut policies = HashMap::new();
+    for name in manager.collectors.keys() {
+        let policy = Policy::new(
+            &base,
+            &device_id,
+            name,
+            &options.publish,
+            options.collectors.get(name),
+        )?;
+        policies.insert(name.clone(), policy);
+    }
+    for name in options.collectors.keys() {
+        if !manager.collectors.contains_key(name) {
+            log::warn!("Ignoring publishing options of unknown collector: {name}");
+        }
+    }
+
+    // command topic -> command name
+    let mut commands = HashMap::new();
+    if !options.commands.disabled {
+        let template = options
+            .commands
+            .topic
+            .as_deref()
+            .unwrap_or(DEFAULT_COMMAND_TOPIC);
+        validate_topic(template, &["base", "device", "command"], &[])?;
+        for name in manager.commands.keys() {
+            let topic = template
+                .replace("{base}", &base)
+                .replace("{device}", &device_id)
+                .replace("{command}", name);
+            commands.insert(topic, name.clone());
+        }
+    }
+
+    let availability_topic = format!("{base}/{device_id}/availability");
+    let mqtt_options = options
+        .connector
+        .mqtt_options(&device_id, &availability_topic)?;
+    log::debug!("Options: {mqtt_options:#?}");
+
+    let disk_buffer = match options.disk_buffer {
+        Some(options) => Some(Buffer::open(options).await?),
+        None => None,
+    };
+
+    let (client, eventloop) = AsyncClient::new(mqtt_options, 128);
+
+    let uplink = Uplink {
+        client,
+        manager,
+        availability_topic,
+        commands,
+    };
+
+    tokio::select! {
+        _ = uplink.publish(&policies, disk_buffer) => {}
+        _ = uplink.events(eventloop) => {}
+    }
+
+    Ok(())
+}
+
+struct Uplink {
+    client: AsyncClient,
+    manager: Arc<Manager>,
+    availability_topic: String,
+    /// Command names, by their topic
+    commands: HashMap<String, String>,
+}
+
+impl Uplink {
+    /// Periodically collect and publish the state of the collectors
+    async fn publish(&self, policies: &HashMap<String, Policy>, mut disk_buffer: Option<Buffer>) {
+        let now = Instant::now();
+        let mut next = policies
+            .keys()
+            .map(|name| (name.as_str(), now))
+            .collect::<HashMap<_, _>>();
+
+        loop {
+            match next.values().min().copied() {
+                Some(due) => tokio::time::sleep_until(due).await,
+                None => std::future::pending().await,
+            }
+
+            log::debug!("Update state");
+            if let Some(disk_buffer) = &mut disk_buffer {
+                self.replay(policies, disk_buffer).await;
+            }
+
+            let now = Instant::now();
+            for (name, next) in next.iter_mut().filter(|(_, next)| **next <= now) {
+                let Some(policy) = policies.get(*name) else {
+                    continue;
+                };
+                *next = now + policy.interval;
+
+                if let Err(err) = self.update(name, policy, disk_buffer.as_mut()).await {
+                    log::warn!("Failed to publish state of '{name}': {err}");
+                }
+            }
+        }
+    }
+
+    async fn update(
+        &self,
+        name: &str,
+        policy: &Policy,
+        disk_buffer: Option<&mut Buffer>,
+    ) -> anyhow::Result<()> {
+        let Some(state) = self.manager.collect_one(name).await? else {
+            return Ok(());
+        };
+
+        let Some(disk_buffer) = disk_buffer else {
+            for (topic, payload) in policy.messages(&state) {
+                self.client
+                    .publish(topic, policy.qos, policy.retain, payload)
+                    .await?;
+            }
+            return Ok(());
+        };
+
+        // keep the order, states still waiting in the buffer go first
+        if disk_buffer.is_empty() && self.try_publish(policy, &state).is_ok() {
+            return Ok(());
+        }
+
+        disk_buffer
+            .push(Snapshot::now(BTreeMap::from([(name.to_string(), state)])))
+            .await
+    }
+
+    /// Queue the messages of a state, without waiting for the connection
+    fn try_publish(&self, policy: &Policy, state: &Value) -> Result<(), ClientError> {
+        for (topic, payload) in policy.messages(state) {
+            self.client
+                .try_publish(topic, policy.qos, policy.retain, payload)?;
+        }
+
+        Ok(())
+    }
+
+    /// Publish the buffered states, oldest first, as long as the client takes them
+    async fn replay(&self, policies: &HashMap<String, Policy>, disk_buffer: &mut Buffer) {
+        let mut sent = 0;
+        'replay: for snapshot in disk_buffer.iter() {
+            for (name, state) in &snapshot.state {
+                // the collector may be gone since the previous run
+                let Some(policy) = policies.get(name) else {
+                    continue;
+                };
+                if self.try_publish(policy, state).is_err() {
+                    break 'replay;
+                }
+            }
+            sent += 1;
+        }
+
+        if sent > 0 {
+            log::info!(
+                "Replayed {sent} buffered states, {} left",
+                disk_buffer.len() - sent
+            );
+            if let Err(err) = disk_buffer.pop(sent).await {
+                log::warn!("Failed to update buffer: {err}");
+            }
+        }
+    }
+
+    /// Drive the connection, handling incoming commands
+    async fn events(&self, mut eventloop: EventLoop) {
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                    log::info!("Connected");
+                    self.connected();
+                }
+                Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                    match self.commands.get(&publish.topic) {
+                        // a retained trigger would run the command again with every reconnect
+                        Some(name) if publish.retain => {
+                            log::warn!("Ignoring retained trigger for command: {name}");
+                        }
+                        Some(name) => {
+                            let payload = String::from_utf8_lossy(&publish.payload);
+                            self.handle_command(name, &publish.topic, payload).await;
+                        }
+                        None => {
+                            log::warn!("received message for unknown topic: {}", publish.topic);
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    log::warn!("Connection failed: {err}");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        }
+    }
+
+    /// Subscribe to commands and announce availability, without blocking the event loop
+    fn connected(&self) {
+        for topic in self.commands.keys() {
+            if let Err(err) = self.client.try_subscribe(topic, QoS::AtLeastOnce) {
+                log::warn!("Failed to subscribe to '{topic}': {err}");
+            }
+        }
+
+        if let Err(err) = self.client.try_publish(
+            &self.availability_topic,
+            QoS::AtLeastOnce,
+            true,
+            PAYLOAD_ONLINE,
+        ) {
+            log::warn!("Failed to announce availability: {err}");
+        }
+    }
+
+    async fn handle_command(&self, name: &str, topic: &str, payload: Cow<'_, str>) {
+        let state_topic = format!("{topic}/state");
+        let value_topic = format!("{topic}/value");
+
+        if let Err(err) =
+            self.client
+                .try_publish(&state_topic, QoS::AtLeastOnce, true, PAYLOAD_RUNNING)
+        {
+            log::warn!("Failed to publish state of command '{name}': {err}");
+        }
+
+        let client = self.client.clone();
+        let command = name.to_string();
+        let started = self
+            .manager
+            .start_command(
+                name,
+                payload,
+                Box::new(move |result, value| {
+                    Box::pin(async move {
+                        if let Some(value) = value {
+                            let _ = client
+                                .publish(value_topic, QoS::AtLeastOnce, true, value)
+                                .await;
+                        }
+                        let state = if result.is_ok() {
+                            PAYLOAD_SUCCEEDED
+                        } else {
+                            PAYLOAD_FAILED
+                        };
+                        let _ = client
+                            .publish(state_topic, QoS::AtLeastOnce, true, state)
+                            .await;
+
+                        log::info!("Command '{command}' completed: {state}");
+                    })
+                }),
+            )
+            .await;
+
+        if !started {
+            log::warn!("Received trigger for unknown command: {topic}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_replay() {
+        let dir = std::env::temp_dir().join(format!("resymo-mqtt-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let mut disk_buffer = Buffer::open(buffer::Options {
+            path: dir.join("buffer.jsonl"),
+            max_size: 1024,
+        })
+        .await
+        .unwrap();
+        for (name, free) in [("memory", 1), ("removed", 2), ("memory", 3), ("memory", 4)] {
+            let state = BTreeMap::from([(name.to_string(), json!({"free": free}))]);
+            disk_buffer.push(Snapshot::now(state)).await.unwrap();
+        }
+
+        // the event loop is never polled, the client only takes two messages
+        let (client, _eventloop) = AsyncClient::new(MqttOptions::new("test", "localhost", 1883), 2);
+        let uplink = Uplink {
+            client,
+            manager: Arc::new(Manager::new()),
+            availability_topic: "resymo/host/availability".into(),
+            commands: Default::default(),
+        };
+        let policies = HashMap::from([(
+            "memory".to_string(),
+            Policy::new("resymo", "host", "memory", &Default::default(), None).unwrap(),
+        )]);
+
+        uplink.replay(&policies, &mut disk_buffer).await;
+        assert_eq!(
+            disk_buffer
+                .iter()
+                .map(|s| &s.state["memory"])
+                .collect::<Vec<_>>(),
+            [&json!({"free": 4})]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+//! Publishing of collected state
+
+use crate::uplink::validate_topic;
+use serde_json::Value;
+use std::time::Duration;
+
+const DEFAULT_TOPIC: &str = "{base}/{device}/{collector}";
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// MQTT quality of service
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Deserialize,
+    serde::Serialize,
+    schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Qos {
+    #[default]
+    AtMostOnce,
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+impl From<Qos> for rumqttc::QoS {
+    fn from(value: Qos) -> Self {
+        match value {
+            Qos::AtMostOnce => Self::AtMostOnce,
+            Qos::AtLeastOnce => Self::AtLeastOnce,
+            Qos::ExactlyOnce => Self::ExactlyOnce,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishOptions {
+    /// Template of the state topic, defaults to `{base}/{device}/{collector}`.
+    ///
+    /// Placeholders: `{base}`, `{device}`, `{collector}` (name of the collector).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+
+    /// Interval of collecting and publishing the state, defaults to 10 seconds.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::utils::non_zero_duration"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub interval: Option<Duration>,
+
+    /// Quality of service of the state messages, defaults to `atMostOnce`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qos: Option<Qos>,
+
+    /// Publish the state as retained message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retain: Option<bool>,
+
+    /// Publish each field on a topic of its own, below the state topic, instead of a single JSON document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flatten: Option<bool>,
+}
+
+/// Effective publishing policy of a collector
+#[derive(Clone, Debug)]
+pub struct Policy {
+    pub topic: String,
+    pub interval: Duration,
+    pub qos: rumqttc::QoS,
+    pub retain: bool,
+    pub flatten: bool,
+}
+
+impl Policy {
+    pub fn new(
+        base: &str,
+        device_id: &str,
+        name: &str,
+        options: &PublishOptions,
+        overrides: Option<&PublishOptions>,
+    ) -> anyhow::Result<Self> {
+        let topic = overrides
+            .and_then(|o| o.topic.as_deref())
+            .or(options.topic.as_deref())
+            .unwrap_or(DEFAULT_TOPIC);
+        validate_topic(topic, &["base", "device", "collector"], &[])?;
+
+        Ok(Self {
+            topic: topic
+                .replace("{base}", base)
+                .replace("{device}", device_id)
+                .replace("{collector}", name),
+            interval: overrides
+                .and_then(|o| o.interval)
+                .or(options.interval)
+                .unwrap_or(DEFAULT_INTERVAL),
+            qos: overrides
+                .and_then(|o| o.qos)
+                .or(options.qos)
+                .unwrap_or_default()
+                .into(),
+            retain: overrides
+                .and_then(|o| o.retain)
+                .or(options.retain)
+                .unwrap_or_default(),
+            flatten: overrides
+                .and_then(|o| o.flatten)
+                .or(options.flatten)
+                .unwrap_or_default(),
+        })
+    }
+
+    /// The messages to publish for a state
+    pub fn messages(&self, state: &Value) -> Vec<(String, Vec<u8>)> {
+        if self.flatten {
+            let mut result = vec![];
+            flatten(&mut result, self.topic.clone(), state);
+            result
+        } else {
+            vec![(self.topic.clone(), state.to_string().into_bytes())]
+        }
+    }
+}
+
+/// Collect one message per leaf value, strings are published without quotes.
+fn flatten(result: &mut Vec<(String, Vec<u8>)>, topic: String, value: &Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(result, format!("{topic}/{}", segment(key)), value);
+            }
+        }
+        Value::Array(items) => {
+            for (i, value) in items.iter().enumerate() {
+                flatten(result, format!("{topic}/{i}"), value);
+            }
+        }
+        Value::String(value) => result.push((topic, value.clone().into_bytes())),
+        value => result.push((topic, value.to_string().into_bytes())),
+    }
+}
+
+/// Turn a key into a single topic level, keys may be paths (like `/dev/sda1`)
+fn segment(key: &str) -> String {
+    let segment = key.trim_start_matches('/').replace(['/', '+', '#'], "_");
+    if segment.is_empty() {
+        "_".into()
+    } else {
+        segment
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_policy() {
+        let options = PublishOptions {
+            qos: Some(Qos::AtLeastOnce),
+            ..Default::default()
+        };
+        let overrides = PublishOptions {
+            topic: Some("nodered/{device}/{collector}".into()),
+            retain: Some(true),
+            ..Default::default()
+        };
+
+        let policy = Policy::new("resymo", "host", "memory", &options, None).unwrap();
+        assert_eq!(policy.topic, "resymo/host/memory");
+        assert_eq!(policy.qos, rumqttc::QoS::AtLeastOnce);
+        assert!(!policy.retain);
+
+        let policy = Policy::new("resymo", "host", "memory", &options, Some(&overrides)).unwrap();
+        assert_eq!(policy.topic, "nodered/host/memory");
+        assert_eq!(policy.qos, rumqttc::QoS::AtLeastOnce);
+        assert!(policy.retain);
+
+        let invalid = PublishOptions {
+            topic: Some("{base}/{host}/#".into()),
+            ..Default::default()
+        };
+        assert!(Policy::new("resymo", "host", "memory", &invalid, None).is_err());
+
+        assert!(serde_json::from_value::<PublishOptions>(json!({"interval": "0s"})).is_err());
+    }
+
+    #[test]
+    fn test_flatten() {
+        let policy = Policy::new(
+            "resymo",
+            "host",
+            "disk_free",
+            &PublishOptions {
+                flatten: Some(true),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap();
+
+        let messages = policy.messages(&json!({
+            "disks": { "/dev/sda1": { "usage": 0.5, "name": "root" } },
+            "reasons": ["kernel"],
+            "required": true,
+        }));
+        let messages = messages
+            .iter()
+            .map(|(topic, payload)| (topic.as_str(), String::from_utf8_lossy(payload)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                ("resymo/host/disk_free/disks/dev_sda1/name", "root".into()),
+                ("resymo/host/disk_free/disks/dev_sda1/usage", "0.5".into()),
+                ("resymo/host/disk_free/reasons/0", "kernel".into()),
+                ("resymo/host/disk_free/required", "true".into()),
+            ]
+        );
+    }
+}
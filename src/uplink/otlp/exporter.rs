@@ -0,0 +1,149 @@
+//! Sending export requests, over gRPC or HTTP
+
+use super::{Options, Protocol};
+use anyhow::{bail, Context};
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    metrics_service_client::MetricsServiceClient, ExportMetricsPartialSuccess,
+    ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+};
+use prost::Message;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+#[cfg(any(feature = "openssl", feature = "rustls"))]
+use tonic::transport::ClientTlsConfig;
+use tonic::{
+    metadata::MetadataMap,
+    transport::{Channel, Endpoint},
+};
+
+const DEFAULT_GRPC_ENDPOINT: &str = "http://localhost:4317";
+const DEFAULT_HTTP_ENDPOINT: &str = "http://localhost:4318/v1/metrics";
+
+/// Exports metrics using the configured protocol
+pub enum Exporter {
+    Grpc {
+        client: MetricsServiceClient<Channel>,
+        headers: HeaderMap,
+    },
+    Http {
+        client: reqwest::Client,
+        endpoint: String,
+        headers: HeaderMap,
+    },
+}
+
+impl Exporter {
+    pub fn new(options: &Options) -> anyhow::Result<Self> {
+        let headers = options
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                Ok((
+                    HeaderName::try_from(name.to_lowercase())
+                        .with_context(|| format!("Invalid header name: {name}"))?,
+                    HeaderValue::try_from(value)
+                        .with_context(|| format!("Invalid value of header: {name}"))?,
+                ))
+            })
+            .collect::<anyhow::Result<HeaderMap>>()?;
+
+        Ok(match options.protocol {
+            Protocol::Grpc => {
+                let endpoint = options
+                    .endpoint
+                    .as_deref()
+                    .unwrap_or(DEFAULT_GRPC_ENDPOINT)
+                    .to_string();
+                let tls = endpoint.starts_with("https:");
+                let endpoint = Endpoint::from_shared(endpoint)
+                    .context("Invalid endpoint")?
+                    .timeout(options.timeout)
+                    .connect_timeout(options.timeout);
+
+                // gRPC always uses rustls, but only when built with TLS support
+                #[cfg(any(feature = "openssl", feature = "rustls"))]
+                let endpoint = match tls {
+                    true => endpoint
+                        .tls_config(ClientTlsConfig::new().with_native_roots())
+                        .context("Failed to configure TLS")?,
+                    false => endpoint,
+                };
+                #[cfg(not(any(feature = "openssl", feature = "rustls")))]
+                if tls {
+                    bail!("Built without support for TLS, use an http endpoint");
+                }
+
+                // connects with the first export, and reconnects when required
+                Self::Grpc {
+                    client: MetricsServiceClient::new(endpoint.connect_lazy()),
+                    headers,
+                }
+            }
+            Protocol::HttpProtobuf => {
+                let client = reqwest::Client::builder().timeout(options.timeout);
+                // same as for the HTTP server, rustls takes precedence
+                #[cfg(feature = "rustls")]
+                let client = client.use_rustls_tls();
+
+                Self::Http {
+                    client: client.build().context("Failed to create HTTP client")?,
+                    endpoint: options
+                        .endpoint
+                        .as_deref()
+                        .unwrap_or(DEFAULT_HTTP_ENDPOINT)
+                        .to_string(),
+                    headers,
+                }
+            }
+        })
+    }
+
+    pub async fn export(&mut self, request: ExportMetricsServiceRequest) -> anyhow::Result<()> {
+        match self {
+            Self::Grpc { client, headers } => {
+                let mut request = tonic::Request::new(request);
+                *request.metadata_mut() = MetadataMap::from_headers(headers.clone());
+                let response = client.export(request).await?.into_inner();
+                log_rejected(response.partial_success);
+            }
+            Self::Http {
+                client,
+                endpoint,
+                headers,
+            } => {
+                let response = client
+                    .post(endpoint.as_str())
+                    .headers(headers.clone())
+                    .header(CONTENT_TYPE, "application/x-protobuf")
+                    .body(request.encode_to_vec())
+                    .send()
+                    .await?;
+
+                let status = response.status();
+                if !status.is_success() {
+                    let text = response.text().await.unwrap_or_default();
+                    bail!("Failed to export: {status}: {}", text.trim());
+                }
+
+                // an empty body is a valid (full) success too
+                if let Ok(response) = ExportMetricsServiceResponse::decode(response.bytes().await?)
+                {
+                    log_rejected(response.partial_success);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn log_rejected(partial: Option<ExportMetricsPartialSuccess>) {
+    if let Some(partial) = partial {
+        if partial.rejected_data_points > 0 || !partial.error_message.is_empty() {
+            log::warn!(
+                "Collector rejected {} data points: {}",
+                partial.rejected_data_points,
+                partial.error_message
+            );
+        }
+    }
+}
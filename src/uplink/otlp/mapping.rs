@@ -0,0 +1,236 @@
+//! Mapping collector state to metrics, following the OpenTelemetry semantic conventions
+
+use crate::uplink::metrics;
+use serde_json::Value;
+
+/// The kind of instrument a metric is reported as
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Gauge,
+    UpDownCounter,
+}
+
+/// A single value of a metric
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub unit: &'static str,
+    pub kind: Kind,
+    pub attributes: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+impl Sample {
+    fn new(name: impl Into<String>, unit: &'static str, kind: Kind, value: f64) -> Self {
+        Self {
+            name: name.into(),
+            unit,
+            kind,
+            attributes: vec![],
+            value,
+        }
+    }
+
+    fn attribute(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.attributes.push((key, value.into()));
+        self
+    }
+}
+
+/// Map the state of a collector to samples.
+///
+/// Known collectors use the names of the semantic conventions, others are flattened below `resymo.<collector>`.
+pub fn samples(name: &str, state: &Value) -> Vec<Sample> {
+    let number = |value: &Value, key: &str| value.get(key).and_then(Value::as_f64);
+
+    match name {
+        "memory" => {
+            let (Some(total), Some(free), Some(used)) = (
+                number(state, "total"),
+                number(state, "free"),
+                number(state, "used"),
+            ) else {
+                return vec![];
+            };
+
+            let mut result = usage("system.memory", "system.memory.state", used, free);
+            if total > 0.0 {
+                result.push(
+                    Sample::new("system.memory.utilization", "1", Kind::Gauge, used / total)
+                        .attribute("system.memory.state", "used"),
+                );
+            }
+            if let Some(available) = number(state, "available") {
+                result.push(Sample::new(
+                    "system.linux.memory.available",
+                    "By",
+                    Kind::UpDownCounter,
+                    available,
+                ));
+            }
+            result
+        }
+        "swap" => {
+            let (Some(free), Some(used), Some(percentage)) = (
+                number(state, "free"),
+                number(state, "used"),
+                number(state, "percentage"),
+            ) else {
+                return vec![];
+            };
+
+            let mut result = usage("system.paging", "system.paging.state", used, free);
+            result.push(
+                Sample::new("system.paging.utilization", "1", Kind::Gauge, percentage)
+                    .attribute("system.paging.state", "used"),
+            );
+            result
+        }
+        "disk_free" => state
+            .get("disks")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .flat_map(|(device, disk)| {
+                let (Some(total), Some(free), Some(utilization)) = (
+                    number(disk, "total"),
+                    number(disk, "free"),
+                    number(disk, "usage"),
+                ) else {
+                    return vec![];
+                };
+
+                let mut result = usage(
+                    "system.filesystem",
+                    "system.filesystem.state",
+                    total - free,
+                    free,
+                );
+                result.push(Sample::new(
+                    "system.filesystem.utilization",
+                    "1",
+                    Kind::Gauge,
+                    utilization,
+                ));
+                result
+                    .into_iter()
+                    .map(|sample| sample.attribute("system.device", device))
+                    .collect()
+            })
+            .collect(),
+        "load_avg" => [("one", "1m"), ("five", "5m"), ("fifteen", "15m")]
+            .into_iter()
+            .filter_map(|(key, suffix)| {
+                number(state, key).map(|value| {
+                    Sample::new(
+                        format!("system.cpu.load_average.{suffix}"),
+                        "{thread}",
+                        Kind::Gauge,
+                        value,
+                    )
+                })
+            })
+            .collect(),
+        "packages" => ["pending", "security"]
+            .into_iter()
+            .filter_map(|key| {
+                number(state, key).map(|value| {
+                    Sample::new(
+                        format!("resymo.packages.{key}"),
+                        "{package}",
+                        Kind::Gauge,
+                        value,
+                    )
+                })
+            })
+            .collect(),
+        "reboot_required" => state
+            .get("required")
+            .and_then(Value::as_bool)
+            .map(|required| {
+                Sample::new(
+                    "resymo.reboot.required",
+                    "1",
+                    Kind::Gauge,
+                    if required { 1.0 } else { 0.0 },
+                )
+            })
+            .into_iter()
+            .collect(),
+        _ => metrics::metrics("resymo", name, state)
+            .into_iter()
+            .map(|(name, value)| Sample::new(name, "", Kind::Gauge, value))
+            .collect(),
+    }
+}
+
+/// The `<prefix>.usage` metric, split by state
+fn usage(prefix: &str, state: &'static str, used: f64, free: f64) -> Vec<Sample> {
+    let name = format!("{prefix}.usage");
+    vec![
+        Sample::new(&name, "By", Kind::UpDownCounter, used).attribute(state, "used"),
+        Sample::new(&name, "By", Kind::UpDownCounter, free).attribute(state, "free"),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_memory() {
+        let samples = samples(
+            "memory",
+            &json!({"free": 1, "total": 4, "used": 2, "available": 2}),
+        );
+        assert_eq!(
+            samples
+                .iter()
+                .map(|sample| (sample.name.as_str(), sample.value))
+                .collect::<Vec<_>>(),
+            vec![
+                ("system.memory.usage", 2.0),
+                ("system.memory.usage", 1.0),
+                ("system.memory.utilization", 0.5),
+                ("system.linux.memory.available", 2.0),
+            ]
+        );
+        assert_eq!(
+            samples[1].attributes,
+            vec![("system.memory.state", "free".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_disk_free() {
+        let samples = samples(
+            "disk_free",
+            &json!({"disks": {"/dev/sda1": {"free": 1, "total": 4, "usage": 0.75}}}),
+        );
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].name, "system.filesystem.usage");
+        assert_eq!(samples[0].value, 3.0);
+        assert_eq!(
+            samples[2],
+            Sample {
+                name: "system.filesystem.utilization".into(),
+                unit: "1",
+                kind: Kind::Gauge,
+                attributes: vec![("system.device", "/dev/sda1".into())],
+                value: 0.75,
+            }
+        );
+    }
+
+    #[test]
+    fn test_fallback() {
+        assert_eq!(
+            samples("temperature", &json!({"stdout": "42.5\n", "status": 0})),
+            vec![
+                Sample::new("resymo.temperature.status", "", Kind::Gauge, 0.0),
+                Sample::new("resymo.temperature.stdout", "", Kind::Gauge, 42.5),
+            ]
+        );
+    }
+}
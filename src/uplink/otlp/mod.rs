@@ -0,0 +1,479 @@
+//! OpenTelemetry uplink, exporting metrics using OTLP over gRPC or HTTP
+
+mod exporter;
+mod mapping;
+
+use crate::manager::Manager;
+use crate::uplink::{
+    buffer::{self, Buffer, Snapshot},
+    default_device_id,
+};
+use exporter::Exporter;
+use mapping::{Kind, Sample};
+use opentelemetry_proto::tonic::{
+    collector::metrics::v1::ExportMetricsServiceRequest,
+    common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue},
+    metrics::v1::{
+        metric, number_data_point, AggregationTemporality, Gauge, Metric, NumberDataPoint,
+        ResourceMetrics, ScopeMetrics, Sum,
+    },
+    resource::v1::Resource,
+};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::time::MissedTickBehavior;
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// The host name (`host.name` resource attribute). Will default to the value of the `HOSTNAME` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+
+    /// The protocol used for exporting
+    #[serde(default)]
+    pub protocol: Protocol,
+
+    /// The endpoint of the collector.
+    ///
+    /// Defaults to `http://localhost:4317` for gRPC, and `http://localhost:4318/v1/metrics` for HTTP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+
+    /// Additional headers (or gRPC metadata) sent with each request, e.g. for authentication
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// Additional resource attributes
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub resource_attributes: BTreeMap<String, String>,
+
+    /// Interval of collecting and exporting the metrics
+    #[serde(
+        with = "crate::utils::non_zero_duration",
+        default = "default::interval"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub interval: Duration,
+
+    /// Timeout of an export request
+    #[serde(with = "humantime_serde", default = "default::timeout")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub timeout: Duration,
+
+    /// Keep the state on disk while the collector is not reachable, replaying it afterwards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_buffer: Option<buffer::Options>,
+}
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Deserialize,
+    serde::Serialize,
+    schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Protocol {
+    /// OTLP over gRPC
+    #[default]
+    Grpc,
+    /// OTLP over HTTP, using protobuf encoding
+    HttpProtobuf,
+}
+
+mod default {
+    use super::*;
+
+    pub const fn interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub const fn timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+pub async fn run(options: Options, manager: Arc<Manager>) -> anyhow::Result<()> {
+    let resource = resource(&options);
+    let mut exporter = Exporter::new(&options)?;
+    let mut disk_buffer = match options.disk_buffer {
+        Some(options) => Some(Buffer::open(options).await?),
+        None => None,
+    };
+
+    // cumulative sums start with the agent
+    let start = timestamp();
+
+    let mut interval = tokio::time::interval(options.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let state = match manager.collect_all().await {
+            Ok(state) => state,
+            Err(err) => {
+                log::warn!("Failed to collect state: {err}");
+                continue;
+            }
+        };
+
+        let Some(disk_buffer) = &mut disk_buffer else {
+            let request = request(resource.clone(), &state, start, timestamp());
+            if let Err(err) = exporter.export(request).await {
+                log::warn!("Failed to export metrics: {err}");
+            }
+            continue;
+        };
+
+        if let Err(err) = disk_buffer.push(Snapshot::now(state)).await {
+            log::warn!("Failed to buffer state: {err}");
+        }
+        replay(&mut exporter, disk_buffer, &resource, start).await;
+    }
+}
+
+/// Export the buffered snapshots, oldest first, with their original timestamps
+async fn replay(
+    exporter: &mut Exporter,
+    disk_buffer: &mut Buffer,
+    resource: &Resource,
+    start: u64,
+) {
+    let mut sent = 0;
+    for snapshot in disk_buffer.iter() {
+        // snapshots of a previous run predate the start
+        let start = start.min(snapshot.timestamp);
+        let request = request(resource.clone(), &snapshot.state, start, snapshot.timestamp);
+        if let Err(err) = exporter.export(request).await {
+            log::warn!(
+                "Failed to export metrics, keeping {} snapshots: {err}",
+                disk_buffer.len() - sent
+            );
+            break;
+        }
+        sent += 1;
+    }
+
+    if sent > 0 {
+        if let Err(err) = disk_buffer.pop(sent).await {
+            log::warn!("Failed to update buffer: {err}");
+        }
+    }
+}
+
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+fn resource(options: &Options) -> Resource {
+    let host = options.device_id.clone().unwrap_or_else(default_device_id);
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    };
+
+    let mut attributes = BTreeMap::from([
+        ("host.name".to_string(), host),
+        ("os.type".to_string(), os.to_string()),
+        ("service.name".to_string(), "resymo-agent".to_string()),
+        (
+            "service.version".to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        ),
+    ]);
+    attributes.extend(options.resource_attributes.clone());
+
+    Resource {
+        attributes: attributes
+            .into_iter()
+            .map(|(key, value)| attribute(key, value))
+            .collect(),
+        dropped_attributes_count: 0,
+    }
+}
+
+fn attribute(key: impl Into<String>, value: String) -> KeyValue {
+    KeyValue {
+        key: key.into(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value)),
+        }),
+    }
+}
+
+/// Build the export request from the state of all collectors
+fn request(
+    resource: Resource,
+    state: &BTreeMap<String, Value>,
+    start: u64,
+    time: u64,
+) -> ExportMetricsServiceRequest {
+    let mut metrics = Vec::<Metric>::new();
+    let mut index = HashMap::<String, usize>::new();
+
+    for (name, state) in state {
+        for Sample {
+            name,
+            unit,
+            kind,
+            attributes,
+            value,
+        } in mapping::samples(name, state)
+        {
+            let point = NumberDataPoint {
+                attributes: attributes
+                    .into_iter()
+                    .map(|(key, value)| attribute(key, value))
+                    .collect(),
+                start_time_unix_nano: if kind == Kind::Gauge { 0 } else { start },
+                time_unix_nano: time,
+                value: Some(number_data_point::Value::AsDouble(value)),
+                ..Default::default()
+            };
+
+            // data points of the same metric are grouped, e.g. by device or state
+            let i = *index.entry(name.clone()).or_insert_with(|| {
+                metrics.push(Metric {
+                    name,
+                    unit: unit.to_string(),
+                    data: Some(match kind {
+                        Kind::Gauge => metric::Data::Gauge(Gauge::default()),
+                        Kind::UpDownCounter => metric::Data::Sum(Sum {
+                            data_points: vec![],
+                            aggregation_temporality: AggregationTemporality::Cumulative as i32,
+                            is_monotonic: false,
+                        }),
+                    }),
+                    ..Default::default()
+                });
+                metrics.len() - 1
+            });
+
+            match &mut metrics[i].data {
+                Some(metric::Data::Gauge(gauge)) => gauge.data_points.push(point),
+                Some(metric::Data::Sum(sum)) => sum.data_points.push(point),
+                _ => {}
+            }
+        }
+    }
+
+    ExportMetricsServiceRequest {
+        resource_metrics: vec![ResourceMetrics {
+            resource: Some(resource),
+            scope_metrics: vec![ScopeMetrics {
+                scope: Some(InstrumentationScope {
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    ..Default::default()
+                }),
+                metrics,
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use opentelemetry_proto::tonic::collector::metrics::v1::{
+        metrics_service_server::{MetricsService, MetricsServiceServer},
+        ExportMetricsServiceResponse,
+    };
+    use prost::Message;
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    fn options(protocol: Protocol, endpoint: String) -> Options {
+        Options {
+            device_id: Some("my-host".into()),
+            protocol,
+            endpoint: Some(endpoint),
+            headers: BTreeMap::from([("Authorization".into(), "Bearer secret".into())]),
+            resource_attributes: Default::default(),
+            interval: default::interval(),
+            timeout: default::timeout(),
+            disk_buffer: None,
+        }
+    }
+
+    fn state() -> BTreeMap<String, Value> {
+        BTreeMap::from([
+            (
+                "memory".into(),
+                json!({"free": 1, "total": 4, "used": 2, "available": 2}),
+            ),
+            (
+                "disk_free".into(),
+                json!({"disks": {
+                    "/dev/sda1": {"free": 1, "total": 4, "usage": 0.75},
+                    "/dev/sda2": {"free": 3, "total": 4, "usage": 0.25},
+                }}),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_request() {
+        let options = options(Protocol::Grpc, "http://localhost:4317".into());
+        let request = request(resource(&options), &state(), 1, 2);
+
+        let resource = request.resource_metrics[0].resource.as_ref().unwrap();
+        assert!(resource
+            .attributes
+            .contains(&attribute("host.name", "my-host".into())));
+        assert!(resource
+            .attributes
+            .contains(&attribute("service.name", "resymo-agent".into())));
+
+        let metrics = &request.resource_metrics[0].scope_metrics[0].metrics;
+        assert_eq!(
+            metrics.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(),
+            vec![
+                "system.filesystem.usage",
+                "system.filesystem.utilization",
+                "system.memory.usage",
+                "system.memory.utilization",
+                "system.linux.memory.available",
+            ]
+        );
+
+        let Some(metric::Data::Sum(sum)) = &metrics[0].data else {
+            panic!("must be a sum");
+        };
+        assert_eq!(metrics[0].unit, "By");
+        assert!(!sum.is_monotonic);
+        assert_eq!(sum.data_points.len(), 4);
+        assert_eq!(sum.data_points[0].start_time_unix_nano, 1);
+        assert_eq!(sum.data_points[0].time_unix_nano, 2);
+
+        let Some(metric::Data::Gauge(gauge)) = &metrics[1].data else {
+            panic!("must be a gauge");
+        };
+        assert_eq!(gauge.data_points.len(), 2);
+        assert_eq!(
+            gauge.data_points[1].value,
+            Some(number_data_point::Value::AsDouble(0.25))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut data = vec![];
+            let mut buf = [0u8; 4096];
+            // read until the announced body is complete
+            let (head, body) = loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                data.extend_from_slice(&buf[..n]);
+                if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let head = String::from_utf8_lossy(&data[..pos]).to_lowercase();
+                    let length = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length: "))
+                        .and_then(|length| length.trim().parse::<usize>().ok())
+                        .unwrap();
+                    if data.len() >= pos + 4 + length {
+                        break (head, data[pos + 4..pos + 4 + length].to_vec());
+                    }
+                }
+            };
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            (head, body)
+        });
+
+        let options = options(
+            Protocol::HttpProtobuf,
+            format!("http://{address}/v1/metrics"),
+        );
+        let mut exporter = Exporter::new(&options).unwrap();
+        exporter
+            .export(request(resource(&options), &state(), 1, 2))
+            .await
+            .unwrap();
+
+        let (head, body) = server.await.unwrap();
+        assert!(head.starts_with("post /v1/metrics "));
+        assert!(head.contains("content-type: application/x-protobuf"));
+        assert!(head.contains("authorization: bearer secret"));
+
+        let request = ExportMetricsServiceRequest::decode(body.as_slice()).unwrap();
+        assert_eq!(
+            request.resource_metrics[0].scope_metrics[0].metrics.len(),
+            5
+        );
+    }
+
+    struct Service(mpsc::UnboundedSender<tonic::Request<ExportMetricsServiceRequest>>);
+
+    #[tonic::async_trait]
+    impl MetricsService for Service {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportMetricsServiceRequest>,
+        ) -> Result<tonic::Response<ExportMetricsServiceResponse>, tonic::Status> {
+            self.0.send(request).unwrap();
+            Ok(tonic::Response::new(Default::default()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_grpc() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(MetricsServiceServer::new(Service(tx)))
+                .serve_with_incoming(
+                    tonic::transport::server::TcpIncoming::from_listener(listener, true, None)
+                        .unwrap(),
+                ),
+        );
+
+        let options = options(Protocol::Grpc, format!("http://{address}"));
+        let mut exporter = Exporter::new(&options).unwrap();
+        exporter
+            .export(request(resource(&options), &state(), 1, 2))
+            .await
+            .unwrap();
+
+        let request = rx.recv().await.unwrap();
+        assert_eq!(
+            request.metadata().get("authorization").unwrap(),
+            "Bearer secret"
+        );
+        assert_eq!(
+            request.into_inner().resource_metrics[0].scope_metrics[0]
+                .metrics
+                .len(),
+            5
+        );
+    }
+}
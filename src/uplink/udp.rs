@@ -0,0 +1,89 @@
+//! Sending lines over UDP
+
+use anyhow::Context;
+use tokio::net::UdpSocket;
+
+/// Sends lines as datagrams, resolving the address with the first send after an error
+pub struct Sender {
+    address: String,
+    max_packet_size: usize,
+    socket: Option<UdpSocket>,
+}
+
+impl Sender {
+    pub fn new(address: impl Into<String>, max_packet_size: usize) -> Self {
+        Self {
+            address: address.into(),
+            max_packet_size,
+            socket: None,
+        }
+    }
+
+    pub async fn send(&mut self, lines: &[String]) -> anyhow::Result<()> {
+        let result = self.try_send(lines).await;
+        if result.is_err() {
+            // resolve and connect again with the next attempt
+            self.socket = None;
+        }
+        result
+    }
+
+    async fn try_send(&mut self, lines: &[String]) -> anyhow::Result<()> {
+        let socket = match &mut self.socket {
+            Some(socket) => socket,
+            None => {
+                let address = tokio::net::lookup_host(&self.address)
+                    .await?
+                    .next()
+                    .with_context(|| format!("Failed to resolve: {}", self.address))?;
+                let socket = UdpSocket::bind(if address.is_ipv6() {
+                    "[::]:0"
+                } else {
+                    "0.0.0.0:0"
+                })
+                .await?;
+                socket.connect(address).await?;
+                self.socket.insert(socket)
+            }
+        };
+
+        for datagram in datagrams(lines, self.max_packet_size) {
+            socket.send(datagram.as_bytes()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Split lines into datagrams, a line exceeding the maximum size gets sent on its own.
+fn datagrams(lines: &[String], max: usize) -> Vec<String> {
+    let mut result = vec![];
+    let mut current = String::new();
+
+    for line in lines {
+        if !current.is_empty() && current.len() + line.len() + 1 > max {
+            result.push(std::mem::take(&mut current));
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.is_empty() {
+        result.push(current);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_datagrams() {
+        let lines = ["a 1", "b 2", "c 3", "very-long-line 4"].map(String::from);
+        assert_eq!(
+            datagrams(&lines, 8),
+            vec!["a 1\nb 2\n", "c 3\n", "very-long-line 4\n"]
+        );
+    }
+}
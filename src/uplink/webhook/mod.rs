@@ -0,0 +1,542 @@
+//! Webhook uplink, sending snapshots or alert events to HTTP endpoints
+
+mod sender;
+
+pub use sender::Sender;
+
+use crate::manager::Manager;
+use crate::uplink::default_device_id;
+use crate::utils::is_default;
+use anyhow::Context;
+use minijinja::Environment;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::time::MissedTickBehavior;
+
+const DEFAULT_MESSAGE: &str = "{{ alert }} is {{ status }} on {{ device }}";
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// The device ID. Will default to the value of the `HOSTNAME` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+
+    /// The webhooks, by name
+    #[serde(default)]
+    pub hooks: BTreeMap<String, Hook>,
+}
+
+/// A hook, sending requests to an endpoint
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Hook {
+    /// Where to send to
+    #[serde(flatten)]
+    pub endpoint: Endpoint,
+
+    /// A (Jinja) template for the body, defaults to the payload as JSON.
+    ///
+    /// Available variables: `device`, `timestamp`, `state` (the state of the collectors), and for alerts: `alert`,
+    /// `status` (`firing` or `resolved`), `message`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+
+    /// Collectors to include, defaults to all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collectors: Vec<String>,
+
+    /// When to send
+    #[serde(default)]
+    pub trigger: Trigger,
+
+    /// Interval of collecting (and checking for a trigger)
+    #[serde(
+        with = "crate::utils::non_zero_duration",
+        default = "default::interval"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub interval: Duration,
+}
+
+/// An HTTP endpoint, receiving POST requests
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoint {
+    /// The URL to POST to
+    pub url: String,
+
+    /// Additional headers
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// The content type of the body
+    #[serde(default = "default::content_type")]
+    pub content_type: String,
+
+    /// Timeout of a single request
+    #[serde(with = "humantime_serde", default = "default::timeout")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub timeout: Duration,
+
+    /// Sign the body using HMAC-SHA256
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing: Option<Signing>,
+
+    /// Retrying failed requests
+    #[serde(default)]
+    pub retry: Retry,
+}
+
+/// When to send a request
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Trigger {
+    /// Send a snapshot with every interval
+    #[default]
+    Interval,
+    /// Send a snapshot when the state changed
+    Change,
+    /// Send an event when an alert starts or stops firing
+    Alert(AlertTrigger),
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertTrigger {
+    /// The alerts, by name
+    pub alerts: BTreeMap<String, Alert>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    /// A (Jinja) expression, the alert is firing while it is true.
+    ///
+    /// The state of the collectors is available as `state`, e.g. `state.memory.used / state.memory.total > 0.9`.
+    pub condition: String,
+
+    /// A (Jinja) template for the message, defaults to `{{ alert }} is {{ status }} on {{ device }}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+
+    /// Don't send an event when the alert is resolved
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub skip_resolved: bool,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Signing {
+    /// The shared secret
+    pub secret: String,
+
+    /// The header carrying the signature, as `sha256=<hex>`
+    #[serde(default = "default::signature_header")]
+    pub header: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Retry {
+    /// Maximum number of attempts, including the first one
+    #[serde(default = "default::attempts")]
+    pub attempts: u32,
+
+    /// Delay before the first retry, doubled with every further retry
+    #[serde(with = "humantime_serde", default = "default::initial_delay")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub initial_delay: Duration,
+
+    /// Maximum delay between two attempts
+    #[serde(with = "humantime_serde", default = "default::max_delay")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub max_delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            attempts: default::attempts(),
+            initial_delay: default::initial_delay(),
+            max_delay: default::max_delay(),
+        }
+    }
+}
+
+mod default {
+    use super::*;
+
+    pub fn content_type() -> String {
+        "application/json".into()
+    }
+
+    pub fn signature_header() -> String {
+        "X-Signature-256".into()
+    }
+
+    pub const fn interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    pub const fn timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub const fn attempts() -> u32 {
+        3
+    }
+
+    pub const fn initial_delay() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    pub const fn max_delay() -> Duration {
+        Duration::from_secs(60)
+    }
+}
+
+/// The data available to the body template, and sent as JSON by default
+#[derive(Clone, Debug, serde::Serialize)]
+struct Payload<'a> {
+    device: &'a str,
+    timestamp: String,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    event: Option<Event>,
+    state: &'a BTreeMap<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+struct Event {
+    alert: String,
+    status: Status,
+    message: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+enum Status {
+    Firing,
+    Resolved,
+}
+
+pub async fn run(options: Options, manager: Arc<Manager>) -> anyhow::Result<()> {
+    let device_id = options.device_id.unwrap_or_else(default_device_id);
+
+    // an uplink finishing would stop the agent
+    anyhow::ensure!(!options.hooks.is_empty(), "No webhooks configured");
+
+    let hooks = options
+        .hooks
+        .into_iter()
+        .map(|(name, hook)| {
+            let sender = Sender::new(&hook.endpoint)
+                .with_context(|| format!("Failed to set up webhook: {name}"))?;
+            validate(&hook).with_context(|| format!("Invalid template of webhook: {name}"))?;
+            Ok(run_hook(name, hook, sender, &device_id, &manager))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    futures::future::try_join_all(hooks).await?;
+
+    Ok(())
+}
+
+/// Check all templates and expressions of a hook, so that errors show up with the start
+fn validate(hook: &Hook) -> anyhow::Result<()> {
+    let env = Environment::new();
+
+    if let Some(body) = &hook.body {
+        env.template_from_str(body)?;
+    }
+
+    if let Trigger::Alert(trigger) = &hook.trigger {
+        for (name, alert) in &trigger.alerts {
+            env.compile_expression(&alert.condition)
+                .with_context(|| format!("Condition of alert: {name}"))?;
+            if let Some(message) = &alert.message {
+                env.template_from_str(message)
+                    .with_context(|| format!("Message of alert: {name}"))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_hook(
+    name: String,
+    hook: Hook,
+    sender: Sender,
+    device_id: &str,
+    manager: &Manager,
+) -> anyhow::Result<()> {
+    let env = Environment::new();
+
+    // last state sent, for the change trigger
+    let mut last = None;
+    // alerts currently firing
+    let mut firing = BTreeSet::new();
+
+    let mut interval = tokio::time::interval(hook.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let mut state = match manager.collect_all().await {
+            Ok(state) => state,
+            Err(err) => {
+                log::warn!("Failed to collect state: {err}");
+                continue;
+            }
+        };
+        if !hook.collectors.is_empty() {
+            state.retain(|name, _| hook.collectors.contains(name));
+        }
+
+        let events = match &hook.trigger {
+            Trigger::Interval => vec![None],
+            Trigger::Change => {
+                if last.as_ref() == Some(&state) {
+                    vec![]
+                } else {
+                    vec![None]
+                }
+            }
+            Trigger::Alert(trigger) => evaluate(&env, trigger, device_id, &state, &mut firing)
+                .into_iter()
+                .map(Some)
+                .collect(),
+        };
+
+        for event in events {
+            let payload = Payload {
+                device: device_id,
+                timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+                event,
+                state: &state,
+            };
+
+            let body = match render(&env, hook.body.as_deref(), &payload) {
+                Ok(body) => body,
+                Err(err) => {
+                    log::warn!("Failed to render body of webhook '{name}': {err}");
+                    continue;
+                }
+            };
+
+            match sender.send(body).await {
+                Ok(()) => {
+                    log::debug!("Sent webhook: {name}");
+                    last = Some(state.clone());
+                }
+                Err(err) => {
+                    log::warn!("Failed to send webhook '{name}': {err}");
+                    if let Some(event) = &payload.event {
+                        unsent(&mut firing, event);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Revert the change of an alert of an event which failed to be sent, so that the event is
+/// created again with the next evaluation
+fn unsent(firing: &mut BTreeSet<String>, event: &Event) {
+    match event.status {
+        Status::Firing => {
+            firing.remove(&event.alert);
+        }
+        Status::Resolved => {
+            firing.insert(event.alert.clone());
+        }
+    }
+}
+
+/// Evaluate the alerts, returning the events for alerts which started or stopped firing
+fn evaluate(
+    env: &Environment,
+    trigger: &AlertTrigger,
+    device_id: &str,
+    state: &BTreeMap<String, Value>,
+    firing: &mut BTreeSet<String>,
+) -> Vec<Event> {
+    let mut result = vec![];
+
+    for (name, alert) in &trigger.alerts {
+        let active = match env
+            .compile_expression(&alert.condition)
+            .and_then(|expr| expr.eval(minijinja::context! { state }))
+        {
+            Ok(value) => value.is_true(),
+            Err(err) => {
+                log::warn!("Failed to evaluate condition of alert '{name}': {err}");
+                continue;
+            }
+        };
+
+        let status = match (active, firing.contains(name)) {
+            (true, false) => {
+                firing.insert(name.clone());
+                Status::Firing
+            }
+            (false, true) => {
+                firing.remove(name);
+                if alert.skip_resolved {
+                    continue;
+                }
+                Status::Resolved
+            }
+            _ => continue,
+        };
+
+        let message = env
+            .render_str(
+                alert.message.as_deref().unwrap_or(DEFAULT_MESSAGE),
+                minijinja::context! { alert => name, status, device => device_id, state },
+            )
+            .unwrap_or_else(|err| {
+                log::warn!("Failed to render message of alert '{name}': {err}");
+                format!("{name}: {status:?}")
+            });
+
+        result.push(Event {
+            alert: name.clone(),
+            status,
+            message,
+        });
+    }
+
+    result
+}
+
+/// Render the body, or encode the payload as JSON if there is no template
+fn render(env: &Environment, template: Option<&str>, payload: &Payload) -> anyhow::Result<String> {
+    Ok(match template {
+        Some(template) => env.render_str(template, payload)?,
+        None => serde_json::to_string(payload)?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn trigger() -> AlertTrigger {
+        serde_yaml::from_str(
+            r#"
+alerts:
+  memory:
+    condition: "state.memory.used / state.memory.total > 0.9"
+    message: "Memory at {{ (state.memory.used / state.memory.total * 100) | round | int }}%"
+  reboot:
+    condition: "state.reboot_required.required"
+    skipResolved: true
+"#,
+        )
+        .unwrap()
+    }
+
+    fn state(used: u64, reboot: bool) -> BTreeMap<String, Value> {
+        BTreeMap::from([
+            ("memory".into(), json!({"total": 100, "used": used})),
+            ("reboot_required".into(), json!({"required": reboot})),
+        ])
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let env = Environment::new();
+        let trigger = trigger();
+        let mut firing = BTreeSet::new();
+
+        let events = evaluate(&env, &trigger, "my-host", &state(50, false), &mut firing);
+        assert_eq!(events, vec![]);
+
+        let events = evaluate(&env, &trigger, "my-host", &state(95, true), &mut firing);
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    alert: "memory".into(),
+                    status: Status::Firing,
+                    message: "Memory at 95%".into(),
+                },
+                Event {
+                    alert: "reboot".into(),
+                    status: Status::Firing,
+                    message: "reboot is firing on my-host".into(),
+                },
+            ]
+        );
+
+        // still firing, nothing to send
+        let events = evaluate(&env, &trigger, "my-host", &state(96, true), &mut firing);
+        assert_eq!(events, vec![]);
+
+        let events = evaluate(&env, &trigger, "my-host", &state(10, false), &mut firing);
+        assert_eq!(
+            events,
+            vec![Event {
+                alert: "memory".into(),
+                status: Status::Resolved,
+                message: "Memory at 10%".into(),
+            }]
+        );
+        assert!(firing.is_empty());
+    }
+
+    #[test]
+    fn test_unsent() {
+        let env = Environment::new();
+        let trigger = trigger();
+        let mut firing = BTreeSet::new();
+
+        let events = evaluate(&env, &trigger, "my-host", &state(95, false), &mut firing);
+        assert_eq!(events.len(), 1);
+
+        // failed to send, so the next evaluation must create it again
+        unsent(&mut firing, &events[0]);
+        let retry = evaluate(&env, &trigger, "my-host", &state(96, false), &mut firing);
+        assert_eq!(retry.len(), 1);
+        assert_eq!(retry[0].status, Status::Firing);
+
+        let events = evaluate(&env, &trigger, "my-host", &state(10, false), &mut firing);
+        assert_eq!(events[0].status, Status::Resolved);
+        unsent(&mut firing, &events[0]);
+        let retry = evaluate(&env, &trigger, "my-host", &state(10, false), &mut firing);
+        assert_eq!(retry, events);
+    }
+
+    #[test]
+    fn test_render() {
+        let env = Environment::new();
+        let state = state(95, false);
+        let payload = Payload {
+            device: "my-host",
+            timestamp: "2024-01-01T00:00:00Z".into(),
+            event: Some(Event {
+                alert: "memory".into(),
+                status: Status::Firing,
+                message: "Memory \"high\"".into(),
+            }),
+            state: &state,
+        };
+
+        assert_eq!(
+            render(&env, Some(r#"{"text": {{ message | tojson }}}"#), &payload).unwrap(),
+            r#"{"text": "Memory \"high\""}"#
+        );
+
+        let json: Value = serde_json::from_str(&render(&env, None, &payload).unwrap()).unwrap();
+        assert_eq!(json["status"], "firing");
+        assert_eq!(json["state"]["memory"]["used"], 95);
+    }
+}
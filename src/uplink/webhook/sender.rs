@@ -0,0 +1,192 @@
+//! Sending requests, with signing and retries
+
+use super::{Endpoint, Retry, Signing};
+use anyhow::{anyhow, Context};
+use hmac::{Hmac, Mac};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    StatusCode,
+};
+use sha2::Sha256;
+
+/// Sends bodies to the URL of an endpoint
+pub struct Sender {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+    content_type: String,
+    signing: Option<Signing>,
+    retry: Retry,
+}
+
+/// A failed attempt
+enum Failure {
+    /// Worth trying again, e.g. a connection error or an unavailable server
+    Temporary(anyhow::Error),
+    /// Will fail again, e.g. a rejected request
+    Permanent(anyhow::Error),
+}
+
+impl Sender {
+    pub fn new(endpoint: &Endpoint) -> anyhow::Result<Self> {
+        let headers = endpoint
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                Ok((
+                    HeaderName::try_from(name.to_lowercase())
+                        .with_context(|| format!("Invalid header name: {name}"))?,
+                    HeaderValue::try_from(value)
+                        .with_context(|| format!("Invalid value of header: {name}"))?,
+                ))
+            })
+            .collect::<anyhow::Result<HeaderMap>>()?;
+
+        if let Some(signing) = &endpoint.signing {
+            HeaderName::try_from(signing.header.to_lowercase())
+                .with_context(|| format!("Invalid signature header name: {}", signing.header))?;
+        }
+
+        let client = reqwest::Client::builder().timeout(endpoint.timeout);
+        // same as for the HTTP server, rustls takes precedence
+        #[cfg(feature = "rustls")]
+        let client = client.use_rustls_tls();
+
+        Ok(Self {
+            client: client.build().context("Failed to create HTTP client")?,
+            url: endpoint.url.clone(),
+            headers,
+            content_type: endpoint.content_type.clone(),
+            signing: endpoint.signing.clone(),
+            retry: endpoint.retry.clone(),
+        })
+    }
+
+    /// Send the body, retrying temporary failures with an exponential backoff
+    pub async fn send(&self, body: String) -> anyhow::Result<()> {
+        let mut delay = self.retry.initial_delay;
+        let mut attempt = 1;
+
+        loop {
+            match self.try_send(&body).await {
+                Ok(()) => return Ok(()),
+                Err(Failure::Temporary(err)) if attempt < self.retry.attempts => {
+                    log::info!("Attempt {attempt} failed, retrying in {delay:?}: {err}");
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(self.retry.max_delay);
+                    attempt += 1;
+                }
+                Err(Failure::Temporary(err) | Failure::Permanent(err)) => return Err(err),
+            }
+        }
+    }
+
+    async fn try_send(&self, body: &str) -> Result<(), Failure> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .headers(self.headers.clone())
+            .header(CONTENT_TYPE, &self.content_type);
+
+        if let Some(signing) = &self.signing {
+            request = request.header(
+                signing.header.to_lowercase(),
+                format!("sha256={}", sign(&signing.secret, body.as_bytes())),
+            );
+        }
+
+        let response = request
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|err| Failure::Temporary(err.into()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let text = response.text().await.unwrap_or_default();
+        let err = anyhow!("Failed to send: {status}: {}", text.trim());
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            Err(Failure::Temporary(err))
+        } else {
+            Err(Failure::Permanent(err))
+        }
+    }
+}
+
+/// Create the hex encoded HMAC-SHA256 signature of a body
+fn sign(secret: &str, body: &[u8]) -> String {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut requests = vec![];
+            for status in ["503 Service Unavailable", "200 OK"] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                requests.push(String::from_utf8_lossy(&buf[..n]).to_lowercase());
+                stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+            }
+            requests
+        });
+
+        let endpoint: Endpoint = serde_json::from_value(serde_json::json!({
+            "url": format!("http://{address}/hook"),
+            "signing": {"secret": "Jefe"},
+            "retry": {"initialDelay": "10ms"},
+        }))
+        .unwrap();
+        assert_eq!(endpoint.retry.initial_delay, Duration::from_millis(10));
+
+        Sender::new(&endpoint)
+            .unwrap()
+            .send("what do ya want for nothing?".into())
+            .await
+            .unwrap();
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].starts_with("post /hook "));
+        assert!(requests[1].contains("content-type: application/json"));
+        assert!(requests[1].contains(
+            "x-signature-256: sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        ));
+    }
+}
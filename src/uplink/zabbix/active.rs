@@ -0,0 +1,241 @@
+//! Active checks, pushing values to the Zabbix server (or proxy) using the sender protocol
+
+use super::{
+    items::{self, Items, Lookup},
+    protocol, ActiveOptions,
+};
+use crate::manager::Manager;
+use crate::uplink::buffer::{Buffer, Snapshot};
+use anyhow::{bail, Context};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{io::AsyncWriteExt, net::TcpStream, time::MissedTickBehavior};
+
+/// Items sent when none are configured
+const DEFAULT_ITEMS: &[&str] = &[
+    "agent.ping",
+    "vm.memory.size[total]",
+    "vm.memory.size[available]",
+    "vm.memory.size[pused]",
+    "system.swap.size[,free]",
+    "system.swap.size[,pused]",
+    "system.cpu.load[all,avg1]",
+    "system.cpu.load[all,avg5]",
+    "system.cpu.load[all,avg15]",
+];
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct Request<'a> {
+    request: &'static str,
+    data: Vec<Data<'a>>,
+    clock: u64,
+    ns: u32,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct Data<'a> {
+    host: &'a str,
+    key: &'a str,
+    value: String,
+    clock: u64,
+    ns: u32,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+struct Response {
+    response: String,
+    #[serde(default)]
+    info: String,
+}
+
+pub async fn run(
+    options: ActiveOptions,
+    device_id: String,
+    items: Arc<Items>,
+    manager: Arc<Manager>,
+) -> anyhow::Result<()> {
+    let keys = if options.items.is_empty() {
+        DEFAULT_ITEMS.iter().map(|key| key.to_string()).collect()
+    } else {
+        options.items
+    };
+    let lookups = keys
+        .into_iter()
+        .map(|key| {
+            let lookup = items
+                .resolve(&key)
+                .map_err(|err| anyhow::anyhow!("Invalid item '{key}': {err}"))?;
+            Ok((key, lookup))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut disk_buffer = match options.disk_buffer {
+        Some(options) => Some(Buffer::open(options).await?),
+        None => None,
+    };
+
+    let mut interval = tokio::time::interval(options.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let state = match manager.collect_all().await {
+            Ok(state) => state,
+            Err(err) => {
+                log::warn!("Failed to collect state: {err}");
+                continue;
+            }
+        };
+        let snapshot = Snapshot::now(state);
+
+        let Some(disk_buffer) = &mut disk_buffer else {
+            let request = request(data(&lookups, &device_id, &snapshot));
+            if let Err(err) = send(&options.server, &request, options.timeout).await {
+                log::warn!("Failed to send values: {err}");
+            }
+            continue;
+        };
+
+        if let Err(err) = disk_buffer.push(snapshot).await {
+            log::warn!("Failed to buffer state: {err}");
+        }
+
+        // values carry their own clock, so that they are recorded at the time of collecting
+        let mut sent = 0;
+        for snapshot in disk_buffer.iter() {
+            let request = request(data(&lookups, &device_id, snapshot));
+            if let Err(err) = send(&options.server, &request, options.timeout).await {
+                log::warn!(
+                    "Failed to send values, keeping {} snapshots: {err}",
+                    disk_buffer.len() - sent
+                );
+                break;
+            }
+            sent += 1;
+        }
+        if sent > 0 {
+            if let Err(err) = disk_buffer.pop(sent).await {
+                log::warn!("Failed to update buffer: {err}");
+            }
+        }
+    }
+}
+
+/// Evaluate the items, for the state of a snapshot
+fn data<'a>(
+    lookups: &'a [(String, Lookup)],
+    device_id: &'a str,
+    snapshot: &Snapshot,
+) -> Vec<Data<'a>> {
+    let (clock, ns) = clock(snapshot.timestamp);
+
+    lookups
+        .iter()
+        .filter_map(|(key, lookup)| {
+            let state = match lookup {
+                Lookup::Static(_) => None,
+                Lookup::Collector { name, .. } => snapshot.state.get(name),
+            };
+            match items::evaluate(lookup, state) {
+                Ok(value) => Some(Data {
+                    host: device_id,
+                    key,
+                    value,
+                    clock,
+                    ns,
+                }),
+                Err(err) => {
+                    log::debug!("Skipping item '{key}': {err}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// A request, with the current time, which the server uses to correct the clock of the values
+fn request(data: Vec<Data<'_>>) -> Request<'_> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    Request {
+        request: "sender data",
+        data,
+        clock: now.as_secs(),
+        ns: now.subsec_nanos(),
+    }
+}
+
+/// Split nanoseconds since the UNIX epoch into seconds and nanoseconds
+fn clock(timestamp: u64) -> (u64, u32) {
+    (
+        timestamp / 1_000_000_000,
+        (timestamp % 1_000_000_000) as u32,
+    )
+}
+
+async fn send(server: &str, request: &Request<'_>, timeout: Duration) -> anyhow::Result<()> {
+    tokio::time::timeout(timeout, send_request(server, request))
+        .await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("Timeout sending values")))
+}
+
+async fn send_request(server: &str, request: &Request<'_>) -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect(server)
+        .await
+        .with_context(|| format!("Failed to connect to: {server}"))?;
+
+    stream
+        .write_all(&protocol::encode(&serde_json::to_vec(request)?))
+        .await?;
+
+    let response: Response = serde_json::from_slice(&protocol::read(&mut stream).await?)
+        .context("Failed to decode response")?;
+    if response.response != "success" {
+        bail!("Server rejected values: {}", response.info);
+    }
+
+    log::debug!("Sent values: {}", response.info);
+    if let Some(failed) = failed(&response.info).filter(|failed| *failed > 0) {
+        // e.g. no matching trapper item on the server
+        log::warn!(
+            "Server failed to process {failed} values: {}",
+            response.info
+        );
+    }
+
+    Ok(())
+}
+
+/// Extract the number of failed values from the info (`processed: 1; failed: 0; total: 1; …`)
+fn failed(info: &str) -> Option<u64> {
+    info.split(';')
+        .find_map(|part| part.trim().strip_prefix("failed:"))
+        .and_then(|failed| failed.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_failed() {
+        assert_eq!(
+            failed("processed: 1; failed: 2; total: 3; seconds spent: 0.000055"),
+            Some(2)
+        );
+        assert_eq!(failed(""), None);
+    }
+
+    #[test]
+    fn test_clock() {
+        assert_eq!(
+            clock(1_700_000_000_123_456_789),
+            (1_700_000_000, 123_456_789)
+        );
+    }
+}
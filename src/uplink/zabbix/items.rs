@@ -0,0 +1,329 @@
+//! Resolving item keys to collector fields
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// A path into the state of a collector
+type Path = Vec<String>;
+
+/// How an item is looked up
+#[derive(Clone, Debug, PartialEq)]
+pub enum Lookup {
+    /// A value not depending on a collector
+    Static(String),
+    /// A value derived from the state of a collector
+    Collector { name: String, expr: Expr },
+}
+
+/// Deriving a value from the state of a collector
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    /// The value of a field
+    Field(Path),
+    /// The difference of two fields, `a - b`
+    Difference(Path, Path),
+    /// The percentage of one field, relative to another one, `a / b * 100`
+    Percentage(Path, Path),
+    /// A field, scaled by a factor
+    Scaled(Path, f64),
+    /// An expression on a disk of `disk_free`, found by its mount point or device name
+    Disk(String, Box<Expr>),
+}
+
+/// Resolves item keys, first using the configured mapping, then the built-in keys
+pub struct Items {
+    device_id: String,
+    mapping: BTreeMap<String, String>,
+}
+
+impl Items {
+    pub fn new(device_id: String, mapping: BTreeMap<String, String>) -> Self {
+        Self { device_id, mapping }
+    }
+
+    pub fn resolve(&self, key: &str) -> Result<Lookup, String> {
+        let (name, params) = parse_key(key)?;
+
+        if let Some(field) = self.mapping.get(key) {
+            return Ok(field_lookup(field, &params));
+        }
+        if let Some(field) = self.mapping.get(&format!("{name}[*]")) {
+            return Ok(field_lookup(field, &params));
+        }
+
+        self.builtin(&name, &params)
+    }
+
+    fn builtin(&self, name: &str, params: &[String]) -> Result<Lookup, String> {
+        let param = |n: usize| params.get(n).map(String::as_str).unwrap_or_default();
+        let path = |segments: &[&str]| segments.iter().map(|s| s.to_string()).collect::<Path>();
+        let collector = |name: &str, expr| Lookup::Collector {
+            name: name.to_string(),
+            expr,
+        };
+        let unsupported = |what: &str| Err(format!("Unsupported {what} for: {name}"));
+
+        Ok(match name {
+            "agent.ping" => Lookup::Static("1".into()),
+            "agent.hostname" => Lookup::Static(self.device_id.clone()),
+            "agent.version" => Lookup::Static(env!("CARGO_PKG_VERSION").into()),
+
+            // vm.memory.size[<mode>]
+            "vm.memory.size" => collector(
+                "memory",
+                match param(0) {
+                    "" | "total" => Expr::Field(path(&["total"])),
+                    mode @ ("free" | "used" | "available") => Expr::Field(path(&[mode])),
+                    "pused" => Expr::Percentage(path(&["used"]), path(&["total"])),
+                    "pavailable" => Expr::Percentage(path(&["available"]), path(&["total"])),
+                    _ => return unsupported("mode"),
+                },
+            ),
+
+            // system.swap.size[<device>,<type>]
+            "system.swap.size" => {
+                if !matches!(param(0), "" | "all") {
+                    return unsupported("device");
+                }
+                collector(
+                    "swap",
+                    match param(1) {
+                        "" | "free" => Expr::Field(path(&["free"])),
+                        mode @ ("total" | "used") => Expr::Field(path(&[mode])),
+                        "pfree" => Expr::Percentage(path(&["free"]), path(&["total"])),
+                        "pused" => Expr::Percentage(path(&["used"]), path(&["total"])),
+                        _ => return unsupported("type"),
+                    },
+                )
+            }
+
+            // system.cpu.load[<cpu>,<mode>]
+            "system.cpu.load" => {
+                if !matches!(param(0), "" | "all") {
+                    return unsupported("cpu");
+                }
+                collector(
+                    "load_avg",
+                    Expr::Field(path(&[match param(1) {
+                        "" | "avg1" => "one",
+                        "avg5" => "five",
+                        "avg15" => "fifteen",
+                        _ => return unsupported("mode"),
+                    }])),
+                )
+            }
+
+            // vfs.fs.size[fs,<mode>], using the mount point, or the name of the device
+            "vfs.fs.size" => {
+                let fs = param(0);
+                if fs.is_empty() {
+                    return unsupported("file system");
+                }
+                let field = |field: &str| path(&[field]);
+                collector(
+                    "disk_free",
+                    Expr::Disk(
+                        fs.to_string(),
+                        Box::new(match param(1) {
+                            "" | "total" => Expr::Field(field("total")),
+                            "free" => Expr::Field(field("free")),
+                            "used" => Expr::Difference(field("total"), field("free")),
+                            "pfree" => Expr::Percentage(field("free"), field("total")),
+                            "pused" => Expr::Scaled(field("usage"), 100.0),
+                            _ => return unsupported("mode"),
+                        }),
+                    ),
+                )
+            }
+
+            _ => return Err(format!("Unsupported item key: {name}")),
+        })
+    }
+}
+
+/// Create a lookup from a mapped field (`<collector>.<field>…`), replacing `$1`…`$9` with the parameters of the key
+fn field_lookup(field: &str, params: &[String]) -> Lookup {
+    let mut segments = field.split('.').map(|segment| {
+        let mut segment = segment.to_string();
+        // replace backwards, so that `$1` doesn't match the start of `$10`
+        for (n, param) in params.iter().enumerate().rev() {
+            segment = segment.replace(&format!("${}", n + 1), param);
+        }
+        segment
+    });
+
+    Lookup::Collector {
+        name: segments.next().unwrap_or_default(),
+        expr: Expr::Field(segments.collect()),
+    }
+}
+
+/// Parse an item key (`name[param1,"param 2"]`) into its name and parameters
+pub fn parse_key(key: &str) -> Result<(String, Vec<String>), String> {
+    let Some((name, rest)) = key.split_once('[') else {
+        return Ok((key.to_string(), vec![]));
+    };
+    let Some(rest) = rest.strip_suffix(']') else {
+        return Err(format!("Invalid item key: {key}"));
+    };
+
+    let mut params = vec![];
+    let mut chars = rest.chars().peekable();
+    loop {
+        while chars.next_if_eq(&' ').is_some() {}
+
+        let mut param = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('\\') if chars.next_if_eq(&'"').is_some() => param.push('"'),
+                    Some('"') => break,
+                    Some(c) => param.push(c),
+                    None => return Err(format!("Unterminated quoted parameter: {key}")),
+                }
+            }
+            while chars.next_if_eq(&' ').is_some() {}
+            if !matches!(chars.peek(), None | Some(',')) {
+                return Err(format!("Invalid quoted parameter: {key}"));
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                param.push(c);
+            }
+        }
+        params.push(param);
+
+        if chars.next().is_none() {
+            break;
+        }
+    }
+
+    Ok((name.to_string(), params))
+}
+
+/// Evaluate a lookup, using the state of the collector (if any)
+pub fn evaluate(lookup: &Lookup, state: Option<&Value>) -> Result<String, String> {
+    let (name, expr) = match lookup {
+        Lookup::Static(value) => return Ok(value.clone()),
+        Lookup::Collector { name, expr } => (name, expr),
+    };
+    let state = state.ok_or_else(|| format!("Unknown collector: {name}"))?;
+
+    evaluate_expr(name, expr, state)
+}
+
+fn evaluate_expr(name: &str, expr: &Expr, state: &Value) -> Result<String, String> {
+    let get = |path: &Path| {
+        path.iter()
+            .try_fold(state, |value, segment| value.get(segment))
+            .ok_or_else(|| format!("Unknown field: {name}.{}", path.join(".")))
+    };
+    let number = |path: &Path| {
+        get(path).and_then(|value| {
+            value
+                .as_f64()
+                .ok_or_else(|| format!("Not a number: {name}.{}", path.join(".")))
+        })
+    };
+
+    Ok(match expr {
+        Expr::Field(path) => match get(path)? {
+            Value::String(value) => value.clone(),
+            Value::Bool(value) => if *value { "1" } else { "0" }.to_string(),
+            Value::Null => return Err(format!("No value: {name}.{}", path.join("."))),
+            value => value.to_string(),
+        },
+        Expr::Difference(a, b) => (number(a)? - number(b)?).to_string(),
+        Expr::Percentage(a, b) => {
+            let total = number(b)?;
+            if total == 0.0 {
+                "0".to_string()
+            } else {
+                (number(a)? / total * 100.0).to_string()
+            }
+        }
+        Expr::Scaled(path, factor) => (number(path)? * factor).to_string(),
+        Expr::Disk(fs, expr) => {
+            let disk = disk(state, fs).ok_or_else(|| format!("Unknown file system: {fs}"))?;
+            return evaluate_expr(name, expr, disk);
+        }
+    })
+}
+
+/// Find a disk of `disk_free` by its mount point, like Zabbix does, falling back to the device name
+fn disk<'a>(state: &'a Value, fs: &str) -> Option<&'a Value> {
+    let disks = state.get("disks")?.as_object()?;
+    disks
+        .values()
+        .find(|disk| disk.get("mount_point").and_then(Value::as_str) == Some(fs))
+        .or_else(|| disks.get(fs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("agent.ping"), Ok(("agent.ping".into(), vec![])));
+        assert_eq!(
+            parse_key(r#"vfs.fs.size[ "/dev/sda1", pused]"#),
+            Ok((
+                "vfs.fs.size".into(),
+                vec!["/dev/sda1".into(), "pused".into()]
+            ))
+        );
+        assert_eq!(
+            parse_key(r#"a[,"x,\"y\""]"#),
+            Ok(("a".into(), vec!["".into(), r#"x,"y""#.into()]))
+        );
+        assert!(parse_key("a[b").is_err());
+        assert!(parse_key(r#"a["b"c]"#).is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let items = Items::new(
+            "my-host".into(),
+            BTreeMap::from([
+                ("custom.temp".into(), "temperature.stdout".into()),
+                ("custom.disk[*]".into(), "disk_free.disks.$1.$2".into()),
+            ]),
+        );
+
+        let state = json!({"disks": {
+            "/dev/sda1": {"free": 1, "total": 4, "usage": 0.75, "mount_point": "/"},
+            "/dev/sdb1": {"free": 3, "total": 4, "usage": 0.25, "mount_point": "/dev/sda1"},
+        }});
+        let value = |key: &str| {
+            items
+                .resolve(key)
+                .and_then(|lookup| evaluate(&lookup, Some(&state)))
+        };
+
+        assert_eq!(value("agent.hostname"), Ok("my-host".into()));
+        assert_eq!(value("vfs.fs.size[/,used]"), Ok("3".into()));
+        assert_eq!(value("vfs.fs.size[/,pused]"), Ok("75".into()));
+        assert_eq!(value("vfs.fs.size[/,pfree]"), Ok("25".into()));
+        // the mount point takes precedence over the device name
+        assert_eq!(value("vfs.fs.size[/dev/sda1,free]"), Ok("3".into()));
+        assert_eq!(value("vfs.fs.size[/dev/sdb1,free]"), Ok("3".into()));
+        assert_eq!(value("custom.disk[/dev/sda1,free]"), Ok("1".into()));
+        assert!(value("vfs.fs.size[/home]").is_err());
+        assert!(value("vfs.fs.size[/dev/sda1,inodes]").is_err());
+        assert!(value("system.run[reboot]").is_err());
+
+        assert_eq!(
+            items.resolve("custom.temp"),
+            Ok(Lookup::Collector {
+                name: "temperature".into(),
+                expr: Expr::Field(vec!["stdout".into()])
+            })
+        );
+        assert_eq!(
+            evaluate(&items.resolve("custom.temp").unwrap(), None),
+            Err("Unknown collector: temperature".into())
+        );
+    }
+}
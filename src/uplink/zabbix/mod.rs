@@ -0,0 +1,134 @@
+//! Zabbix uplink, acting as a passive agent, and as an active sender
+
+mod active;
+mod items;
+mod passive;
+mod protocol;
+
+use crate::manager::Manager;
+use crate::uplink::{buffer, default_device_id};
+use items::Items;
+use std::{
+    collections::BTreeMap, future::Future, net::IpAddr, pin::Pin, sync::Arc, time::Duration,
+};
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Options {
+    /// The host name, as configured in Zabbix. Will default to the value of the `HOSTNAME` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+
+    /// Mapping of item keys to collector fields (`<collector>.<field>…`), e.g. `temperature.stdout`.
+    ///
+    /// A key ending with `[*]` matches any parameters, which can be referenced using `$1`…`$9`. Mapped keys take
+    /// precedence over the built-in keys (`agent.*`, `vm.memory.size`, `system.swap.size`, `system.cpu.load`,
+    /// `vfs.fs.size`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub items: BTreeMap<String, String>,
+
+    /// Act as passive agent, answering requests of the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passive: Option<PassiveOptions>,
+
+    /// Act as active sender, pushing values to the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<ActiveOptions>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PassiveOptions {
+    /// The address to listen on
+    #[serde(default = "default::bind")]
+    pub bind: String,
+
+    /// Addresses of servers (or proxies) allowed to connect. Required, like `Server=` of `zabbix_agentd`.
+    pub allowed_hosts: Vec<IpAddr>,
+
+    /// Timeout of handling a request
+    #[serde(with = "humantime_serde", default = "default::passive_timeout")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub timeout: Duration,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveOptions {
+    /// The address of the server (or proxy), e.g. `zabbix.example.com:10051`
+    pub server: String,
+
+    /// Item keys to send, using trapper items on the server. Defaults to memory, swap, and load values.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<String>,
+
+    /// Interval of collecting and sending the values
+    #[serde(
+        with = "crate::utils::non_zero_duration",
+        default = "default::interval"
+    )]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub interval: Duration,
+
+    /// Timeout of connecting and sending
+    #[serde(with = "humantime_serde", default = "default::active_timeout")]
+    #[schemars(schema_with = "crate::utils::humantime_duration")]
+    pub timeout: Duration,
+
+    /// Keep the values on disk while the server is not reachable, sending them afterwards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_buffer: Option<buffer::Options>,
+}
+
+mod default {
+    use super::*;
+
+    pub fn bind() -> String {
+        "0.0.0.0:10050".into()
+    }
+
+    pub const fn passive_timeout() -> Duration {
+        Duration::from_secs(3)
+    }
+
+    pub const fn interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    pub const fn active_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+pub async fn run(options: Options, manager: Arc<Manager>) -> anyhow::Result<()> {
+    let device_id = options.device_id.unwrap_or_else(default_device_id);
+    let items = Arc::new(Items::new(device_id.clone(), options.items));
+
+    let mut tasks = Vec::<Pin<Box<dyn Future<Output = anyhow::Result<()>>>>>::new();
+    if let Some(passive) = options.passive {
+        tasks.push(Box::pin(passive::run(
+            passive,
+            items.clone(),
+            manager.clone(),
+        )));
+    }
+    if let Some(active) = options.active {
+        log::info!("Sending active checks to: {}", active.server);
+        tasks.push(Box::pin(active::run(
+            active,
+            device_id,
+            items.clone(),
+            manager.clone(),
+        )));
+    }
+
+    // an uplink finishing would stop the agent
+    anyhow::ensure!(
+        !tasks.is_empty(),
+        "Neither passive nor active mode configured"
+    );
+
+    futures::future::try_join_all(tasks).await?;
+
+    Ok(())
+}
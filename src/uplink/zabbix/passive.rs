@@ -0,0 +1,109 @@
+//! Passive checks, answering requests of the Zabbix server
+
+use super::{
+    items::{self, Items, Lookup},
+    protocol, PassiveOptions,
+};
+use crate::manager::Manager;
+use anyhow::{bail, Context};
+use std::sync::Arc;
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+};
+
+pub async fn run(
+    options: PassiveOptions,
+    items: Arc<Items>,
+    manager: Arc<Manager>,
+) -> anyhow::Result<()> {
+    if options.allowed_hosts.is_empty() {
+        bail!("Passive checks require at least one allowed host");
+    }
+
+    let listener = TcpListener::bind(&options.bind)
+        .await
+        .with_context(|| format!("Failed to bind: {}", options.bind))?;
+    log::info!("Listening for passive checks on: {}", options.bind);
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                log::warn!("Failed to accept connection: {err}");
+                continue;
+            }
+        };
+
+        let ip = peer.ip().to_canonical();
+        if !options.allowed_hosts.contains(&ip) {
+            log::warn!("Rejected connection from: {peer}");
+            continue;
+        }
+
+        let items = items.clone();
+        let manager = manager.clone();
+        let timeout = options.timeout;
+        tokio::spawn(async move {
+            match tokio::time::timeout(timeout, handle(stream, &items, &manager)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => log::info!("Failed to handle request from {peer}: {err}"),
+                Err(_) => log::info!("Timeout handling request from: {peer}"),
+            }
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, items: &Items, manager: &Manager) -> anyhow::Result<()> {
+    let request = protocol::read(&mut stream).await?;
+    let key = String::from_utf8_lossy(&request);
+    let key = key.trim();
+
+    log::debug!("Passive check: {key}");
+
+    let response = if key.starts_with('{') {
+        // JSON requests (Zabbix 7) fall back to plain keys, when answered like by an older agent
+        format!("{}\0Unsupported item key.", protocol::NOT_SUPPORTED)
+    } else {
+        value(items, manager, key)
+            .await
+            .unwrap_or_else(|err| format!("{}\0{err}", protocol::NOT_SUPPORTED))
+    };
+
+    stream
+        .write_all(&protocol::encode(response.as_bytes()))
+        .await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+async fn value(items: &Items, manager: &Manager, key: &str) -> Result<String, String> {
+    let lookup = items.resolve(key)?;
+
+    let state = match &lookup {
+        Lookup::Static(_) => None,
+        Lookup::Collector { name, .. } => manager
+            .collect_one(name)
+            .await
+            .map_err(|err| err.to_string())?,
+    };
+
+    items::evaluate(&lookup, state.as_ref())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_allowed_hosts() {
+        // like `Server=` of zabbix_agentd, allowed hosts are required
+        assert!(serde_json::from_value::<PassiveOptions>(json!({})).is_err());
+
+        let options: PassiveOptions = serde_json::from_value(json!({"allowedHosts": []})).unwrap();
+        let items = Arc::new(Items::new("host".into(), Default::default()));
+        assert!(run(options, items, Arc::new(Manager::new())).await.is_err());
+    }
+}
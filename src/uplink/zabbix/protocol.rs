@@ -0,0 +1,121 @@
+//! The Zabbix protocol, framing data with a `ZBXD` header
+
+use anyhow::{bail, Context};
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const MAGIC: &[u8; 4] = b"ZBXD";
+const FLAG_PROTOCOL: u8 = 0x01;
+const FLAG_COMPRESSED: u8 = 0x02;
+const FLAG_LARGE: u8 = 0x04;
+
+/// Maximum size of data we accept
+const MAX_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Prefix of a reply, for an item which is not supported
+pub const NOT_SUPPORTED: &str = "ZBX_NOTSUPPORTED";
+
+/// Frame data with the protocol header
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len() + 13);
+    result.extend_from_slice(MAGIC);
+    result.push(FLAG_PROTOCOL);
+    result.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    result.extend_from_slice(&0u32.to_le_bytes());
+    result.extend_from_slice(data);
+    result
+}
+
+/// Read a message, either framed, or (for older servers) a plain line of text
+pub async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
+    let mut magic = [0u8; 4];
+    let mut n = 0;
+    while n < magic.len() {
+        match reader.read(&mut magic[n..]).await? {
+            0 => break,
+            read => n += read,
+        }
+    }
+
+    if &magic[..n] != MAGIC {
+        // a plain line of text, until the end of line or stream
+        let mut data = magic[..n].to_vec();
+        let mut buf = [0u8; 1024];
+        while n > 0 && !data.contains(&b'\n') && (data.len() as u64) < MAX_SIZE {
+            n = reader.read(&mut buf).await?;
+            data.extend_from_slice(&buf[..n]);
+        }
+        if let Some(end) = data.iter().position(|c| *c == b'\n') {
+            data.truncate(end);
+        }
+        return Ok(data);
+    }
+
+    let flags = reader.read_u8().await?;
+    let (length, reserved) = if flags & FLAG_LARGE != 0 {
+        (reader.read_u64_le().await?, reader.read_u64_le().await?)
+    } else {
+        (
+            reader.read_u32_le().await? as u64,
+            reader.read_u32_le().await? as u64,
+        )
+    };
+
+    if length > MAX_SIZE {
+        bail!("Message too large: {length} bytes");
+    }
+
+    let mut data = vec![0u8; length as usize];
+    reader
+        .read_exact(&mut data)
+        .await
+        .context("Failed to read message")?;
+
+    if flags & FLAG_COMPRESSED != 0 {
+        // the reserved field carries the uncompressed size
+        if reserved > MAX_SIZE {
+            bail!("Message too large: {reserved} bytes");
+        }
+        let mut uncompressed = Vec::with_capacity(reserved as usize);
+        ZlibDecoder::new(data.as_slice())
+            .take(MAX_SIZE)
+            .read_to_end(&mut uncompressed)
+            .context("Failed to decompress message")?;
+        data = uncompressed;
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_read() {
+        let framed = encode(b"agent.ping");
+        assert_eq!(&framed[..9], b"ZBXD\x01\x0a\x00\x00\x00");
+        assert_eq!(read(&mut framed.as_slice()).await.unwrap(), b"agent.ping");
+
+        assert_eq!(
+            read(&mut &b"agent.ping\n"[..]).await.unwrap(),
+            b"agent.ping"
+        );
+        assert_eq!(read(&mut &b"ab"[..]).await.unwrap(), b"ab");
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"agent.version").unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut framed = b"ZBXD\x03".to_vec();
+        framed.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        framed.extend_from_slice(&13u32.to_le_bytes());
+        framed.extend_from_slice(&compressed);
+        assert_eq!(
+            read(&mut framed.as_slice()).await.unwrap(),
+            b"agent.version"
+        );
+    }
+}
@@ -22,3 +22,53 @@ pub(crate) fn humantime_duration(
     }));
     schema.into()
 }
+
+/// A value which may be zero, rejected by [`non_zero_duration`].
+pub(crate) trait IsZero {
+    fn is_zero(&self) -> bool;
+}
+
+impl IsZero for std::time::Duration {
+    fn is_zero(&self) -> bool {
+        std::time::Duration::is_zero(self)
+    }
+}
+
+impl IsZero for Option<std::time::Duration> {
+    fn is_zero(&self) -> bool {
+        self.is_some_and(|duration| duration.is_zero())
+    }
+}
+
+/// Like `humantime_serde`, but rejecting a zero duration. For the periods of intervals, which
+/// must not be zero.
+pub(crate) mod non_zero_duration {
+    use super::IsZero;
+    use serde::{de::Error, Deserialize, Deserializer};
+    use std::time::Duration;
+
+    pub use humantime_serde::serialize;
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: IsZero,
+        humantime_serde::Serde<T>: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let value: T = humantime_serde::deserialize(deserializer)?;
+        if value.is_zero() {
+            return Err(D::Error::custom("duration must not be zero"));
+        }
+        Ok(value)
+    }
+
+    /// Parse a command line argument
+    #[cfg_attr(not(any(feature = "openssl", feature = "rustls")), allow(dead_code))]
+    pub fn parse(value: &str) -> Result<Duration, String> {
+        match humantime::parse_duration(value) {
+            Ok(duration) if duration.is_zero() => Err("duration must not be zero".to_string()),
+            Ok(duration) => Ok(duration),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}